@@ -1,15 +1,29 @@
+use std::env;
 use std::process::Command;
 
-// TODO: Works only on Bash shells
 fn main() {
+    // With `runtime-shaders` the GLSL sources are compiled when the pipeline is built.
+    if env::var_os("CARGO_FEATURE_RUNTIME_SHADERS").is_some() {
+        return;
+    }
+
     println!("cargo:rerun-if-changed=shaders/shader.frag");
     println!("cargo:rerun-if-changed=shaders/shader.vert");
+    println!("cargo:rerun-if-changed=shaders/mesh.frag");
+    println!("cargo:rerun-if-changed=shaders/mesh.vert");
 
+    // TODO: Works only on Bash shells
     if cfg!(windows) {
+        println!("cargo:warning=Skipping shaders/compile.sh on Windows; compile the shaders manually or enable `runtime-shaders`");
         return;
-    } else {
-        Command::new("shaders/compile.sh")
-            .output()
-            .expect("Failed to run compile script");
+    }
+
+    match Command::new("shaders/compile.sh").output() {
+        Ok(output) if output.status.success() => {}
+        Ok(output) => println!(
+            "cargo:warning=shaders/compile.sh failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ),
+        Err(err) => println!("cargo:warning=Failed to run shaders/compile.sh: {}", err),
     }
 }
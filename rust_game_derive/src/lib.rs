@@ -0,0 +1,96 @@
+//! Derive macros for `rust_game`.
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{parse_macro_input, Data, DeriveInput, Error, Fields, LitInt};
+
+/// Implements `rust_game::utility::vertex::Vertex` for a struct with named fields.
+///
+/// Every field needs a `#[location(N)]` attribute matching the `layout(location = N)` of the
+/// vertex shader input; its Vulkan format comes from the field type through `VertexFormat`.
+///
+/// ```ignore
+/// #[repr(C)]
+/// #[derive(Clone, Copy, Vertex)]
+/// struct ColoredVertex {
+///     #[location(0)]
+///     pos: [f32; 2],
+///     #[location(1)]
+///     color: [f32; 3],
+/// }
+/// ```
+#[proc_macro_derive(Vertex, attributes(location))]
+pub fn derive_vertex(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+
+    expand_vertex(&input)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+fn expand_vertex(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => {
+                return Err(Error::new_spanned(
+                    &input.ident,
+                    "Vertex can only be derived for structs with named fields",
+                ))
+            }
+        },
+        _ => {
+            return Err(Error::new_spanned(
+                &input.ident,
+                "Vertex can only be derived for structs",
+            ))
+        }
+    };
+
+    let mut locations: Vec<u32> = vec![];
+    let mut attributes = vec![];
+    for field in fields {
+        let field_name = field.ident.as_ref().expect("named fields have identifiers");
+        let field_type = &field.ty;
+
+        let location_attr = field
+            .attrs
+            .iter()
+            .find(|attr| attr.path().is_ident("location"))
+            .ok_or_else(|| {
+                Error::new_spanned(field_name, "vertex field is missing #[location(N)]")
+            })?;
+        let location = location_attr
+            .parse_args::<LitInt>()?
+            .base10_parse::<u32>()?;
+        if locations.contains(&location) {
+            return Err(Error::new_spanned(
+                location_attr,
+                format!("location {} is used by another field", location),
+            ));
+        }
+        locations.push(location);
+
+        attributes.push(quote! {
+            ::rust_game::utility::vertex::VertexInputAttributeDescription {
+                location: #location,
+                binding: 0,
+                format: <#field_type as ::rust_game::utility::vertex::VertexFormat>::FORMAT,
+                offset: ::core::mem::offset_of!(Self, #field_name) as u32,
+            }
+        });
+    }
+
+    let name = &input.ident;
+    let (impl_generics, type_generics, where_clause) = input.generics.split_for_impl();
+
+    Ok(quote! {
+        impl #impl_generics ::rust_game::utility::vertex::Vertex for #name #type_generics #where_clause {
+            fn attribute_descriptions(
+            ) -> ::std::vec::Vec<::rust_game::utility::vertex::VertexInputAttributeDescription> {
+                ::std::vec![#(#attributes),*]
+            }
+        }
+    })
+}
@@ -4,3 +4,5 @@ SCRIPTPATH="$( cd "$(dirname "$0")" >/dev/null 2>&1 ; pwd -P )"
 
 glslc $SCRIPTPATH/shader.vert -o $SCRIPTPATH/vert.spv
 glslc $SCRIPTPATH/shader.frag -o $SCRIPTPATH/frag.spv
+glslc $SCRIPTPATH/mesh.vert -o $SCRIPTPATH/mesh_vert.spv
+glslc $SCRIPTPATH/mesh.frag -o $SCRIPTPATH/mesh_frag.spv
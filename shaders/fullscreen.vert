@@ -0,0 +1,10 @@
+#version 450
+
+layout(location = 0) flat out float shadertoyFlipY;
+
+// Draws one triangle that covers the whole viewport; no vertex buffer is bound.
+void main() {
+    vec2 uv = vec2(float((gl_VertexIndex << 1) & 2), float(gl_VertexIndex & 2));
+    gl_Position = vec4(uv * 2.0 - 1.0, 0.0, 1.0);
+    shadertoyFlipY = 1.0;
+}
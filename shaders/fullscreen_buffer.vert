@@ -0,0 +1,10 @@
+#version 450
+
+layout(location = 0) flat out float shadertoyFlipY;
+
+// Same as fullscreen.vert, for Shadertoy buffers which are not flipped vertically.
+void main() {
+    vec2 uv = vec2(float((gl_VertexIndex << 1) & 2), float(gl_VertexIndex & 2));
+    gl_Position = vec4(uv * 2.0 - 1.0, 0.0, 1.0);
+    shadertoyFlipY = 0.0;
+}
@@ -0,0 +1,24 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+// 0 shades normally, 1 shows texture coordinates, 2 shows the vertex color without texture.
+// Set from shaders/specialization.cfg.
+layout(constant_id = 0) const int DEBUG_VIEW = 0;
+
+layout(binding = 1) uniform texture2D texImage;
+layout(binding = 2) uniform sampler texSampler;
+
+layout(location = 0) in vec4 fragColor;
+layout(location = 1) in vec2 fragTexCoord;
+
+layout(location = 0) out vec4 outColor;
+
+void main() {
+    if (DEBUG_VIEW == 1) {
+        outColor = vec4(fragTexCoord, 0.0, 1.0);
+    } else if (DEBUG_VIEW == 2) {
+        outColor = fragColor;
+    } else {
+        outColor = fragColor * texture(sampler2D(texImage, texSampler), fragTexCoord);
+    }
+}
@@ -0,0 +1,28 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+layout(binding = 0) uniform UniformBufferObject {
+    mat4 model;
+    mat4 view;
+    mat4 proj;
+    float u_time;
+} ubo;
+
+// Per draw, see MeshPushConstants.
+layout(push_constant) uniform PushConstants {
+    mat4 transform;
+    vec4 baseColor;
+} push;
+
+layout(location = 0) in vec4 inPosition;
+layout(location = 1) in vec4 inColor;
+layout(location = 2) in vec2 inTexCoord;
+
+layout(location = 0) out vec4 fragColor;
+layout(location = 1) out vec2 fragTexCoord;
+
+void main() {
+    gl_Position = ubo.proj * ubo.view * ubo.model * push.transform * inPosition;
+    fragColor = inColor * push.baseColor;
+    fragTexCoord = inTexCoord;
+}
@@ -2,9 +2,14 @@
 #extension GL_ARB_separate_shader_objects : enable
 
 layout(binding = 0) uniform UniformBufferObject {
+    mat4 model;
+    mat4 view;
+    mat4 proj;
     float u_time;
 } ubo;
-layout(binding = 1) uniform sampler2D texSampler;
+
+layout(binding = 1) uniform texture2D texImage;
+layout(binding = 2) uniform sampler texSampler;
 
 layout(location = 0) in vec4 fragColor;
 layout(location = 1) in vec2 fragTexCoord;
@@ -13,5 +18,5 @@ layout(location = 0) out vec4 outColor;
 
 void main() {
     vec3 color = vec3(fragColor.x, fragColor.y, abs(sin(ubo.u_time)));
-    outColor = vec4(color, 1.0);
+    outColor = vec4(color, 1.0) * texture(sampler2D(texImage, texSampler), fragTexCoord);
 }
@@ -0,0 +1,14 @@
+
+// 1.0 for the image pass, 0.0 for offscreen buffers (see fullscreen.vert).
+layout(location = 0) flat in float shadertoyFlipY;
+layout(location = 0) out vec4 shadertoyFragColor;
+
+void main() {
+    // Shadertoy puts the origin in the bottom-left corner, Vulkan in the top-left one.
+    // Buffers are stored upside down instead, so that texture coordinates computed from
+    // fragCoord / iResolution address the same texel they were rendered to.
+    float y = mix(gl_FragCoord.y, iResolution.y - gl_FragCoord.y, shadertoyFlipY);
+    vec4 color = vec4(0.0, 0.0, 0.0, 1.0);
+    mainImage(color, vec2(gl_FragCoord.x, y));
+    shadertoyFragColor = color;
+}
@@ -0,0 +1,14 @@
+// Fading trail: blends the previous frame of this buffer with a moving dot.
+void mainImage(out vec4 fragColor, in vec2 fragCoord)
+{
+    vec2 uv = fragCoord / iResolution.xy;
+    vec4 previous = texture(iChannel0, uv);
+
+    vec2 center = iResolution.xy * (0.5 + 0.35 * vec2(cos(iTime), sin(iTime * 1.3)));
+    if (iMouse.z > 0.0) {
+        center = iMouse.xy;
+    }
+    float spot = smoothstep(12.0, 8.0, length(fragCoord - center));
+
+    fragColor = max(previous * 0.97, vec4(spot));
+}
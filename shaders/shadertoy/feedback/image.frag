@@ -0,0 +1,7 @@
+void mainImage(out vec4 fragColor, in vec2 fragCoord)
+{
+    float trail = texture(iChannel0, fragCoord / iResolution.xy).r;
+    vec3 col = trail * (0.5 + 0.5 * cos(iTime + vec3(0.0, 2.0, 4.0)));
+
+    fragColor = vec4(col, 1.0);
+}
@@ -0,0 +1,21 @@
+#version 450
+
+layout(binding = 0) uniform ShadertoyUniforms {
+    vec3 iResolution;
+    float iTime;
+    vec4 iMouse;
+    vec4 iDate;
+    float iTimeDelta;
+    int iFrame;
+};
+
+layout(binding = 1) uniform sampler iChannelSampler;
+layout(binding = 2) uniform texture2D iChannel0Texture;
+layout(binding = 3) uniform texture2D iChannel1Texture;
+layout(binding = 4) uniform texture2D iChannel2Texture;
+layout(binding = 5) uniform texture2D iChannel3Texture;
+#define iChannel0 sampler2D(iChannel0Texture, iChannelSampler)
+#define iChannel1 sampler2D(iChannel1Texture, iChannelSampler)
+#define iChannel2 sampler2D(iChannel2Texture, iChannelSampler)
+#define iChannel3 sampler2D(iChannel3Texture, iChannelSampler)
+
@@ -1,11 +1,24 @@
+#![allow(clippy::too_many_arguments)]
+
+use rust_game::error::{Error, Result, VkResultExt};
 use rust_game::utility::{
+    allocator::{Allocation, MemoryAllocator},
+    camera::Camera,
     constants,
-    constants::{DEVICE_EXTENSIONS, MAX_FRAMES_IN_FLIGHT, VALIDATION},
-    debug,
+    constants::{DEVICE_EXTENSIONS, MAX_FRAMES_IN_FLIGHT},
+    debug::{self, DebugMarker, ValidationInfo},
+    debug_messenger::DebugMessenger,
+    device_selection::DeviceSelector,
+    headless::HeadlessRenderer,
+    multipass::Multipass,
+    pipeline_cache,
     program_proc::ProgramProc,
-    share,
+    shadertoy::{PassesDescription, ShadertoyInput},
+    share::{self, pipeline_builder::GraphicsPipelineBuilder},
+    specialization::SpecializationConfig,
     structures::{
-        QueueFamilyIndices, SurfaceStuff, UniformBufferObject, VertexV2, RECT_INDICES_DATA,
+        DrawCommand, DrawItem, MeshDraw, MeshPushConstants, QueueFamilyIndices, ShadertoyUniforms,
+        SurfaceStuff, TextureSource, UniformBufferObject, VertexV2, VertexV3, RECT_INDICES_DATA,
         RECT_VERTICES_DATA,
     },
     traits::VulkanApp,
@@ -14,10 +27,31 @@ use rust_game::utility::{
 
 use ash::version::{DeviceV1_0, InstanceV1_0};
 use ash::vk; // Vulkan API
+use cgmath::{Deg, Matrix4, Point3, Vector3};
+use std::path::{Path, PathBuf};
 use std::ptr;
+use std::sync::Arc;
 
 // Constants
-const WINDOW_TITLE: &'static str = "Rectangle Test";
+const WINDOW_TITLE: &str = "Rectangle Test";
+
+/// What the window renders, picked from the command line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum RenderMode {
+    Rectangle,
+    /// A textured OBJ or glTF model seen through a perspective camera.
+    Mesh,
+    /// Shadertoy passes on fullscreen triangles.
+    Shadertoy,
+}
+
+/// Pipeline state that depends on the device, fixed when the app starts.
+#[derive(Debug, Clone, Copy)]
+struct PipelineSettings {
+    msaa_samples: vk::SampleCountFlags,
+    min_sample_shading: Option<f32>,
+    max_push_constants_size: u32,
+}
 
 struct VulkanAppImpl {
     window: winit::window::Window,
@@ -28,10 +62,14 @@ struct VulkanAppImpl {
     surface: vk::SurfaceKHR,
     debug_utils_loader: ash::extensions::ext::DebugUtils,
     debug_messenger: vk::DebugUtilsMessengerEXT,
+    is_validation_enabled: bool,
+    // Referenced by the debug messenger, so dropped after the instance is destroyed.
+    messenger: Arc<DebugMessenger>,
 
     physical_device: vk::PhysicalDevice,
-    memory_properties: vk::PhysicalDeviceMemoryProperties,
+    allocator: MemoryAllocator,
     device: ash::Device, // Logical Device
+    debug_marker: DebugMarker,
 
     queue_family: QueueFamilyIndices,
     graphics_queue: vk::Queue,
@@ -47,34 +85,54 @@ struct VulkanAppImpl {
 
     render_pass: vk::RenderPass,
     ubo_layout: vk::DescriptorSetLayout,
+    pipeline_cache: vk::PipelineCache,
     pipeline_layout: vk::PipelineLayout,
     graphics_pipeline: vk::Pipeline,
+    vert_shader_path: PathBuf,
+    frag_shader_path: PathBuf,
+    // Re-read with the shaders when set, so constants can be tweaked while running.
+    specialization_path: Option<PathBuf>,
+    specialization: SpecializationConfig,
 
     depth_image: vk::Image,
     depth_image_view: vk::ImageView,
-    depth_image_memory: vk::DeviceMemory,
-
-    // _mip_levels: u32,
-    // texture_image: vk::Image,
-    // texture_image_memory: vk::DeviceMemory,
-    // texture_image_view: vk::ImageView,
-    // texture_sampler: vk::Sampler,
-    _vertices: Vec<VertexV2>,
+    depth_image_memory: Allocation,
+
+    pipeline_settings: PipelineSettings,
+    // Multisampled color attachment, null with a single sample.
+    color_image: vk::Image,
+    color_image_view: vk::ImageView,
+    color_image_memory: Allocation,
+
+    _mip_levels: u32,
+    texture_image: vk::Image,
+    texture_image_memory: Allocation,
+    texture_image_view: vk::ImageView,
+    texture_sampler: vk::Sampler,
     indices: Vec<u32>,
+    // One per mesh of the model in `RenderMode::Mesh`.
+    mesh_draws: Vec<MeshDraw>,
 
     vertex_buffer: vk::Buffer,
-    vertex_buffer_memory: vk::DeviceMemory,
+    vertex_buffer_memory: Allocation,
     index_buffer: vk::Buffer,
-    index_buffer_memory: vk::DeviceMemory,
+    index_buffer_memory: Allocation,
 
+    mode: RenderMode,
     uniform_transform: UniformBufferObject,
+    camera: Camera,
+    // Set in `RenderMode::Shadertoy`.
+    shadertoy: Option<ShadertoyInput>,
+    passes: Option<PassesDescription>,
+    multipass: Option<Multipass>,
     uniform_buffers: Vec<vk::Buffer>,
-    uniform_buffers_memory: Vec<vk::DeviceMemory>,
+    uniform_buffers_memory: Vec<Allocation>,
 
     descriptor_pool: vk::DescriptorPool,
     descriptor_sets: Vec<vk::DescriptorSet>,
 
     command_pool: vk::CommandPool,
+    // One per frame in flight, recorded again by every `draw_frame`.
     command_buffers: Vec<vk::CommandBuffer>,
 
     image_available_semaphores: Vec<vk::Semaphore>,
@@ -86,7 +144,47 @@ struct VulkanAppImpl {
 }
 
 impl VulkanAppImpl {
-    pub fn new(event_loop: &winit::event_loop::EventLoop<()>) -> VulkanAppImpl {
+    pub fn new(
+        event_loop: &winit::event_loop::EventLoop<()>,
+        shadertoy_path: Option<PathBuf>,
+        model_path: Option<PathBuf>,
+        texture_path: Option<PathBuf>,
+        requested_samples: u32,
+        min_sample_shading: Option<f32>,
+        specialization_path: Option<PathBuf>,
+        gpu_selector: Option<DeviceSelector>,
+        validation: &ValidationInfo,
+    ) -> Result<VulkanAppImpl> {
+        let passes = match shadertoy_path {
+            Some(path) => Some(PassesDescription::load(&path)?),
+            None => None,
+        };
+        let specialization = match &specialization_path {
+            Some(path) => SpecializationConfig::load(path)?,
+            None => SpecializationConfig::default(),
+        };
+        let mode = if passes.is_some() {
+            RenderMode::Shadertoy
+        } else if model_path.is_some() {
+            RenderMode::Mesh
+        } else {
+            RenderMode::Rectangle
+        };
+        let (vert_shader_path, frag_shader_path) = match (&passes, mode) {
+            (Some(passes), _) => (
+                PathBuf::from(constants::FULLSCREEN_VERT_SHADER_PATH),
+                passes.image.shader_path.clone(),
+            ),
+            (None, RenderMode::Mesh) => (
+                PathBuf::from(constants::MESH_VERT_SHADER_PATH),
+                PathBuf::from(constants::MESH_FRAG_SHADER_PATH),
+            ),
+            (None, _) => (
+                PathBuf::from(constants::VERT_SHADER_PATH),
+                PathBuf::from(constants::FRAG_SHADER_PATH),
+            ),
+        };
+
         let window = window::init_window(
             event_loop,
             WINDOW_TITLE,
@@ -94,134 +192,106 @@ impl VulkanAppImpl {
             constants::WINDOW_HEIGHT,
         );
 
-        let entry = ash::Entry::new().unwrap();
-        let instance = share::create_instance(
-            &entry,
-            WINDOW_TITLE,
-            VALIDATION.is_enable,
-            &VALIDATION.required_validation_layers.to_vec(),
-        );
+        let entry = ash::Entry::new()?;
+        let messenger = Arc::new(DebugMessenger::from_validation(validation));
+        let (instance, validation) =
+            share::create_instance(&entry, WINDOW_TITLE, validation, &messenger)?;
         let (debug_utils_loader, debug_messenger) =
-            debug::setup_debug_utils(VALIDATION.is_enable, &entry, &instance);
-        let surface_stuff = share::create_surface(
+            match debug::setup_debug_utils(&validation, &messenger, &entry, &instance) {
+                Ok(debug_utils) => debug_utils,
+                Err(err) => {
+                    unsafe { instance.destroy_instance(None) };
+                    return Err(err);
+                }
+            };
+        // Until the app owns the instance and its `Drop` takes over, failures have to release
+        // what was created so far themselves.
+        let destroy_instance = || unsafe {
+            if validation.is_enable {
+                debug_utils_loader.destroy_debug_utils_messenger(debug_messenger, None);
+            }
+            instance.destroy_instance(None);
+        };
+        let surface_stuff = match share::create_surface(
             &entry,
             &instance,
             &window,
             constants::WINDOW_WIDTH,
             constants::WINDOW_HEIGHT,
-        );
-        let physical_device =
-            share::pick_physical_device(&instance, &surface_stuff, &DEVICE_EXTENSIONS);
-        let physical_device_memory_properties =
-            unsafe { instance.get_physical_device_memory_properties(physical_device) };
-        let (logical_device, queue_family) = share::create_logical_device(
+        ) {
+            Ok(surface_stuff) => surface_stuff,
+            Err(err) => {
+                destroy_instance();
+                return Err(err);
+            }
+        };
+        let device = share::pick_physical_device(
             &instance,
-            physical_device,
-            &VALIDATION,
+            Some(&surface_stuff),
             &DEVICE_EXTENSIONS,
-            &surface_stuff,
-        );
+            gpu_selector.as_ref(),
+        )
+        .and_then(|physical_device| {
+            share::create_logical_device(
+                &instance,
+                physical_device,
+                &validation,
+                &DEVICE_EXTENSIONS,
+                Some(&surface_stuff),
+            )
+            .map(|(device, queue_family)| (physical_device, device, queue_family))
+        });
+        let (physical_device, logical_device, queue_family) = match device {
+            Ok(device) => device,
+            Err(err) => {
+                unsafe {
+                    surface_stuff
+                        .surface_loader
+                        .destroy_surface(surface_stuff.surface, None)
+                };
+                destroy_instance();
+                return Err(err);
+            }
+        };
+        let allocator = MemoryAllocator::new(&instance, physical_device);
+        let debug_marker = DebugMarker::new(&debug_utils_loader, &logical_device);
         let graphics_queue =
             unsafe { logical_device.get_device_queue(queue_family.graphics_family.unwrap(), 0) };
         let present_queue =
             unsafe { logical_device.get_device_queue(queue_family.present_family.unwrap(), 0) };
-        let swapchain_stuff = share::create_swapchain(
-            &instance,
-            &logical_device,
-            physical_device,
-            &window,
-            &surface_stuff,
-            &queue_family,
-        );
-        let swapchain_imageviews = share::pipeline::create_image_views(
-            &logical_device,
-            swapchain_stuff.swapchain_format,
-            &swapchain_stuff.swapchain_images,
-        );
-        let render_pass = share::pipeline::create_render_pass(
-            &instance,
-            &logical_device,
-            physical_device,
-            swapchain_stuff.swapchain_format,
-        );
-        let ubo_layout = share::pipeline::create_descriptor_set_layout(&logical_device);
-        let (graphics_pipeline, pipeline_layout) = share::pipeline::create_graphics_pipeline(
-            &logical_device,
-            render_pass,
-            swapchain_stuff.swapchain_extent,
-            ubo_layout,
-            &VertexV2::get_binding_descriptions(),
-            &VertexV2::get_attribute_descriptions(),
+        let msaa_samples = share::pipeline::clamp_sample_count(
+            requested_samples,
+            share::pipeline::supported_sample_counts(&instance, physical_device),
         );
-        let command_pool = share::pipeline::create_command_pool(&logical_device, &queue_family);
-        let (depth_image, depth_image_view, depth_image_memory) =
-            share::pipeline::create_depth_resources(
-                &instance,
-                &logical_device,
-                physical_device,
-                command_pool,
-                graphics_queue,
-                swapchain_stuff.swapchain_extent,
-                &physical_device_memory_properties,
+        if msaa_samples.as_raw() != requested_samples {
+            eprintln!(
+                "{}x MSAA is not supported, using {}x",
+                requested_samples,
+                msaa_samples.as_raw()
             );
-        let swapchain_framebuffers = share::pipeline::create_framebuffers(
-            &logical_device,
-            render_pass,
-            &swapchain_imageviews,
-            depth_image_view,
-            swapchain_stuff.swapchain_extent,
-        );
-        VulkanAppImpl::check_mipmap_support(&instance, physical_device, vk::Format::R8G8B8A8_UNORM);
-        let (vertex_buffer, vertex_buffer_memory) = share::pipeline::create_vertex_buffer(
-            &instance,
-            &logical_device,
-            physical_device,
-            command_pool,
-            graphics_queue,
-            &RECT_VERTICES_DATA,
-        );
-        let (index_buffer, index_buffer_memory) = share::pipeline::create_index_buffer(
-            &instance,
-            &logical_device,
-            physical_device,
-            command_pool,
-            graphics_queue,
-            &RECT_INDICES_DATA,
-        );
-        let (uniform_buffers, uniform_buffers_memory) = share::pipeline::create_uniform_buffers(
-            &logical_device,
-            &physical_device_memory_properties,
-            swapchain_stuff.swapchain_images.len(),
-        );
-        let descriptor_pool = share::pipeline::create_descriptor_pool(
-            &logical_device,
-            swapchain_stuff.swapchain_images.len(),
-        );
-        let descriptor_sets = share::pipeline::create_descriptor_sets(
-            &logical_device,
-            descriptor_pool,
-            ubo_layout,
-            &uniform_buffers,
-            Option::None,
-            swapchain_stuff.swapchain_images.len(),
-        );
-        let command_buffers = share::pipeline::create_command_buffers(
-            &logical_device,
-            command_pool,
-            graphics_pipeline,
-            &swapchain_framebuffers,
-            render_pass,
-            swapchain_stuff.swapchain_extent,
-            vertex_buffer,
-            index_buffer,
-            pipeline_layout,
-            &descriptor_sets,
-            RECT_INDICES_DATA.len() as u32,
-        );
-        let sync_objects =
-            share::pipeline::create_sync_objects(&logical_device, MAX_FRAMES_IN_FLIGHT);
+        }
+        let is_sample_shading_supported =
+            unsafe { instance.get_physical_device_features(physical_device) }.sample_rate_shading
+                == vk::TRUE;
+        let min_sample_shading = match min_sample_shading {
+            Some(_) if !is_sample_shading_supported => {
+                eprintln!("Sample shading is not supported, ignoring --sample-shading");
+                None
+            }
+            min_sample_shading => min_sample_shading,
+        };
+        let pipeline_settings = PipelineSettings {
+            msaa_samples,
+            min_sample_shading,
+            max_push_constants_size: unsafe {
+                instance.get_physical_device_properties(physical_device)
+            }
+            .limits
+            .max_push_constants_size,
+        };
+        let swapchain_loader = ash::extensions::khr::Swapchain::new(&instance, &logical_device);
 
-        VulkanAppImpl {
+        let mut vulkan_app = VulkanAppImpl {
             window,
 
             _entry: entry,
@@ -230,61 +300,568 @@ impl VulkanAppImpl {
             surface_loader: surface_stuff.surface_loader,
             debug_utils_loader,
             debug_messenger,
+            is_validation_enabled: validation.is_enable,
+            messenger,
 
             physical_device,
-            memory_properties: physical_device_memory_properties,
+            allocator,
             device: logical_device,
+            debug_marker,
 
             queue_family,
             graphics_queue,
             present_queue,
 
-            swapchain_loader: swapchain_stuff.swapchain_loader,
-            swapchain: swapchain_stuff.swapchain,
-            swapchain_format: swapchain_stuff.swapchain_format,
-            swapchain_images: swapchain_stuff.swapchain_images,
-            swapchain_extent: swapchain_stuff.swapchain_extent,
-            swapchain_imageviews,
-            swapchain_framebuffers,
+            swapchain_loader,
+            swapchain: vk::SwapchainKHR::null(),
+            swapchain_format: vk::Format::UNDEFINED,
+            swapchain_images: vec![],
+            swapchain_extent: vk::Extent2D::default(),
+            swapchain_imageviews: vec![],
+            swapchain_framebuffers: vec![],
+
+            render_pass: vk::RenderPass::null(),
+            ubo_layout: vk::DescriptorSetLayout::null(),
+            pipeline_cache: vk::PipelineCache::null(),
+            pipeline_layout: vk::PipelineLayout::null(),
+            graphics_pipeline: vk::Pipeline::null(),
+            vert_shader_path,
+            frag_shader_path,
+            specialization_path,
+            specialization,
+
+            depth_image: vk::Image::null(),
+            depth_image_view: vk::ImageView::null(),
+            depth_image_memory: Allocation::null(),
+
+            pipeline_settings,
+            color_image: vk::Image::null(),
+            color_image_view: vk::ImageView::null(),
+            color_image_memory: Allocation::null(),
+
+            _mip_levels: 0,
+            texture_image: vk::Image::null(),
+            texture_image_memory: Allocation::null(),
+            texture_image_view: vk::ImageView::null(),
+            texture_sampler: vk::Sampler::null(),
+            indices: vec![],
+            mesh_draws: vec![],
+
+            vertex_buffer: vk::Buffer::null(),
+            vertex_buffer_memory: Allocation::null(),
+            index_buffer: vk::Buffer::null(),
+            index_buffer_memory: Allocation::null(),
+
+            mode,
+            uniform_transform: UniformBufferObject::default(),
+            // Looks at the origin from above, with Z up like the OBJ assets.
+            camera: Camera {
+                eye: Point3::new(2.0, 2.0, 2.0),
+                target: Point3::new(0.0, 0.0, 0.0),
+                up: Vector3::new(0.0, 0.0, 1.0),
+                fov_y: Deg(45.0),
+                z_near: 0.1,
+                z_far: 10.0,
+            },
+            shadertoy: if mode == RenderMode::Shadertoy {
+                Some(ShadertoyInput::new())
+            } else {
+                None
+            },
+            passes,
+            multipass: None,
+            uniform_buffers: vec![],
+            uniform_buffers_memory: vec![],
+
+            descriptor_pool: vk::DescriptorPool::null(),
+            descriptor_sets: vec![],
+
+            command_pool: vk::CommandPool::null(),
+            command_buffers: vec![],
+
+            image_available_semaphores: vec![],
+            render_finished_semaphores: vec![],
+            inflight_fences: vec![],
+            current_frame: 0,
+
+            is_framebuffer_resized: false,
+        };
+
+        // Handles stay null until they are created, so `Drop` releases exactly what was
+        // created before a failure.
+        vulkan_app.create_resources(model_path, texture_path)?;
+
+        Ok(vulkan_app)
+    }
+
+    fn create_resources(
+        &mut self,
+        model_path: Option<PathBuf>,
+        texture_path: Option<PathBuf>,
+    ) -> Result<()> {
+        self.create_swapchain()?;
+        self.render_pass = share::pipeline::create_render_pass(
+            &self.instance,
+            &self.device,
+            self.physical_device,
+            self.swapchain_format,
+            vk::ImageLayout::PRESENT_SRC_KHR,
+            self.pipeline_settings.msaa_samples,
+        )?;
+        self.ubo_layout = if self.mode == RenderMode::Shadertoy {
+            share::pipeline::create_shadertoy_descriptor_set_layout(&self.device)?
+        } else {
+            share::pipeline::create_descriptor_set_layout(&self.device)?
+        };
+        self.pipeline_cache = pipeline_cache::load_pipeline_cache(
+            &self.instance,
+            &self.device,
+            self.physical_device,
+            &pipeline_cache::default_cache_path(),
+        )?;
+        let (graphics_pipeline, pipeline_layout) = VulkanAppImpl::create_graphics_pipeline(
+            &self.device,
+            self.render_pass,
+            self.pipeline_cache,
+            self.ubo_layout,
+            &self.vert_shader_path,
+            &self.frag_shader_path,
+            &self.specialization,
+            self.mode,
+            self.pipeline_settings,
+        )?;
+        self.graphics_pipeline = graphics_pipeline;
+        self.pipeline_layout = pipeline_layout;
+        self.command_pool = share::pipeline::create_command_pool(&self.device, &self.queue_family)?;
+        self.command_buffers = share::pipeline::allocate_command_buffers(
+            &self.device,
+            self.command_pool,
+            MAX_FRAMES_IN_FLIGHT,
+        )?;
+        self.create_attachments()?;
+        VulkanAppImpl::check_mipmap_support(
+            &self.instance,
+            self.physical_device,
+            vk::Format::R8G8B8A8_UNORM,
+        )?;
+        let model = match &model_path {
+            Some(model_path) => Some(share::load_model(model_path)?),
+            None => None,
+        };
+        // Shadertoy passes sample their buffers instead, so they only get the white texture.
+        // A model defaults to the diffuse texture of its first textured material, then to the
+        // image next to it with the same name, e.g. viking_room.png.
+        let texture = if self.mode == RenderMode::Shadertoy {
+            None
+        } else if let Some(texture_path) = texture_path {
+            Some(TextureSource::Path(texture_path))
+        } else {
+            let material_texture = model.as_ref().and_then(|model| {
+                model
+                    .materials
+                    .iter()
+                    .find_map(|material| material.diffuse_texture.clone())
+            });
+            let default_texture_path = match &model_path {
+                Some(model_path) => model_path.with_extension("png"),
+                None => PathBuf::from(constants::TEXTURE_PATH),
+            };
+            material_texture.or_else(|| {
+                Some(default_texture_path)
+                    .filter(|path| path.exists())
+                    .map(TextureSource::Path)
+            })
+        };
+        let (texture_image, texture_image_memory, mip_levels) = match &texture {
+            Some(TextureSource::Path(texture_path)) => share::pipeline::create_texture_image(
+                &self.device,
+                self.command_pool,
+                self.graphics_queue,
+                &mut self.allocator,
+                texture_path,
+            )?,
+            Some(TextureSource::Rgba {
+                width,
+                height,
+                pixels,
+            }) => share::pipeline::create_texture_image_from_pixels(
+                &self.device,
+                self.command_pool,
+                self.graphics_queue,
+                &mut self.allocator,
+                *width,
+                *height,
+                pixels,
+            )?,
+            None => share::pipeline::create_white_texture_image(
+                &self.device,
+                self.command_pool,
+                self.graphics_queue,
+                &mut self.allocator,
+            )?,
+        };
+        self._mip_levels = mip_levels;
+        self.texture_image = texture_image;
+        self.texture_image_memory = texture_image_memory;
+        self.texture_image_view = share::pipeline::create_texture_image_view(
+            &self.device,
+            self.texture_image,
+            mip_levels,
+        )?;
+        self.texture_sampler = share::pipeline::create_texture_sampler(&self.device, mip_levels)?;
+        self.mesh_draws = match &model {
+            Some(model) => model
+                .meshes
+                .iter()
+                .map(|mesh| {
+                    let [red, green, blue] = mesh
+                        .material_index
+                        .map_or([1.0; 3], |index| model.materials[index].diffuse_color);
+                    MeshDraw {
+                        first_index: mesh.first_index,
+                        index_count: mesh.index_count,
+                        push_constants: MeshPushConstants {
+                            base_color: [red, green, blue, 1.0],
+                            ..MeshPushConstants::default()
+                        },
+                    }
+                })
+                .collect(),
+            None => vec![],
+        };
+        let ((vertex_buffer, vertex_buffer_memory), indices) = match model {
+            Some(model) => {
+                let vertex_buffer = share::pipeline::create_vertex_buffer(
+                    &self.device,
+                    &mut self.allocator,
+                    self.command_pool,
+                    self.graphics_queue,
+                    &model.vertices,
+                )?;
+                (vertex_buffer, model.indices)
+            }
+            None => {
+                let vertex_buffer = share::pipeline::create_vertex_buffer(
+                    &self.device,
+                    &mut self.allocator,
+                    self.command_pool,
+                    self.graphics_queue,
+                    &RECT_VERTICES_DATA,
+                )?;
+                (vertex_buffer, RECT_INDICES_DATA.to_vec())
+            }
+        };
+        self.vertex_buffer = vertex_buffer;
+        self.vertex_buffer_memory = vertex_buffer_memory;
+        self.indices = indices;
+        let (index_buffer, index_buffer_memory) = share::pipeline::create_index_buffer(
+            &self.device,
+            &mut self.allocator,
+            self.command_pool,
+            self.graphics_queue,
+            &self.indices,
+        )?;
+        self.index_buffer = index_buffer;
+        self.index_buffer_memory = index_buffer_memory;
+        let (uniform_buffers, uniform_buffers_memory) = share::pipeline::create_uniform_buffers(
+            &self.device,
+            &mut self.allocator,
+            VulkanAppImpl::uniform_buffer_size(self.mode),
+            self.swapchain_images.len(),
+        )?;
+        self.uniform_buffers = uniform_buffers;
+        self.uniform_buffers_memory = uniform_buffers_memory;
+        let (descriptor_pool, descriptor_sets) = VulkanAppImpl::create_descriptor_sets(
+            &self.device,
+            self.mode,
+            self.ubo_layout,
+            &self.uniform_buffers,
+            (self.texture_image_view, self.texture_sampler),
+        )?;
+        self.descriptor_pool = descriptor_pool;
+        self.descriptor_sets = descriptor_sets;
+        let sync_objects =
+            share::pipeline::create_sync_objects(&self.device, MAX_FRAMES_IN_FLIGHT)?;
+        self.image_available_semaphores = sync_objects.image_available_semaphores;
+        self.render_finished_semaphores = sync_objects.render_finished_semaphores;
+        self.inflight_fences = sync_objects.inflight_fences;
+
+        self.create_frame_resources()?;
+        self.set_debug_names()
+    }
 
-            render_pass,
+    /// Creates the swapchain and its image views.
+    fn create_swapchain(&mut self) -> Result<()> {
+        let surface_stuff = SurfaceStuff {
+            surface_loader: self.surface_loader.clone(),
+            surface: self.surface,
+            screen_width: constants::WINDOW_WIDTH,
+            screen_height: constants::WINDOW_HEIGHT,
+        };
+
+        let swapchain_stuff = share::create_swapchain(
+            &self.instance,
+            &self.device,
+            self.physical_device,
+            &self.window,
+            &surface_stuff,
+            &self.queue_family,
+        )?;
+        self.swapchain_loader = swapchain_stuff.swapchain_loader;
+        self.swapchain = swapchain_stuff.swapchain;
+        self.swapchain_images = swapchain_stuff.swapchain_images;
+        self.swapchain_format = swapchain_stuff.swapchain_format;
+        self.swapchain_extent = swapchain_stuff.swapchain_extent;
+
+        self.swapchain_imageviews = share::pipeline::create_image_views(
+            &self.device,
+            self.swapchain_format,
+            &self.swapchain_images,
+        )?;
+
+        Ok(())
+    }
+
+    /// Creates the depth and multisampled color attachments and the framebuffers, which have
+    /// the size of the swapchain.
+    fn create_attachments(&mut self) -> Result<()> {
+        let depth_resources = share::pipeline::create_depth_resources(
+            &self.instance,
+            &self.device,
+            self.physical_device,
+            self.command_pool,
+            self.graphics_queue,
+            self.swapchain_extent,
+            self.pipeline_settings.msaa_samples,
+            &mut self.allocator,
+        )?;
+        self.depth_image = depth_resources.0;
+        self.depth_image_view = depth_resources.1;
+        self.depth_image_memory = depth_resources.2;
+
+        let color_resources = VulkanAppImpl::create_color_resources(
+            &self.device,
+            self.swapchain_format,
+            self.swapchain_extent,
+            self.pipeline_settings.msaa_samples,
+            &mut self.allocator,
+        )?;
+        self.color_image = color_resources.0;
+        self.color_image_view = color_resources.1;
+        self.color_image_memory = color_resources.2;
+
+        self.swapchain_framebuffers = share::pipeline::create_framebuffers(
+            &self.device,
+            self.render_pass,
+            &self.swapchain_imageviews,
+            self.depth_image_view,
+            VulkanAppImpl::msaa_color_image_view(
+                self.pipeline_settings.msaa_samples,
+                self.color_image_view,
+            ),
+            self.swapchain_extent,
+        )?;
+
+        Ok(())
+    }
+
+    /// Creates the Shadertoy passes, whose buffers have the size of the swapchain. Once they
+    /// exist only the buffers are resized, the render pass and pipelines are kept.
+    fn create_frame_resources(&mut self) -> Result<()> {
+        if let Some(multipass) = self.multipass.as_mut() {
+            multipass.resize(
+                &self.device,
+                &mut self.allocator,
+                self.command_pool,
+                self.graphics_queue,
+                self.swapchain_extent,
+                &self.uniform_buffers,
+            )?;
+        } else if let Some(passes) = &self.passes {
+            self.multipass = Some(Multipass::new(
+                &self.device,
+                &mut self.allocator,
+                self.command_pool,
+                self.graphics_queue,
+                passes,
+                self.ubo_layout,
+                self.pipeline_cache,
+                self.swapchain_extent,
+                &self.uniform_buffers,
+            )?);
+        }
+
+        Ok(())
+    }
+
+    /// Names every object the app owns, again after the swapchain or the pipelines change.
+    fn set_debug_names(&self) -> Result<()> {
+        let marker = &self.debug_marker;
+        marker.set_names(&self.swapchain_images, "swapchain image")?;
+        marker.set_names(&self.swapchain_imageviews, "swapchain image view")?;
+        marker.set_names(&self.swapchain_framebuffers, "swapchain framebuffer")?;
+        marker.set_name(self.depth_image, "depth image")?;
+        marker.set_name(self.depth_image_view, "depth image view")?;
+        marker.set_name(self.color_image, "color image")?;
+        marker.set_name(self.color_image_view, "color image view")?;
+        marker.set_name(self.render_pass, "render pass")?;
+        marker.set_name(self.pipeline_cache, "pipeline cache")?;
+        marker.set_name(self.pipeline_layout, "pipeline layout")?;
+        marker.set_name(self.graphics_pipeline, "graphics pipeline")?;
+        marker.set_name(self.vertex_buffer, "vertex buffer")?;
+        marker.set_name(self.index_buffer, "index buffer")?;
+        marker.set_names(&self.uniform_buffers, "uniform buffer")?;
+        marker.set_name(self.texture_image, "texture image")?;
+        marker.set_name(self.texture_image_view, "texture image view")?;
+        marker.set_name(self.texture_sampler, "texture sampler")?;
+        marker.set_names(&self.descriptor_sets, "descriptor set")?;
+        marker.set_names(&self.command_buffers, "command buffer")?;
+        match &self.multipass {
+            Some(multipass) => multipass.set_debug_names(marker),
+            None => Ok(()),
+        }
+    }
+
+    /// Resets the command buffer of the current frame in flight and records the draw items
+    /// of this frame into framebuffer `image_index`.
+    fn record_command_buffer(&self, image_index: usize) -> Result<()> {
+        let command_buffer = self.command_buffers[self.current_frame];
+        // Shadertoy passes alternate between two sets of images, see Multipass.
+        let frame = self.shadertoy.as_ref().map_or(0, ShadertoyInput::frame);
+
+        unsafe {
+            self.device
+                .reset_command_buffer(command_buffer, vk::CommandBufferResetFlags::empty())
+                .vk_context("Failed to reset Command Buffer")?;
+        }
+        share::pipeline::begin_command_buffer(
+            &self.device,
+            command_buffer,
+            vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT,
+        )?;
+        if let Some(multipass) = &self.multipass {
+            multipass.cmd_buffer_passes(
+                &self.device,
+                &self.debug_marker,
+                command_buffer,
+                frame,
+                image_index,
+            );
+        }
+        share::pipeline::cmd_draw_items(
+            &self.device,
+            &self.debug_marker,
+            command_buffer,
+            "main pass",
+            self.render_pass,
+            self.swapchain_framebuffers[image_index],
+            self.swapchain_extent,
+            &self.draw_items(frame, image_index),
+        );
+        share::pipeline::end_command_buffer(&self.device, command_buffer)
+    }
+
+    /// What the window shows on frame `frame`, drawn into swapchain image `image_index`.
+    fn draw_items(&self, frame: usize, image_index: usize) -> Vec<DrawItem> {
+        let (descriptor_sets, command) = match &self.multipass {
+            Some(multipass) => (
+                vec![multipass.image_descriptor_set(frame, image_index)],
+                DrawCommand::FullscreenTriangle,
+            ),
+            None if self.mode == RenderMode::Mesh => (
+                vec![self.descriptor_sets[image_index]],
+                DrawCommand::IndexedMeshes {
+                    vertex_buffer: self.vertex_buffer,
+                    index_buffer: self.index_buffer,
+                    draws: self.mesh_draws.clone(),
+                },
+            ),
+            None => (
+                vec![self.descriptor_sets[image_index]],
+                DrawCommand::Indexed {
+                    vertex_buffer: self.vertex_buffer,
+                    index_buffer: self.index_buffer,
+                    index_count: self.indices.len() as u32,
+                },
+            ),
+        };
+
+        vec![DrawItem {
+            pipeline: self.graphics_pipeline,
+            pipeline_layout: self.pipeline_layout,
+            descriptor_sets,
+            command,
+        }]
+    }
+
+    /// One descriptor set per uniform buffer. Shadertoy passes own their descriptor sets, so
+    /// the pool is null in `RenderMode::Shadertoy`.
+    fn create_descriptor_sets(
+        device: &ash::Device,
+        mode: RenderMode,
+        ubo_layout: vk::DescriptorSetLayout,
+        uniform_buffers: &[vk::Buffer],
+        texture_details: (vk::ImageView, vk::Sampler),
+    ) -> Result<(vk::DescriptorPool, Vec<vk::DescriptorSet>)> {
+        if mode == RenderMode::Shadertoy {
+            return Ok((vk::DescriptorPool::null(), vec![]));
+        }
+
+        let descriptor_pool =
+            share::pipeline::create_descriptor_pool(device, uniform_buffers.len())?;
+        let descriptor_sets = share::pipeline::create_descriptor_sets(
+            device,
+            descriptor_pool,
             ubo_layout,
-            pipeline_layout,
-            graphics_pipeline,
-
-            depth_image,
-            depth_image_view,
-            depth_image_memory,
-
-            // _mip_levels: mip_levels,
-            // texture_image,
-            // texture_image_memory,
-            // texture_image_view,
-            // texture_sampler,
-            _vertices: RECT_VERTICES_DATA.to_vec(),
-            indices: RECT_INDICES_DATA.to_vec(),
-
-            vertex_buffer,
-            vertex_buffer_memory,
-            index_buffer,
-            index_buffer_memory,
-
-            uniform_transform: UniformBufferObject { u_time: 0.0 },
             uniform_buffers,
-            uniform_buffers_memory,
+            texture_details,
+            uniform_buffers.len(),
+        )?;
 
-            descriptor_pool,
-            descriptor_sets,
+        Ok((descriptor_pool, descriptor_sets))
+    }
 
-            command_pool,
-            command_buffers,
+    fn destroy_uniform_buffers(&mut self) {
+        unsafe {
+            self.device
+                .destroy_descriptor_pool(self.descriptor_pool, None);
+            for i in 0..self.uniform_buffers.len() {
+                self.device.destroy_buffer(self.uniform_buffers[i], None);
+                self.allocator
+                    .free(&self.device, self.uniform_buffers_memory[i]);
+            }
+        }
 
-            image_available_semaphores: sync_objects.image_available_semaphores,
-            render_finished_semaphores: sync_objects.render_finished_semaphores,
-            inflight_fences: sync_objects.inflight_fences,
-            current_frame: 0,
+        self.descriptor_pool = vk::DescriptorPool::null();
+        self.descriptor_sets.clear();
+        self.uniform_buffers.clear();
+        self.uniform_buffers_memory.clear();
+    }
 
-            is_framebuffer_resized: false,
+    /// Multisampled color attachment, or null handles when there is a single sample and the
+    /// swapchain image is rendered to directly.
+    fn create_color_resources(
+        device: &ash::Device,
+        format: vk::Format,
+        extent: vk::Extent2D,
+        msaa_samples: vk::SampleCountFlags,
+        allocator: &mut MemoryAllocator,
+    ) -> Result<(vk::Image, vk::ImageView, Allocation)> {
+        if msaa_samples == vk::SampleCountFlags::TYPE_1 {
+            return Ok((vk::Image::null(), vk::ImageView::null(), Allocation::null()));
+        }
+
+        share::pipeline::create_color_resources(device, format, extent, msaa_samples, allocator)
+    }
+
+    fn msaa_color_image_view(
+        msaa_samples: vk::SampleCountFlags,
+        color_image_view: vk::ImageView,
+    ) -> Option<vk::ImageView> {
+        if msaa_samples == vk::SampleCountFlags::TYPE_1 {
+            None
+        } else {
+            Some(color_image_view)
         }
     }
 
@@ -292,7 +869,7 @@ impl VulkanAppImpl {
         instance: &ash::Instance,
         physical_device: vk::PhysicalDevice,
         image_format: vk::Format,
-    ) {
+    ) -> Result<()> {
         let format_properties = unsafe {
             instance.get_physical_device_format_properties(physical_device, image_format)
         };
@@ -302,50 +879,107 @@ impl VulkanAppImpl {
             .contains(vk::FormatFeatureFlags::SAMPLED_IMAGE_FILTER_LINEAR);
 
         if !is_sample_image_filter_linear_support {
-            panic!("Texture Image does not support linear blitting!")
+            // Texture Image does not support linear blitting!
+            return Err(Error::UnsupportedFormat {
+                candidates: vec![image_format],
+                features: vk::FormatFeatureFlags::SAMPLED_IMAGE_FILTER_LINEAR,
+            });
         }
+
+        Ok(())
     }
 
-    fn update_uniform_buffer(&mut self, current_image: usize, delta_time: f32) {
-        self.uniform_transform.u_time = self.uniform_transform.u_time + delta_time;
+    fn create_graphics_pipeline(
+        device: &ash::Device,
+        render_pass: vk::RenderPass,
+        pipeline_cache: vk::PipelineCache,
+        ubo_layout: vk::DescriptorSetLayout,
+        vert_shader_path: &Path,
+        frag_shader_path: &Path,
+        specialization: &SpecializationConfig,
+        mode: RenderMode,
+        settings: PipelineSettings,
+    ) -> Result<(vk::Pipeline, vk::PipelineLayout)> {
+        let builder = GraphicsPipelineBuilder::new()
+            .shader(vk::ShaderStageFlags::VERTEX, vert_shader_path)
+            .shader(vk::ShaderStageFlags::FRAGMENT, frag_shader_path)
+            .specialization(vk::ShaderStageFlags::VERTEX, specialization.vertex.clone())
+            .specialization(
+                vk::ShaderStageFlags::FRAGMENT,
+                specialization.fragment.clone(),
+            )
+            .descriptor_set_layout(ubo_layout)
+            .rasterization_samples(settings.msaa_samples)
+            .max_push_constants_size(settings.max_push_constants_size)
+            .pipeline_cache(pipeline_cache);
+        let builder = match settings.min_sample_shading {
+            Some(min_sample_shading) => builder.sample_shading(min_sample_shading),
+            None => builder,
+        };
+        let builder = match mode {
+            RenderMode::Rectangle => builder.vertex_input::<VertexV2>(),
+            RenderMode::Mesh => builder
+                .vertex_input::<VertexV3>()
+                .front_face(vk::FrontFace::COUNTER_CLOCKWISE)
+                .push_constant_range(MeshPushConstants::push_constant_range()),
+            // The fullscreen triangle is generated in the vertex shader, so it has no vertex input.
+            RenderMode::Shadertoy => builder,
+        };
+
+        builder.build(device, render_pass)
+    }
+
+    fn uniform_buffer_size(mode: RenderMode) -> usize {
+        match mode {
+            RenderMode::Shadertoy => std::mem::size_of::<ShadertoyUniforms>(),
+            RenderMode::Rectangle | RenderMode::Mesh => std::mem::size_of::<UniformBufferObject>(),
+        }
+    }
+
+    fn update_uniform_buffer(&mut self, current_image: usize, delta_time: f32) -> Result<()> {
+        if let Some(shadertoy) = self.shadertoy.as_mut() {
+            let uniforms = shadertoy.next_frame(self.swapchain_extent, delta_time);
+            return self.write_uniform_buffer(current_image, uniforms);
+        }
 
-        let ubos = [self.uniform_transform.clone()];
+        self.uniform_transform.u_time += delta_time;
+        if self.mode == RenderMode::Mesh {
+            // Spins the model a quarter turn per second around the up axis.
+            self.uniform_transform.model =
+                Matrix4::from_angle_z(Deg(90.0 * self.uniform_transform.u_time));
+            self.uniform_transform.view = self.camera.view();
+            self.uniform_transform.proj = self.camera.projection(self.swapchain_extent);
+        }
+        self.write_uniform_buffer(current_image, self.uniform_transform)
+    }
 
-        let buffer_size = (std::mem::size_of::<UniformBufferObject>() * ubos.len()) as u64;
+    fn write_uniform_buffer<T: Copy>(&self, current_image: usize, uniforms: T) -> Result<()> {
+        let ubos = [uniforms];
 
         unsafe {
-            let data_ptr =
-                self.device
-                    .map_memory(
-                        self.uniform_buffers_memory[current_image],
-                        0,
-                        buffer_size,
-                        vk::MemoryMapFlags::empty(),
-                    )
-                    .expect("Failed to Map Memory") as *mut UniformBufferObject;
+            let data_ptr = self.uniform_buffers_memory[current_image].mapped_ptr()? as *mut T;
 
             data_ptr.copy_from_nonoverlapping(ubos.as_ptr(), ubos.len());
-
-            self.device
-                .unmap_memory(self.uniform_buffers_memory[current_image]);
         }
+
+        Ok(())
     }
 }
 
 impl VulkanApp for VulkanAppImpl {
-    fn draw_frame(&mut self, delta_time: f32) {
+    fn draw_frame(&mut self, delta_time: f32) -> Result<()> {
         let wait_fences = [self.inflight_fences[self.current_frame]];
 
         unsafe {
             self.device
-                .wait_for_fences(&wait_fences, true, std::u64::MAX)
-                .expect("Failed to wait for Fence!");
+                .wait_for_fences(&wait_fences, true, u64::MAX)
+                .vk_context("Failed to wait for Fence!")?;
         }
 
         let (image_index, _is_sub_optimal) = unsafe {
             let result = self.swapchain_loader.acquire_next_image(
                 self.swapchain,
-                std::u64::MAX,
+                u64::MAX,
                 self.image_available_semaphores[self.current_frame],
                 vk::Fence::null(),
             );
@@ -354,15 +988,20 @@ impl VulkanApp for VulkanAppImpl {
                 Ok(image_index) => image_index,
                 Err(vk_result) => match vk_result {
                     vk::Result::ERROR_OUT_OF_DATE_KHR => {
-                        self.recreate_swapchain();
-                        return;
+                        return self.recreate_swapchain();
+                    }
+                    _ => {
+                        return Err(Error::Vulkan {
+                            context: "Failed to acquire Swap Chain Image!",
+                            result: vk_result,
+                        })
                     }
-                    _ => panic!("Failed to acquire Swap Chain Image!"),
                 },
             }
         };
 
-        self.update_uniform_buffer(image_index as usize, delta_time);
+        self.record_command_buffer(image_index as usize)?;
+        self.update_uniform_buffer(image_index as usize, delta_time)?;
 
         let wait_semaphores = [self.image_available_semaphores[self.current_frame]];
         let wait_stages = [vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT];
@@ -375,7 +1014,7 @@ impl VulkanApp for VulkanAppImpl {
             p_wait_semaphores: wait_semaphores.as_ptr(),
             p_wait_dst_stage_mask: wait_stages.as_ptr(),
             command_buffer_count: 1,
-            p_command_buffers: &self.command_buffers[image_index as usize],
+            p_command_buffers: &self.command_buffers[self.current_frame],
             signal_semaphore_count: signal_semaphores.len() as u32,
             p_signal_semaphores: signal_semaphores.as_ptr(),
         }];
@@ -383,7 +1022,7 @@ impl VulkanApp for VulkanAppImpl {
         unsafe {
             self.device
                 .reset_fences(&wait_fences)
-                .expect("Failed to reset Fence");
+                .vk_context("Failed to reset Fence")?;
 
             self.device
                 .queue_submit(
@@ -391,7 +1030,7 @@ impl VulkanApp for VulkanAppImpl {
                     &submit_infos,
                     self.inflight_fences[self.current_frame],
                 )
-                .expect("Failed to execute queue submit");
+                .vk_context("Failed to execute queue submit")?;
         }
 
         let swapchains = [self.swapchain];
@@ -414,155 +1053,96 @@ impl VulkanApp for VulkanAppImpl {
             Ok(_) => self.is_framebuffer_resized,
             Err(vk_result) => match vk_result {
                 vk::Result::ERROR_OUT_OF_DATE_KHR | vk::Result::SUBOPTIMAL_KHR => true,
-                _ => panic!("Failed to execute queue present"),
+                _ => {
+                    return Err(Error::Vulkan {
+                        context: "Failed to execute queue present",
+                        result: vk_result,
+                    })
+                }
             },
         };
         if is_resized {
             self.is_framebuffer_resized = false;
-            self.recreate_swapchain();
+            self.recreate_swapchain()?;
         }
 
         self.current_frame = (self.current_frame + 1) % MAX_FRAMES_IN_FLIGHT;
-    }
 
-    fn recreate_swapchain(&mut self) {
-        let surface_stuff = SurfaceStuff {
-            surface_loader: self.surface_loader.clone(),
-            surface: self.surface,
-            screen_width: constants::WINDOW_WIDTH,
-            screen_height: constants::WINDOW_HEIGHT,
-        };
+        Ok(())
+    }
 
+    /// Only rebuilds what depends on the swapchain images and extent. Pipelines use dynamic
+    /// viewport and scissor, and the render pass only depends on the surface format, which
+    /// `create_swapchain` picks the same way every time.
+    fn recreate_swapchain(&mut self) -> Result<()> {
         unsafe {
             self.device
                 .device_wait_idle()
-                .expect("Failed to wait device idle!")
+                .vk_context("Failed to wait device idle!")?
         };
+        let previous_image_count = self.swapchain_images.len();
         self.cleanup_swapchain();
 
-        let swapchain_stuff = share::create_swapchain(
-            &self.instance,
-            &self.device,
-            self.physical_device,
-            &self.window,
-            &surface_stuff,
-            &self.queue_family,
-        );
-        self.swapchain_loader = swapchain_stuff.swapchain_loader;
-        self.swapchain = swapchain_stuff.swapchain;
-        self.swapchain_images = swapchain_stuff.swapchain_images;
-        self.swapchain_format = swapchain_stuff.swapchain_format;
-        self.swapchain_extent = swapchain_stuff.swapchain_extent;
-
-        self.swapchain_imageviews = share::pipeline::create_image_views(
-            &self.device,
-            self.swapchain_format,
-            &self.swapchain_images,
-        );
-        self.render_pass = share::pipeline::create_render_pass(
-            &self.instance,
-            &self.device,
-            self.physical_device,
-            self.swapchain_format,
-        );
-        let (graphics_pipeline, pipeline_layout) = share::pipeline::create_graphics_pipeline(
-            &self.device,
-            self.render_pass,
-            swapchain_stuff.swapchain_extent,
-            self.ubo_layout,
-            &VertexV2::get_binding_descriptions(),
-            &VertexV2::get_attribute_descriptions(),
-        );
-        self.graphics_pipeline = graphics_pipeline;
-        self.pipeline_layout = pipeline_layout;
-
-        let depth_resources = share::pipeline::create_depth_resources(
-            &self.instance,
-            &self.device,
-            self.physical_device,
-            self.command_pool,
-            self.graphics_queue,
-            self.swapchain_extent,
-            &self.memory_properties,
-        );
-        self.depth_image = depth_resources.0;
-        self.depth_image_view = depth_resources.1;
-        self.depth_image_memory = depth_resources.2;
-
-        let physical_device_memory_properties = unsafe {
-            self.instance
-                .get_physical_device_memory_properties(self.physical_device)
-        };
-
-        self.swapchain_framebuffers = share::pipeline::create_framebuffers(
-            &self.device,
-            self.render_pass,
-            &self.swapchain_imageviews,
-            self.depth_image_view,
-            self.swapchain_extent,
-        );
-        let (uniform_buffers, uniform_buffers_memory) = share::pipeline::create_uniform_buffers(
-            &self.device,
-            &physical_device_memory_properties,
-            self.swapchain_images.len(),
-        );
-        self.uniform_buffers = uniform_buffers;
-        self.uniform_buffers_memory = uniform_buffers_memory;
-        self.descriptor_pool =
-            share::pipeline::create_descriptor_pool(&self.device, self.swapchain_images.len());
-        self.descriptor_sets = share::pipeline::create_descriptor_sets(
-            &self.device,
-            self.descriptor_pool,
-            self.ubo_layout,
-            &self.uniform_buffers,
-            Option::None,
-            self.swapchain_images.len(),
-        );
-        self.command_buffers = share::pipeline::create_command_buffers(
-            &self.device,
-            self.command_pool,
-            self.graphics_pipeline,
-            &self.swapchain_framebuffers,
-            self.render_pass,
-            self.swapchain_extent,
-            self.vertex_buffer,
-            self.index_buffer,
-            self.pipeline_layout,
-            &self.descriptor_sets,
-            self.indices.len() as u32,
-        );
+        self.create_swapchain()?;
+        self.create_attachments()?;
+
+        // Uniform buffers are per swapchain image, so they only need rebuilding if the driver
+        // hands out a different number of images.
+        if self.swapchain_images.len() != previous_image_count {
+            self.destroy_uniform_buffers();
+            let (uniform_buffers, uniform_buffers_memory) =
+                share::pipeline::create_uniform_buffers(
+                    &self.device,
+                    &mut self.allocator,
+                    VulkanAppImpl::uniform_buffer_size(self.mode),
+                    self.swapchain_images.len(),
+                )?;
+            self.uniform_buffers = uniform_buffers;
+            self.uniform_buffers_memory = uniform_buffers_memory;
+            let (descriptor_pool, descriptor_sets) = VulkanAppImpl::create_descriptor_sets(
+                &self.device,
+                self.mode,
+                self.ubo_layout,
+                &self.uniform_buffers,
+                (self.texture_image_view, self.texture_sampler),
+            )?;
+            self.descriptor_pool = descriptor_pool;
+            self.descriptor_sets = descriptor_sets;
+        }
+        self.create_frame_resources()?;
+        self.set_debug_names()
     }
 
-    fn cleanup_swapchain(&self) {
+    // Resets what it destroys to null handles, so that `Drop` does not destroy it again if
+    // `recreate_swapchain` fails halfway.
+    fn cleanup_swapchain(&mut self) {
         unsafe {
             self.device.destroy_image_view(self.depth_image_view, None);
             self.device.destroy_image(self.depth_image, None);
-            self.device.free_memory(self.depth_image_memory, None);
-
-            self.device
-                .free_command_buffers(self.command_pool, &self.command_buffers);
-
-            for i in 0..self.uniform_buffers.len() {
-                self.device.destroy_buffer(self.uniform_buffers[i], None);
-                self.device
-                    .free_memory(self.uniform_buffers_memory[i], None);
-            }
-            self.device
-                .destroy_descriptor_pool(self.descriptor_pool, None);
+            self.allocator.free(&self.device, self.depth_image_memory);
+            self.device.destroy_image_view(self.color_image_view, None);
+            self.device.destroy_image(self.color_image, None);
+            self.allocator.free(&self.device, self.color_image_memory);
 
             for &framebuffer in self.swapchain_framebuffers.iter() {
                 self.device.destroy_framebuffer(framebuffer, None);
             }
-            self.device.destroy_pipeline(self.graphics_pipeline, None);
-            self.device
-                .destroy_pipeline_layout(self.pipeline_layout, None);
-            self.device.destroy_render_pass(self.render_pass, None);
             for &image_view in self.swapchain_imageviews.iter() {
                 self.device.destroy_image_view(image_view, None);
             }
             self.swapchain_loader
                 .destroy_swapchain(self.swapchain, None);
         }
+
+        self.depth_image_view = vk::ImageView::null();
+        self.depth_image = vk::Image::null();
+        self.depth_image_memory = Allocation::null();
+        self.color_image_view = vk::ImageView::null();
+        self.color_image = vk::Image::null();
+        self.color_image_memory = Allocation::null();
+        self.swapchain_framebuffers.clear();
+        self.swapchain_imageviews.clear();
+        self.swapchain = vk::SwapchainKHR::null();
     }
 
     fn wait_device_idle(&self) {
@@ -580,42 +1160,144 @@ impl VulkanApp for VulkanAppImpl {
     fn window_ref(&self) -> &winit::window::Window {
         &self.window
     }
+
+    fn shader_paths(&self) -> Vec<PathBuf> {
+        let mut shader_paths = vec![self.vert_shader_path.clone()];
+        match &self.multipass {
+            Some(multipass) => shader_paths.extend(multipass.shader_paths()),
+            None => shader_paths.push(self.frag_shader_path.clone()),
+        }
+        shader_paths.extend(self.specialization_path.clone());
+
+        shader_paths
+    }
+
+    fn cursor_moved(&mut self, x: f64, y: f64) {
+        if let Some(shadertoy) = self.shadertoy.as_mut() {
+            shadertoy.mouse.cursor_moved(x, y);
+        }
+    }
+
+    fn mouse_button_changed(&mut self, is_pressed: bool) {
+        if let Some(shadertoy) = self.shadertoy.as_mut() {
+            shadertoy.mouse.button_changed(is_pressed);
+        }
+    }
+
+    fn reload_shaders(&mut self) -> Result<()> {
+        // Build the new pipelines first, so a broken shader leaves the current ones untouched.
+        let specialization = match &self.specialization_path {
+            Some(path) => SpecializationConfig::load(path)?,
+            None => SpecializationConfig::default(),
+        };
+        let (graphics_pipeline, pipeline_layout) = VulkanAppImpl::create_graphics_pipeline(
+            &self.device,
+            self.render_pass,
+            self.pipeline_cache,
+            self.ubo_layout,
+            &self.vert_shader_path,
+            &self.frag_shader_path,
+            &specialization,
+            self.mode,
+            self.pipeline_settings,
+        )?;
+        let buffer_pipelines = match &self.multipass {
+            Some(multipass) => match multipass.create_pipelines(&self.device) {
+                Ok(buffer_pipelines) => buffer_pipelines,
+                Err(err) => {
+                    unsafe {
+                        self.device.destroy_pipeline(graphics_pipeline, None);
+                        self.device.destroy_pipeline_layout(pipeline_layout, None);
+                    }
+                    return Err(err);
+                }
+            },
+            None => vec![],
+        };
+
+        unsafe {
+            self.device
+                .device_wait_idle()
+                .vk_context("Failed to wait device idle!")?;
+
+            self.device.destroy_pipeline(self.graphics_pipeline, None);
+            self.device
+                .destroy_pipeline_layout(self.pipeline_layout, None);
+        }
+        self.graphics_pipeline = graphics_pipeline;
+        self.pipeline_layout = pipeline_layout;
+        self.specialization = specialization;
+        if let Some(multipass) = self.multipass.as_mut() {
+            multipass.replace_pipelines(&self.device, buffer_pipelines);
+        }
+
+        self.set_debug_names()
+    }
 }
 
 impl Drop for VulkanAppImpl {
     fn drop(&mut self) {
         unsafe {
-            for i in 0..MAX_FRAMES_IN_FLIGHT {
-                self.device
-                    .destroy_semaphore(self.image_available_semaphores[i], None);
-                self.device
-                    .destroy_semaphore(self.render_finished_semaphores[i], None);
-                self.device.destroy_fence(self.inflight_fences[i], None);
+            for &semaphore in self.image_available_semaphores.iter() {
+                self.device.destroy_semaphore(semaphore, None);
+            }
+            for &semaphore in self.render_finished_semaphores.iter() {
+                self.device.destroy_semaphore(semaphore, None);
+            }
+            for &fence in self.inflight_fences.iter() {
+                self.device.destroy_fence(fence, None);
             }
 
             self.cleanup_swapchain();
+            if let Some(multipass) = self.multipass.as_mut() {
+                multipass.destroy(&self.device, &mut self.allocator);
+            }
+            self.destroy_uniform_buffers();
+            self.device.destroy_pipeline(self.graphics_pipeline, None);
+            self.device
+                .destroy_pipeline_layout(self.pipeline_layout, None);
+            self.device.destroy_render_pass(self.render_pass, None);
+
+            // Losing the cache only makes the next start slower, so failing to save it is not
+            // worth more than a warning.
+            if self.pipeline_cache != vk::PipelineCache::null() {
+                if let Err(err) = pipeline_cache::save_pipeline_cache(
+                    &self.device,
+                    self.pipeline_cache,
+                    &pipeline_cache::default_cache_path(),
+                ) {
+                    eprintln!("Failed to save pipeline cache: {}", err);
+                }
+            }
+            self.device
+                .destroy_pipeline_cache(self.pipeline_cache, None);
 
             self.device.destroy_buffer(self.index_buffer, None);
-            self.device.free_memory(self.index_buffer_memory, None);
+            self.allocator.free(&self.device, self.index_buffer_memory);
 
             self.device.destroy_buffer(self.vertex_buffer, None);
-            self.device.free_memory(self.vertex_buffer_memory, None);
+            self.allocator.free(&self.device, self.vertex_buffer_memory);
 
-            // self.device.destroy_sampler(self.texture_sampler, None);
-            // self.device
-            //     .destroy_image_view(self.texture_image_view, None);
-            // self.device.destroy_image(self.texture_image, None);
-            // self.device.free_memory(self.texture_image_memory, None);
+            self.device.destroy_sampler(self.texture_sampler, None);
+            self.device
+                .destroy_image_view(self.texture_image_view, None);
+            self.device.destroy_image(self.texture_image, None);
+            self.allocator.free(&self.device, self.texture_image_memory);
 
             self.device
                 .destroy_descriptor_set_layout(self.ubo_layout, None);
 
+            if !self.command_buffers.is_empty() {
+                self.device
+                    .free_command_buffers(self.command_pool, &self.command_buffers);
+            }
             self.device.destroy_command_pool(self.command_pool, None);
 
+            self.allocator.destroy(&self.device);
             self.device.destroy_device(None);
             self.surface_loader.destroy_surface(self.surface, None);
 
-            if VALIDATION.is_enable {
+            if self.is_validation_enabled {
                 self.debug_utils_loader
                     .destroy_debug_utils_messenger(self.debug_messenger, None);
             }
@@ -625,8 +1307,160 @@ impl Drop for VulkanAppImpl {
     }
 }
 
+fn run_headless(
+    output_path: &Path,
+    frame_count: u32,
+    validation: &ValidationInfo,
+    gpu_selector: Option<DeviceSelector>,
+) -> Result<()> {
+    let mut renderer = HeadlessRenderer::new(
+        constants::WINDOW_WIDTH,
+        constants::WINDOW_HEIGHT,
+        validation,
+        gpu_selector,
+        Path::new(constants::VERT_SHADER_PATH),
+        Path::new(constants::FRAG_SHADER_PATH),
+    )?;
+
+    // Advance time as if the frames were shown at 60 FPS.
+    let mut ubo = UniformBufferObject::default();
+    for _ in 0..frame_count {
+        renderer.render_frame(&ubo)?;
+        ubo.u_time += 1.0 / 60.0;
+    }
+
+    renderer.save_png(output_path)?;
+    println!("Saved {} frame(s) to {:?}", frame_count, output_path);
+    renderer.messenger().check()?;
+
+    Ok(())
+}
+
 fn main() {
+    // Usage: main [--headless <output.png> [--frames <count>]]
+    //             [--shadertoy <shader.frag | description.passes>]
+    //             [--model [model.obj | model.gltf | model.glb]] [--texture <image>]
+    //             [--msaa <samples>] [--sample-shading <fraction>]
+    //             [--specialization <constants.cfg>] [--gpu <index | name | vendor:device>]
+    //             [--validation-config <validation.cfg>] [--validation <on | off>]
+    //             [--validation-severity <list>] [--validation-types <list>]
+    //             [--validation-layers <list>] [--validation-sink <stderr | log | memory>]
+    //             [--validation-fail-on-error <on | off>]
+    let args: Vec<String> = std::env::args().collect();
+    let validation_config_path = match args.iter().position(|arg| arg == "--validation-config") {
+        Some(position) => Some(PathBuf::from(
+            args.get(position + 1)
+                .expect("--validation-config expects a config path"),
+        )),
+        None => Some(PathBuf::from(constants::VALIDATION_CONFIG_PATH)).filter(|path| path.exists()),
+    };
+    let validation = match ValidationInfo::resolve(validation_config_path.as_deref(), &args) {
+        Ok(validation) => validation,
+        Err(err) => {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
+    };
+    let gpu_selector = match args.iter().position(|arg| arg == "--gpu") {
+        Some(position) => Some(DeviceSelector::parse(
+            args.get(position + 1)
+                .expect("--gpu expects a device index, name or vendor:device ID"),
+        )),
+        None => DeviceSelector::from_env(),
+    };
+    if let Some(position) = args.iter().position(|arg| arg == "--headless") {
+        let output_path = args
+            .get(position + 1)
+            .expect("--headless expects an output PNG path");
+        let frame_count = args
+            .iter()
+            .position(|arg| arg == "--frames")
+            .and_then(|position| args.get(position + 1))
+            .map(|count| count.parse().expect("--frames expects a number"))
+            .unwrap_or(1);
+
+        if let Err(err) = run_headless(
+            Path::new(output_path),
+            frame_count,
+            &validation,
+            gpu_selector,
+        ) {
+            eprintln!("Headless rendering failed: {}", err);
+            std::process::exit(1);
+        }
+        return;
+    }
+
     let program_proc = ProgramProc::new();
-    let vulkan_app = VulkanAppImpl::new(&program_proc.event_loop);
+    let shadertoy_path = args
+        .iter()
+        .position(|arg| arg == "--shadertoy")
+        .map(|position| {
+            args.get(position + 1)
+                .expect("--shadertoy expects a fragment shader or pass description path")
+        })
+        .map(PathBuf::from);
+    let model_path = args
+        .iter()
+        .position(|arg| arg == "--model")
+        .map(|position| match args.get(position + 1) {
+            Some(path) if !path.starts_with("--") => PathBuf::from(path),
+            _ => PathBuf::from(constants::MODEL_PATH),
+        });
+    let texture_path = args
+        .iter()
+        .position(|arg| arg == "--texture")
+        .map(|position| {
+            args.get(position + 1)
+                .expect("--texture expects an image path")
+        })
+        .map(PathBuf::from);
+    let msaa_samples = args
+        .iter()
+        .position(|arg| arg == "--msaa")
+        .and_then(|position| args.get(position + 1))
+        .map(|samples| samples.parse().expect("--msaa expects a sample count"))
+        .unwrap_or(constants::DEFAULT_MSAA_SAMPLES);
+    let min_sample_shading = args
+        .iter()
+        .position(|arg| arg == "--sample-shading")
+        .and_then(|position| args.get(position + 1))
+        .map(|fraction| {
+            fraction
+                .parse()
+                .expect("--sample-shading expects a fraction between 0 and 1")
+        });
+    let specialization_path = match args.iter().position(|arg| arg == "--specialization") {
+        Some(position) => Some(PathBuf::from(
+            args.get(position + 1)
+                .expect("--specialization expects a config path"),
+        )),
+        None => {
+            Some(PathBuf::from(constants::SPECIALIZATION_CONFIG_PATH)).filter(|path| path.exists())
+        }
+    };
+    let vulkan_app = match VulkanAppImpl::new(
+        &program_proc.event_loop,
+        shadertoy_path,
+        model_path,
+        texture_path,
+        msaa_samples,
+        min_sample_shading,
+        specialization_path,
+        gpu_selector,
+        &validation,
+    ) {
+        Ok(vulkan_app) => vulkan_app,
+        Err(err) => {
+            eprintln!("Failed to initialize Vulkan: {}", err);
+            std::process::exit(1);
+        }
+    };
+    // Kept past the app, so errors reported while it is destroyed are counted too.
+    let messenger = vulkan_app.messenger.clone();
     program_proc.main_loop(vulkan_app);
+    if let Err(err) = messenger.check() {
+        eprintln!("{}", err);
+        std::process::exit(1);
+    }
 }
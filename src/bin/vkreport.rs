@@ -0,0 +1,101 @@
+use rust_game::{
+    error::Result,
+    utility::{
+        capability_report::CapabilityReport, debug::ValidationInfo,
+        debug_messenger::DebugMessenger, platforms, share,
+    },
+};
+
+use ash::version::InstanceV1_0;
+use winit::event_loop::EventLoop;
+
+const REPORT_TITLE: &str = "vkreport";
+
+/// winit panics when there is no display to connect to, so only try to open the hidden
+/// window when one is configured.
+#[cfg(all(unix, not(target_os = "android"), not(target_os = "macos")))]
+fn display_available() -> bool {
+    ["DISPLAY", "WAYLAND_DISPLAY"]
+        .iter()
+        .any(|variable| std::env::var_os(variable).is_some_and(|value| !value.is_empty()))
+}
+
+#[cfg(not(all(unix, not(target_os = "android"), not(target_os = "macos"))))]
+fn display_available() -> bool {
+    true
+}
+
+fn collect_headless(entry: &ash::Entry) -> Result<CapabilityReport> {
+    let (instance, _) = share::create_instance_with_extensions(
+        entry,
+        REPORT_TITLE,
+        &ValidationInfo::disabled(),
+        &DebugMessenger::new(vec![], false),
+        &platforms::headless_extension_names(),
+    )?;
+    let report = CapabilityReport::collect(entry, &instance, None);
+    unsafe {
+        instance.destroy_instance(None);
+    }
+    report
+}
+
+/// Uses a hidden window so present support and swapchain capabilities can be reported.
+fn collect_with_surface(entry: &ash::Entry) -> Result<CapabilityReport> {
+    let event_loop = EventLoop::new();
+    let window = winit::window::WindowBuilder::new()
+        .with_title(REPORT_TITLE)
+        .with_visible(false)
+        .build(&event_loop)
+        .expect("Failed to create window.");
+    let size = window.inner_size();
+
+    let (instance, _) = share::create_instance(
+        entry,
+        REPORT_TITLE,
+        &ValidationInfo::disabled(),
+        &DebugMessenger::new(vec![], false),
+    )?;
+    let report = share::create_surface(entry, &instance, &window, size.width, size.height)
+        .and_then(|surface_stuff| {
+            let report = CapabilityReport::collect(entry, &instance, Some(&surface_stuff));
+            unsafe {
+                surface_stuff
+                    .surface_loader
+                    .destroy_surface(surface_stuff.surface, None);
+            }
+            report
+        });
+    unsafe {
+        instance.destroy_instance(None);
+    }
+    report
+}
+
+fn main() {
+    // Usage: vkreport [--json] [--no-surface]
+    let args: Vec<String> = std::env::args().collect();
+    let as_json = args.iter().any(|arg| arg == "--json");
+    let with_surface = !args.iter().any(|arg| arg == "--no-surface") && display_available();
+
+    let report = ash::Entry::new().map_err(Into::into).and_then(|entry| {
+        if with_surface {
+            collect_with_surface(&entry)
+        } else {
+            collect_headless(&entry)
+        }
+    });
+    let report = match report {
+        Ok(report) => report,
+        Err(err) => {
+            eprintln!("Failed to collect the Vulkan report: {}", err);
+            std::process::exit(1);
+        }
+    };
+
+    if as_json {
+        println!("{:#}", report.to_json());
+    } else {
+        print!("{}", report);
+    }
+}
@@ -0,0 +1,227 @@
+use crate::utility::debug_messenger::DebugCounts;
+
+use ash::vk;
+use std::fmt;
+use std::path::PathBuf;
+
+#[derive(Debug)]
+pub enum Error {
+    /// The Vulkan loader library could not be found or loaded.
+    Loading(ash::LoadingError),
+    Instance(ash::InstanceError),
+    /// A Vulkan call failed; `context` says which one.
+    Vulkan {
+        context: &'static str,
+        result: vk::Result,
+    },
+    /// A validation setting from a config file, the environment or the command line is
+    /// invalid; `origin` names where it came from.
+    InvalidValidationSetting {
+        origin: String,
+        reason: String,
+    },
+    /// Validation reported errors while `fail_on_error` was set.
+    ValidationFailed(DebugCounts),
+    NoSuitableGpu,
+    /// No suitable GPU matches the `--gpu` or `RUST_GAME_GPU` selector.
+    NoMatchingGpu(String),
+    NoSuitableMemoryType {
+        type_filter: u32,
+        required_properties: vk::MemoryPropertyFlags,
+    },
+    UnsupportedFormat {
+        candidates: Vec<vk::Format>,
+        features: vk::FormatFeatureFlags,
+    },
+    UnsupportedLayoutTransition {
+        old_layout: vk::ImageLayout,
+        new_layout: vk::ImageLayout,
+    },
+    Io {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    Image {
+        path: PathBuf,
+        source: image::ImageError,
+    },
+    UnsupportedImage {
+        path: PathBuf,
+        reason: &'static str,
+    },
+    /// Pixels read back from an image do not fill `extent` with RGBA8 texels.
+    CaptureSizeMismatch {
+        extent: vk::Extent2D,
+        byte_count: usize,
+    },
+    Model {
+        path: PathBuf,
+        source: tobj::LoadError,
+    },
+    Gltf {
+        path: PathBuf,
+        source: gltf::Error,
+    },
+    InvalidModel {
+        path: PathBuf,
+        reason: &'static str,
+    },
+    /// A pipeline layout declares push constants beyond the device's `maxPushConstantsSize`.
+    PushConstantsTooLarge {
+        size: u32,
+        max_size: u32,
+    },
+    /// GLSL source was given but the crate was built without `runtime-shaders`.
+    UnsupportedShader {
+        path: PathBuf,
+        reason: &'static str,
+    },
+    /// A Shadertoy pass description file could not be parsed.
+    InvalidPasses {
+        path: PathBuf,
+        line: usize,
+        reason: String,
+    },
+    /// A specialization constant config file could not be parsed.
+    InvalidSpecialization {
+        path: PathBuf,
+        line: usize,
+        reason: String,
+    },
+    /// The in-process GLSL compiler rejected a shader.
+    ShaderCompile {
+        path: PathBuf,
+        line: Option<u32>,
+        column: Option<u32>,
+        message: String,
+    },
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Loading(err) => write!(f, "Failed to load Vulkan: {}", err),
+            Error::Instance(err) => write!(f, "Failed to create instance: {}", err),
+            Error::Vulkan { context, result } => write!(f, "{}: {}", context, result),
+            Error::InvalidValidationSetting { origin, reason } => {
+                write!(f, "Invalid validation setting in {}: {}", origin, reason)
+            }
+            Error::ValidationFailed(counts) => write!(f, "Validation reported {}", counts),
+            Error::NoSuitableGpu => write!(f, "Failed to find a suitable GPU"),
+            Error::NoMatchingGpu(selector) => {
+                write!(f, "Failed to find a suitable GPU with {}", selector)
+            }
+            Error::NoSuitableMemoryType {
+                type_filter,
+                required_properties,
+            } => write!(
+                f,
+                "Failed to find suitable memory type (type filter {:#b}, properties {:?})",
+                type_filter, required_properties
+            ),
+            Error::UnsupportedFormat {
+                candidates,
+                features,
+            } => write!(
+                f,
+                "None of the formats {:?} supports {:?}",
+                candidates, features
+            ),
+            Error::UnsupportedLayoutTransition {
+                old_layout,
+                new_layout,
+            } => write!(
+                f,
+                "Unsupported layout transition from {:?} to {:?}",
+                old_layout, new_layout
+            ),
+            Error::Io { path, source } => write!(f, "I/O error on {:?}: {}", path, source),
+            Error::Image { path, source } => {
+                write!(f, "Failed to process image {:?}: {}", path, source)
+            }
+            Error::UnsupportedImage { path, reason } => {
+                write!(f, "Unsupported image {:?}: {}", path, reason)
+            }
+            Error::CaptureSizeMismatch { extent, byte_count } => write!(
+                f,
+                "Captured {} bytes do not fit a {}x{} RGBA image",
+                byte_count, extent.width, extent.height
+            ),
+            Error::Model { path, source } => {
+                write!(f, "Failed to load model {:?}: {}", path, source)
+            }
+            Error::Gltf { path, source } => {
+                write!(f, "Failed to load glTF model {:?}: {}", path, source)
+            }
+            Error::InvalidModel { path, reason } => {
+                write!(f, "Invalid model {:?}: {}", path, reason)
+            }
+            Error::PushConstantsTooLarge { size, max_size } => write!(
+                f,
+                "Push constants need {} bytes, but the device supports {}",
+                size, max_size
+            ),
+            Error::UnsupportedShader { path, reason } => {
+                write!(f, "Unsupported shader {:?}: {}", path, reason)
+            }
+            Error::InvalidPasses { path, line, reason }
+            | Error::InvalidSpecialization { path, line, reason } => {
+                write!(f, "{}:{}: {}", path.display(), line, reason)
+            }
+            Error::ShaderCompile {
+                path,
+                line: Some(line),
+                column,
+                message,
+            } => {
+                write!(f, "{}:{}:", path.display(), line)?;
+                if let Some(column) = column {
+                    write!(f, "{}:", column)?;
+                }
+                write!(f, " {}", message)
+            }
+            Error::ShaderCompile { path, message, .. } => {
+                write!(f, "{}: {}", path.display(), message)
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Loading(err) => Some(err),
+            Error::Instance(err) => Some(err),
+            Error::Io { source, .. } => Some(source),
+            Error::Image { source, .. } => Some(source),
+            Error::Model { source, .. } => Some(source),
+            Error::Gltf { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+impl From<ash::LoadingError> for Error {
+    fn from(err: ash::LoadingError) -> Error {
+        Error::Loading(err)
+    }
+}
+
+impl From<ash::InstanceError> for Error {
+    fn from(err: ash::InstanceError) -> Error {
+        Error::Instance(err)
+    }
+}
+
+/// Attaches a description of the failed call to a raw `vk::Result`.
+pub trait VkResultExt<T> {
+    fn vk_context(self, context: &'static str) -> Result<T>;
+}
+
+impl<T> VkResultExt<T> for std::result::Result<T, vk::Result> {
+    fn vk_context(self, context: &'static str) -> Result<T> {
+        self.map_err(|result| Error::Vulkan { context, result })
+    }
+}
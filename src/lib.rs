@@ -1 +1,9 @@
+#![allow(clippy::too_many_arguments, clippy::missing_safety_doc)]
+
+// Lets `#[derive(Vertex)]`, which expands to `::rust_game::...` paths, work inside this crate.
+extern crate self as rust_game;
+
+pub mod error;
 pub mod utility;
+
+pub use error::{Error, Result};
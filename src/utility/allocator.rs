@@ -0,0 +1,439 @@
+use crate::error::{Error, Result, VkResultExt};
+use crate::utility::share;
+
+use ash::version::{DeviceV1_0, InstanceV1_0};
+use ash::vk;
+use std::collections::HashMap;
+use std::ffi::c_void;
+use std::fmt;
+use std::ptr;
+
+// Size of the blocks resources are sub-allocated from, unless the heap is small.
+const DEFAULT_BLOCK_SIZE: vk::DeviceSize = 64 * 1024 * 1024;
+// Heaps up to this size get blocks of an eighth of the heap instead.
+const SMALL_HEAP_SIZE: vk::DeviceSize = 1024 * 1024 * 1024;
+
+/// What an allocation is bound to. Optimal images get other blocks than buffers and linear
+/// images, so neighbouring resources never have to be `bufferImageGranularity` apart.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResourceKind {
+    Buffer,
+    LinearImage,
+    OptimalImage,
+}
+
+impl ResourceKind {
+    fn is_linear(self) -> bool {
+        self != ResourceKind::OptimalImage
+    }
+}
+
+impl fmt::Display for ResourceKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ResourceKind::Buffer => write!(f, "buffer"),
+            ResourceKind::LinearImage => write!(f, "linear image"),
+            ResourceKind::OptimalImage => write!(f, "image"),
+        }
+    }
+}
+
+/// Range of a memory block bound to one buffer or image, given back with
+/// `MemoryAllocator::free`.
+#[derive(Debug, Clone, Copy)]
+pub struct Allocation {
+    memory: vk::DeviceMemory,
+    offset: vk::DeviceSize,
+    size: vk::DeviceSize,
+    // Null unless the memory is host visible.
+    mapped_ptr: *mut c_void,
+}
+
+impl Allocation {
+    /// Stands in for resources that were not created, like a null `vk::DeviceMemory`.
+    /// Freeing it does nothing.
+    pub fn null() -> Allocation {
+        Allocation {
+            memory: vk::DeviceMemory::null(),
+            offset: 0,
+            size: 0,
+            mapped_ptr: ptr::null_mut(),
+        }
+    }
+
+    pub fn memory(&self) -> vk::DeviceMemory {
+        self.memory
+    }
+
+    pub fn offset(&self) -> vk::DeviceSize {
+        self.offset
+    }
+
+    pub fn size(&self) -> vk::DeviceSize {
+        self.size
+    }
+
+    /// Start of the allocation in host memory. Host visible blocks stay mapped while they
+    /// exist, so there is nothing to unmap.
+    pub fn mapped_ptr(&self) -> Result<*mut c_void> {
+        if self.mapped_ptr.is_null() {
+            return Err(Error::Vulkan {
+                context: "Failed to map memory that is not host visible",
+                result: vk::Result::ERROR_MEMORY_MAP_FAILED,
+            });
+        }
+
+        Ok(self.mapped_ptr)
+    }
+}
+
+/// Ranges in use within one block. Free space is searched first fit.
+#[derive(Debug, Clone)]
+pub struct SubAllocator {
+    size: vk::DeviceSize,
+    // Offset and size of each allocation, sorted by offset.
+    used: Vec<(vk::DeviceSize, vk::DeviceSize)>,
+}
+
+impl SubAllocator {
+    pub fn new(size: vk::DeviceSize) -> SubAllocator {
+        SubAllocator { size, used: vec![] }
+    }
+
+    /// Offset of a free range of `size` bytes aligned to `alignment`, a power of two, or
+    /// `None` if no gap is large enough.
+    pub fn allocate(
+        &mut self,
+        size: vk::DeviceSize,
+        alignment: vk::DeviceSize,
+    ) -> Option<vk::DeviceSize> {
+        let mut gap_start = 0;
+        for index in 0..=self.used.len() {
+            let gap_end = self
+                .used
+                .get(index)
+                .map_or(self.size, |&(offset, _)| offset);
+            let offset = align_up(gap_start, alignment);
+            if offset + size <= gap_end {
+                self.used.insert(index, (offset, size));
+                return Some(offset);
+            }
+            if let Some(&(offset, used_size)) = self.used.get(index) {
+                gap_start = offset + used_size;
+            }
+        }
+
+        None
+    }
+
+    /// Frees the range starting at `offset`. Returns false if no allocation starts there.
+    pub fn free(&mut self, offset: vk::DeviceSize) -> bool {
+        match self
+            .used
+            .binary_search_by_key(&offset, |&(used_offset, _)| used_offset)
+        {
+            Ok(index) => {
+                self.used.remove(index);
+                true
+            }
+            Err(_) => false,
+        }
+    }
+
+    pub fn size(&self) -> vk::DeviceSize {
+        self.size
+    }
+
+    pub fn used_bytes(&self) -> vk::DeviceSize {
+        self.used.iter().map(|&(_, size)| size).sum()
+    }
+
+    pub fn allocation_count(&self) -> usize {
+        self.used.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.used.is_empty()
+    }
+}
+
+fn align_up(value: vk::DeviceSize, alignment: vk::DeviceSize) -> vk::DeviceSize {
+    let mask = alignment.max(1) - 1;
+    (value + mask) & !mask
+}
+
+struct MemoryBlock {
+    memory: vk::DeviceMemory,
+    memory_type_index: u32,
+    is_linear: bool,
+    // Holds one allocation larger than the block size and is freed with it.
+    is_dedicated: bool,
+    mapped_ptr: *mut c_void,
+    sub_allocator: SubAllocator,
+    // Kind of each live allocation by offset, for the leak report.
+    kinds: HashMap<vk::DeviceSize, ResourceKind>,
+}
+
+impl MemoryBlock {
+    fn allocate(
+        &mut self,
+        requirements: &vk::MemoryRequirements,
+        kind: ResourceKind,
+    ) -> Option<Allocation> {
+        let offset = self
+            .sub_allocator
+            .allocate(requirements.size, requirements.alignment)?;
+        self.kinds.insert(offset, kind);
+
+        Some(Allocation {
+            memory: self.memory,
+            offset,
+            size: requirements.size,
+            mapped_ptr: if self.mapped_ptr.is_null() {
+                ptr::null_mut()
+            } else {
+                unsafe { (self.mapped_ptr as *mut u8).add(offset as usize) as *mut c_void }
+            },
+        })
+    }
+}
+
+/// Number and size of the device memory allocations and of the resources placed in them.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct AllocatorStats {
+    pub block_count: usize,
+    pub block_bytes: vk::DeviceSize,
+    pub allocation_count: usize,
+    pub allocated_bytes: vk::DeviceSize,
+}
+
+impl fmt::Display for AllocatorStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        const MIB: f64 = 1024.0 * 1024.0;
+        write!(
+            f,
+            "{} allocations ({:.1} MiB) in {} blocks ({:.1} MiB)",
+            self.allocation_count,
+            self.allocated_bytes as f64 / MIB,
+            self.block_count,
+            self.block_bytes as f64 / MIB,
+        )
+    }
+}
+
+/// Places buffers and images in large blocks of device memory, so the number of
+/// `vkAllocateMemory` calls stays far below `maxMemoryAllocationCount`.
+///
+/// Blocks are allocated per memory type and kept until `destroy`, except for dedicated
+/// blocks of resources larger than a block, which are freed with their resource.
+pub struct MemoryAllocator {
+    memory_properties: vk::PhysicalDeviceMemoryProperties,
+    blocks: Vec<MemoryBlock>,
+}
+
+impl MemoryAllocator {
+    pub fn new(instance: &ash::Instance, physical_device: vk::PhysicalDevice) -> MemoryAllocator {
+        MemoryAllocator {
+            memory_properties: unsafe {
+                instance.get_physical_device_memory_properties(physical_device)
+            },
+            blocks: vec![],
+        }
+    }
+
+    /// Finds room for a resource with `requirements` in memory with `required_properties`,
+    /// allocating a new block when the existing ones are full.
+    pub fn allocate(
+        &mut self,
+        device: &ash::Device,
+        requirements: vk::MemoryRequirements,
+        required_properties: vk::MemoryPropertyFlags,
+        kind: ResourceKind,
+    ) -> Result<Allocation> {
+        let memory_types: Vec<u32> = share::memory_type_candidates(
+            requirements.memory_type_bits,
+            required_properties,
+            &self.memory_properties,
+        )
+        .collect();
+        if memory_types.is_empty() {
+            return Err(Error::NoSuitableMemoryType {
+                type_filter: requirements.memory_type_bits,
+                required_properties,
+            });
+        }
+
+        for &memory_type_index in memory_types.iter() {
+            let allocation = self
+                .blocks
+                .iter_mut()
+                .filter(|block| {
+                    block.memory_type_index == memory_type_index
+                        && block.is_linear == kind.is_linear()
+                        && !block.is_dedicated
+                })
+                .find_map(|block| block.allocate(&requirements, kind));
+            if let Some(allocation) = allocation {
+                return Ok(allocation);
+            }
+        }
+
+        // Fall back to the next memory type when a heap is out of memory.
+        let mut last_error = None;
+        for &memory_type_index in memory_types.iter() {
+            let block_size = self.block_size(memory_type_index);
+            let is_dedicated = requirements.size > block_size;
+            let size = if is_dedicated {
+                requirements.size
+            } else {
+                block_size
+            };
+
+            match self.create_block(device, memory_type_index, size, kind, is_dedicated) {
+                Ok(mut block) => {
+                    let allocation = block
+                        .allocate(&requirements, kind)
+                        .expect("a new block fits the allocation");
+                    self.blocks.push(block);
+                    return Ok(allocation);
+                }
+                Err(err) => last_error = Some(err),
+            }
+        }
+
+        Err(last_error.unwrap())
+    }
+
+    /// Makes the range of `allocation` available again. The resource bound to it has to be
+    /// destroyed first.
+    pub fn free(&mut self, device: &ash::Device, allocation: Allocation) {
+        if allocation.memory == vk::DeviceMemory::null() {
+            return;
+        }
+
+        let index = match self
+            .blocks
+            .iter()
+            .position(|block| block.memory == allocation.memory)
+        {
+            Some(index) => index,
+            None => {
+                debug_assert!(false, "freeing memory of another allocator");
+                return;
+            }
+        };
+        let block = &mut self.blocks[index];
+        let is_freed = block.sub_allocator.free(allocation.offset);
+        debug_assert!(is_freed, "freeing an allocation twice");
+        block.kinds.remove(&allocation.offset);
+
+        if block.is_dedicated && block.sub_allocator.is_empty() {
+            let block = self.blocks.swap_remove(index);
+            unsafe {
+                device.free_memory(block.memory, None);
+            }
+        }
+    }
+
+    pub fn stats(&self) -> AllocatorStats {
+        let mut stats = AllocatorStats::default();
+        for block in self.blocks.iter() {
+            stats.block_count += 1;
+            stats.block_bytes += block.sub_allocator.size();
+            stats.allocation_count += block.sub_allocator.allocation_count();
+            stats.allocated_bytes += block.sub_allocator.used_bytes();
+        }
+
+        stats
+    }
+
+    /// Frees all blocks. Allocations that are still live at this point were leaked and get
+    /// reported on stderr.
+    pub fn destroy(&mut self, device: &ash::Device) {
+        let stats = self.stats();
+        if stats.allocation_count > 0 {
+            eprintln!("Leaked GPU memory: {}", stats);
+            for block in self.blocks.iter() {
+                let mut leaks: Vec<_> = block.kinds.iter().collect();
+                leaks.sort_by_key(|&(&offset, _)| offset);
+                for (offset, kind) in leaks {
+                    eprintln!(
+                        "    {} at offset {} of a block in memory type {}",
+                        kind, offset, block.memory_type_index
+                    );
+                }
+            }
+        }
+
+        for block in self.blocks.drain(..) {
+            unsafe {
+                device.free_memory(block.memory, None);
+            }
+        }
+    }
+
+    fn block_size(&self, memory_type_index: u32) -> vk::DeviceSize {
+        let heap_index = self.memory_properties.memory_types[memory_type_index as usize].heap_index;
+        let heap_size = self.memory_properties.memory_heaps[heap_index as usize].size;
+
+        if heap_size <= SMALL_HEAP_SIZE {
+            heap_size / 8
+        } else {
+            DEFAULT_BLOCK_SIZE
+        }
+    }
+
+    fn create_block(
+        &self,
+        device: &ash::Device,
+        memory_type_index: u32,
+        size: vk::DeviceSize,
+        kind: ResourceKind,
+        is_dedicated: bool,
+    ) -> Result<MemoryBlock> {
+        let allocate_info = vk::MemoryAllocateInfo {
+            s_type: vk::StructureType::MEMORY_ALLOCATE_INFO,
+            p_next: ptr::null(),
+            allocation_size: size,
+            memory_type_index,
+        };
+
+        let memory = unsafe {
+            device
+                .allocate_memory(&allocate_info, None)
+                .vk_context("Failed to allocate memory block")?
+        };
+
+        let property_flags =
+            self.memory_properties.memory_types[memory_type_index as usize].property_flags;
+        let mapped_ptr = if property_flags.contains(vk::MemoryPropertyFlags::HOST_VISIBLE) {
+            let result = unsafe {
+                device.map_memory(memory, 0, vk::WHOLE_SIZE, vk::MemoryMapFlags::empty())
+            };
+            match result {
+                Ok(mapped_ptr) => mapped_ptr,
+                Err(result) => {
+                    unsafe {
+                        device.free_memory(memory, None);
+                    }
+                    return Err(Error::Vulkan {
+                        context: "Failed to map memory block",
+                        result,
+                    });
+                }
+            }
+        } else {
+            ptr::null_mut()
+        };
+
+        Ok(MemoryBlock {
+            memory,
+            memory_type_index,
+            is_linear: kind.is_linear(),
+            is_dedicated,
+            mapped_ptr,
+            sub_allocator: SubAllocator::new(size),
+            kinds: HashMap::new(),
+        })
+    }
+}
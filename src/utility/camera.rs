@@ -0,0 +1,41 @@
+use ash::vk;
+use cgmath::{Deg, Matrix4, Point3, Vector3};
+
+/// Perspective camera looking from `eye` at `target`.
+#[derive(Debug, Clone, Copy)]
+pub struct Camera {
+    pub eye: Point3<f32>,
+    pub target: Point3<f32>,
+    pub up: Vector3<f32>,
+    pub fov_y: Deg<f32>,
+    pub z_near: f32,
+    pub z_far: f32,
+}
+
+impl Camera {
+    pub fn view(&self) -> Matrix4<f32> {
+        Matrix4::look_at(self.eye, self.target, self.up)
+    }
+
+    /// Projection for a viewport of `extent`, so it has to be recomputed after resizes to
+    /// keep the aspect ratio.
+    pub fn projection(&self, extent: vk::Extent2D) -> Matrix4<f32> {
+        let aspect = extent.width as f32 / extent.height.max(1) as f32;
+
+        opengl_to_vulkan() * cgmath::perspective(self.fov_y, aspect, self.z_near, self.z_far)
+    }
+}
+
+/// cgmath follows OpenGL conventions: Y points up and depth goes from -1 to 1, while
+/// Vulkan's Y points down and depth goes from 0 to 1.
+fn opengl_to_vulkan() -> Matrix4<f32> {
+    #[rustfmt::skip]
+    let matrix = Matrix4::new(
+        1.0,  0.0, 0.0, 0.0,
+        0.0, -1.0, 0.0, 0.0,
+        0.0,  0.0, 0.5, 0.0,
+        0.0,  0.0, 0.5, 1.0,
+    );
+
+    matrix
+}
@@ -0,0 +1,790 @@
+use crate::error::{Result, VkResultExt};
+use crate::utility::{
+    constants::{DEVICE_EXTENSIONS, VALIDATION_LAYER},
+    debug, share,
+    share::pipeline,
+    structures::SurfaceStuff,
+    tools,
+};
+
+use ash::version::{EntryV1_0, InstanceV1_0};
+use ash::vk;
+use serde_json::{json, Value};
+use std::fmt;
+
+/// Formats whose support usually explains rendering differences between machines: the
+/// swapchain and texture formats the renderer asks for, HDR targets and the depth candidates.
+pub const REPORTED_FORMATS: [vk::Format; 8] = [
+    vk::Format::B8G8R8A8_SRGB,
+    vk::Format::B8G8R8A8_UNORM,
+    vk::Format::R8G8B8A8_SRGB,
+    vk::Format::R8G8B8A8_UNORM,
+    vk::Format::R16G16B16A16_SFLOAT,
+    vk::Format::D32_SFLOAT,
+    vk::Format::D32_SFLOAT_S8_UINT,
+    vk::Format::D24_UNORM_S8_UINT,
+];
+
+/// Everything `vkreport` knows about the Vulkan installation, printed as text through
+/// `Display` or as JSON through `to_json`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CapabilityReport {
+    pub instance: InstanceReport,
+    pub devices: Vec<DeviceReport>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct InstanceReport {
+    /// `None` on Vulkan 1.0 loaders, which cannot report their version.
+    pub api_version: Option<u32>,
+    pub validation_layers_available: bool,
+    pub layers: Vec<LayerReport>,
+    pub extensions: Vec<ExtensionReport>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LayerReport {
+    pub name: String,
+    pub description: String,
+    pub spec_version: u32,
+    pub implementation_version: u32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExtensionReport {
+    pub name: String,
+    pub spec_version: u32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DeviceReport {
+    pub index: usize,
+    pub name: String,
+    pub device_type: String,
+    pub vendor_id: u32,
+    pub device_id: u32,
+    pub api_version: u32,
+    pub driver_version: u32,
+    /// Whether every extension in `DEVICE_EXTENSIONS` is available.
+    pub required_extensions_supported: bool,
+    pub extensions: Vec<ExtensionReport>,
+    pub limits: Vec<(&'static str, Value)>,
+    pub features: Vec<(&'static str, bool)>,
+    pub memory_heaps: Vec<MemoryHeapReport>,
+    pub memory_types: Vec<MemoryTypeReport>,
+    pub queue_families: Vec<QueueFamilyReport>,
+    pub formats: Vec<FormatReport>,
+    /// The format `find_depth_format` would pick, if any candidate is supported.
+    pub depth_format: Option<String>,
+    /// Only available when the report was collected with a surface.
+    pub swapchain: Option<SwapchainReport>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MemoryHeapReport {
+    pub size: vk::DeviceSize,
+    pub flags: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MemoryTypeReport {
+    pub heap_index: u32,
+    pub flags: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct QueueFamilyReport {
+    pub queue_count: u32,
+    pub flags: String,
+    pub timestamp_valid_bits: u32,
+    /// `None` when there was no surface to ask about.
+    pub present_support: Option<bool>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FormatReport {
+    pub format: String,
+    pub linear_tiling: String,
+    pub optimal_tiling: String,
+    pub buffer: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SwapchainReport {
+    pub min_image_count: u32,
+    /// 0 means there is no limit.
+    pub max_image_count: u32,
+    pub current_extent: (u32, u32),
+    pub min_image_extent: (u32, u32),
+    pub max_image_extent: (u32, u32),
+    pub surface_formats: Vec<String>,
+    pub present_modes: Vec<String>,
+}
+
+impl CapabilityReport {
+    /// Queries the instance and every physical device. With a surface, queue family present
+    /// support and swapchain support are included as well.
+    pub fn collect(
+        entry: &ash::Entry,
+        instance: &ash::Instance,
+        surface_stuff: Option<&SurfaceStuff>,
+    ) -> Result<CapabilityReport> {
+        let instance_report = InstanceReport::collect(entry)?;
+        let physical_devices = unsafe {
+            instance
+                .enumerate_physical_devices()
+                .vk_context("Failed to enumerate Physical Devices!")?
+        };
+        let devices = physical_devices
+            .into_iter()
+            .enumerate()
+            .map(|(index, physical_device)| {
+                DeviceReport::collect(instance, physical_device, index, surface_stuff)
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(CapabilityReport {
+            instance: instance_report,
+            devices,
+        })
+    }
+
+    pub fn to_json(&self) -> Value {
+        json!({
+            "instance": self.instance.to_json(),
+            "devices": self.devices.iter().map(DeviceReport::to_json).collect::<Vec<_>>(),
+        })
+    }
+}
+
+impl InstanceReport {
+    pub fn collect(entry: &ash::Entry) -> Result<InstanceReport> {
+        let api_version = entry
+            .try_enumerate_instance_version()
+            .vk_context("Failed to enumerate Instance Version!")?;
+        let validation_layers_available =
+            debug::check_validation_layer_support(entry, &[VALIDATION_LAYER])?;
+        let layers = entry
+            .enumerate_instance_layer_properties()
+            .vk_context("Failed to enumerate Instance Layers Properties!")?
+            .iter()
+            .map(|layer| LayerReport {
+                name: tools::vk_to_string(&layer.layer_name),
+                description: tools::vk_to_string(&layer.description),
+                spec_version: layer.spec_version,
+                implementation_version: layer.implementation_version,
+            })
+            .collect();
+        let extensions = entry
+            .enumerate_instance_extension_properties()
+            .vk_context("Failed to enumerate Instance Extension Properties!")?
+            .iter()
+            .map(ExtensionReport::new)
+            .collect();
+
+        Ok(InstanceReport {
+            api_version,
+            validation_layers_available,
+            layers,
+            extensions,
+        })
+    }
+
+    pub fn to_json(&self) -> Value {
+        json!({
+            "api_version": self.api_version.map(version_string),
+            "validation_layers_available": self.validation_layers_available,
+            "layers": self.layers.iter().map(|layer| json!({
+                "name": layer.name,
+                "description": layer.description,
+                "spec_version": version_string(layer.spec_version),
+                "implementation_version": layer.implementation_version,
+            })).collect::<Vec<_>>(),
+            "extensions": extensions_json(&self.extensions),
+        })
+    }
+}
+
+impl ExtensionReport {
+    fn new(extension: &vk::ExtensionProperties) -> ExtensionReport {
+        ExtensionReport {
+            name: tools::vk_to_string(&extension.extension_name),
+            spec_version: extension.spec_version,
+        }
+    }
+}
+
+impl DeviceReport {
+    pub fn collect(
+        instance: &ash::Instance,
+        physical_device: vk::PhysicalDevice,
+        index: usize,
+        surface_stuff: Option<&SurfaceStuff>,
+    ) -> Result<DeviceReport> {
+        let (properties, features, memory_properties, queue_families, extensions) = unsafe {
+            (
+                instance.get_physical_device_properties(physical_device),
+                instance.get_physical_device_features(physical_device),
+                instance.get_physical_device_memory_properties(physical_device),
+                instance.get_physical_device_queue_family_properties(physical_device),
+                instance
+                    .enumerate_device_extension_properties(physical_device)
+                    .vk_context("Failed to get device extension properties.")?,
+            )
+        };
+
+        let mut queue_family_reports = Vec::with_capacity(queue_families.len());
+        for (family_index, family) in queue_families.iter().enumerate() {
+            let present_support = match surface_stuff {
+                Some(surface_stuff) => Some(unsafe {
+                    surface_stuff
+                        .surface_loader
+                        .get_physical_device_surface_support(
+                            physical_device,
+                            family_index as u32,
+                            surface_stuff.surface,
+                        )
+                        .vk_context("Failed to query for surface support.")?
+                }),
+                None => None,
+            };
+            queue_family_reports.push(QueueFamilyReport {
+                queue_count: family.queue_count,
+                flags: flags_string(family.queue_flags, family.queue_flags.is_empty()),
+                timestamp_valid_bits: family.timestamp_valid_bits,
+                present_support,
+            });
+        }
+
+        let formats = REPORTED_FORMATS
+            .iter()
+            .map(|&format| {
+                let properties = unsafe {
+                    instance.get_physical_device_format_properties(physical_device, format)
+                };
+                let features =
+                    |flags: vk::FormatFeatureFlags| flags_string(flags, flags.is_empty());
+                FormatReport {
+                    format: format!("{:?}", format),
+                    linear_tiling: features(properties.linear_tiling_features),
+                    optimal_tiling: features(properties.optimal_tiling_features),
+                    buffer: features(properties.buffer_features),
+                }
+            })
+            .collect();
+
+        let swapchain = match surface_stuff {
+            Some(surface_stuff) => {
+                let detail = share::query_swapchain_support(physical_device, surface_stuff)?;
+                let extent = |extent: vk::Extent2D| (extent.width, extent.height);
+                Some(SwapchainReport {
+                    min_image_count: detail.capabilities.min_image_count,
+                    max_image_count: detail.capabilities.max_image_count,
+                    current_extent: extent(detail.capabilities.current_extent),
+                    min_image_extent: extent(detail.capabilities.min_image_extent),
+                    max_image_extent: extent(detail.capabilities.max_image_extent),
+                    surface_formats: detail
+                        .formats
+                        .iter()
+                        .map(|format| format!("{:?} {:?}", format.format, format.color_space))
+                        .collect(),
+                    present_modes: detail
+                        .present_modes
+                        .iter()
+                        .map(|mode| format!("{:?}", mode))
+                        .collect(),
+                })
+            }
+            None => None,
+        };
+
+        Ok(DeviceReport {
+            index,
+            name: tools::vk_to_string(&properties.device_name),
+            device_type: format!("{:?}", properties.device_type),
+            vendor_id: properties.vendor_id,
+            device_id: properties.device_id,
+            api_version: properties.api_version,
+            driver_version: properties.driver_version,
+            required_extensions_supported: share::check_device_extension_support(
+                instance,
+                physical_device,
+                &DEVICE_EXTENSIONS,
+            )?,
+            extensions: extensions.iter().map(ExtensionReport::new).collect(),
+            limits: device_limits(&properties.limits),
+            features: device_features(&features),
+            memory_heaps: memory_properties.memory_heaps
+                [..memory_properties.memory_heap_count as usize]
+                .iter()
+                .map(|heap| MemoryHeapReport {
+                    size: heap.size,
+                    flags: flags_string(heap.flags, heap.flags.is_empty()),
+                })
+                .collect(),
+            memory_types: memory_properties.memory_types
+                [..memory_properties.memory_type_count as usize]
+                .iter()
+                .map(|memory_type| MemoryTypeReport {
+                    heap_index: memory_type.heap_index,
+                    flags: flags_string(
+                        memory_type.property_flags,
+                        memory_type.property_flags.is_empty(),
+                    ),
+                })
+                .collect(),
+            queue_families: queue_family_reports,
+            formats,
+            depth_format: pipeline::find_depth_format(instance, physical_device)
+                .ok()
+                .map(|format| format!("{:?}", format)),
+            swapchain,
+        })
+    }
+
+    pub fn to_json(&self) -> Value {
+        let limits: serde_json::Map<_, _> = self
+            .limits
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.clone()))
+            .collect();
+        let features: serde_json::Map<_, _> = self
+            .features
+            .iter()
+            .map(|&(name, supported)| (name.to_owned(), Value::Bool(supported)))
+            .collect();
+        json!({
+            "index": self.index,
+            "name": self.name,
+            "device_type": self.device_type,
+            "vendor_id": format!("{:04x}", self.vendor_id),
+            "device_id": format!("{:04x}", self.device_id),
+            "api_version": version_string(self.api_version),
+            "driver_version": self.driver_version,
+            "required_extensions_supported": self.required_extensions_supported,
+            "extensions": extensions_json(&self.extensions),
+            "limits": limits,
+            "features": features,
+            "memory_heaps": self.memory_heaps.iter().map(|heap| json!({
+                "size": heap.size,
+                "flags": heap.flags,
+            })).collect::<Vec<_>>(),
+            "memory_types": self.memory_types.iter().map(|memory_type| json!({
+                "heap_index": memory_type.heap_index,
+                "flags": memory_type.flags,
+            })).collect::<Vec<_>>(),
+            "queue_families": self.queue_families.iter().map(|family| json!({
+                "queue_count": family.queue_count,
+                "flags": family.flags,
+                "timestamp_valid_bits": family.timestamp_valid_bits,
+                "present_support": family.present_support,
+            })).collect::<Vec<_>>(),
+            "formats": self.formats.iter().map(|format| json!({
+                "format": format.format,
+                "linear_tiling": format.linear_tiling,
+                "optimal_tiling": format.optimal_tiling,
+                "buffer": format.buffer,
+            })).collect::<Vec<_>>(),
+            "depth_format": self.depth_format,
+            "swapchain": self.swapchain.as_ref().map(|swapchain| json!({
+                "min_image_count": swapchain.min_image_count,
+                "max_image_count": swapchain.max_image_count,
+                "current_extent": [swapchain.current_extent.0, swapchain.current_extent.1],
+                "min_image_extent": [swapchain.min_image_extent.0, swapchain.min_image_extent.1],
+                "max_image_extent": [swapchain.max_image_extent.0, swapchain.max_image_extent.1],
+                "surface_formats": swapchain.surface_formats,
+                "present_modes": swapchain.present_modes,
+            })),
+        })
+    }
+}
+
+/// `major.minor.patch` of a packed Vulkan version.
+pub fn version_string(version: u32) -> String {
+    format!(
+        "{}.{}.{}",
+        vk::version_major(version),
+        vk::version_minor(version),
+        vk::version_patch(version)
+    )
+}
+
+/// Every `VkPhysicalDeviceFeatures` member and whether the device supports it.
+pub fn device_features(features: &vk::PhysicalDeviceFeatures) -> Vec<(&'static str, bool)> {
+    macro_rules! features {
+        ($($feature:ident),* $(,)?) => {
+            vec![$((stringify!($feature), features.$feature == vk::TRUE)),*]
+        };
+    }
+
+    features![
+        robust_buffer_access,
+        full_draw_index_uint32,
+        image_cube_array,
+        independent_blend,
+        geometry_shader,
+        tessellation_shader,
+        sample_rate_shading,
+        dual_src_blend,
+        logic_op,
+        multi_draw_indirect,
+        draw_indirect_first_instance,
+        depth_clamp,
+        depth_bias_clamp,
+        fill_mode_non_solid,
+        depth_bounds,
+        wide_lines,
+        large_points,
+        alpha_to_one,
+        multi_viewport,
+        sampler_anisotropy,
+        texture_compression_etc2,
+        texture_compression_astc_ldr,
+        texture_compression_bc,
+        occlusion_query_precise,
+        pipeline_statistics_query,
+        vertex_pipeline_stores_and_atomics,
+        fragment_stores_and_atomics,
+        shader_tessellation_and_geometry_point_size,
+        shader_image_gather_extended,
+        shader_storage_image_extended_formats,
+        shader_storage_image_multisample,
+        shader_storage_image_read_without_format,
+        shader_storage_image_write_without_format,
+        shader_uniform_buffer_array_dynamic_indexing,
+        shader_sampled_image_array_dynamic_indexing,
+        shader_storage_buffer_array_dynamic_indexing,
+        shader_storage_image_array_dynamic_indexing,
+        shader_clip_distance,
+        shader_cull_distance,
+        shader_float64,
+        shader_int64,
+        shader_int16,
+        shader_resource_residency,
+        shader_resource_min_lod,
+        sparse_binding,
+        sparse_residency_buffer,
+        sparse_residency_image2_d,
+        sparse_residency_image3_d,
+        sparse_residency2_samples,
+        sparse_residency4_samples,
+        sparse_residency8_samples,
+        sparse_residency16_samples,
+        sparse_residency_aliased,
+        variable_multisample_rate,
+        inherited_queries,
+    ]
+}
+
+/// Every `VkPhysicalDeviceLimits` member. Sample counts are listed by name, the rest keep
+/// their numeric values.
+pub fn device_limits(limits: &vk::PhysicalDeviceLimits) -> Vec<(&'static str, Value)> {
+    macro_rules! limits {
+        ($($kind:ident $limit:ident),* $(,)?) => {
+            vec![$((stringify!($limit), limits!(@$kind limits.$limit))),*]
+        };
+        (@value $value:expr) => {
+            json!($value)
+        };
+        (@bool $value:expr) => {
+            Value::Bool($value == vk::TRUE)
+        };
+        (@samples $value:expr) => {
+            Value::String(flags_string($value, $value.is_empty()))
+        };
+    }
+
+    limits![
+        value max_image_dimension1_d,
+        value max_image_dimension2_d,
+        value max_image_dimension3_d,
+        value max_image_dimension_cube,
+        value max_image_array_layers,
+        value max_texel_buffer_elements,
+        value max_uniform_buffer_range,
+        value max_storage_buffer_range,
+        value max_push_constants_size,
+        value max_memory_allocation_count,
+        value max_sampler_allocation_count,
+        value buffer_image_granularity,
+        value sparse_address_space_size,
+        value max_bound_descriptor_sets,
+        value max_per_stage_descriptor_samplers,
+        value max_per_stage_descriptor_uniform_buffers,
+        value max_per_stage_descriptor_storage_buffers,
+        value max_per_stage_descriptor_sampled_images,
+        value max_per_stage_descriptor_storage_images,
+        value max_per_stage_descriptor_input_attachments,
+        value max_per_stage_resources,
+        value max_descriptor_set_samplers,
+        value max_descriptor_set_uniform_buffers,
+        value max_descriptor_set_uniform_buffers_dynamic,
+        value max_descriptor_set_storage_buffers,
+        value max_descriptor_set_storage_buffers_dynamic,
+        value max_descriptor_set_sampled_images,
+        value max_descriptor_set_storage_images,
+        value max_descriptor_set_input_attachments,
+        value max_vertex_input_attributes,
+        value max_vertex_input_bindings,
+        value max_vertex_input_attribute_offset,
+        value max_vertex_input_binding_stride,
+        value max_vertex_output_components,
+        value max_tessellation_generation_level,
+        value max_tessellation_patch_size,
+        value max_tessellation_control_per_vertex_input_components,
+        value max_tessellation_control_per_vertex_output_components,
+        value max_tessellation_control_per_patch_output_components,
+        value max_tessellation_control_total_output_components,
+        value max_tessellation_evaluation_input_components,
+        value max_tessellation_evaluation_output_components,
+        value max_geometry_shader_invocations,
+        value max_geometry_input_components,
+        value max_geometry_output_components,
+        value max_geometry_output_vertices,
+        value max_geometry_total_output_components,
+        value max_fragment_input_components,
+        value max_fragment_output_attachments,
+        value max_fragment_dual_src_attachments,
+        value max_fragment_combined_output_resources,
+        value max_compute_shared_memory_size,
+        value max_compute_work_group_count,
+        value max_compute_work_group_invocations,
+        value max_compute_work_group_size,
+        value sub_pixel_precision_bits,
+        value sub_texel_precision_bits,
+        value mipmap_precision_bits,
+        value max_draw_indexed_index_value,
+        value max_draw_indirect_count,
+        value max_sampler_lod_bias,
+        value max_sampler_anisotropy,
+        value max_viewports,
+        value max_viewport_dimensions,
+        value viewport_bounds_range,
+        value viewport_sub_pixel_bits,
+        value min_memory_map_alignment,
+        value min_texel_buffer_offset_alignment,
+        value min_uniform_buffer_offset_alignment,
+        value min_storage_buffer_offset_alignment,
+        value min_texel_offset,
+        value max_texel_offset,
+        value min_texel_gather_offset,
+        value max_texel_gather_offset,
+        value min_interpolation_offset,
+        value max_interpolation_offset,
+        value sub_pixel_interpolation_offset_bits,
+        value max_framebuffer_width,
+        value max_framebuffer_height,
+        value max_framebuffer_layers,
+        samples framebuffer_color_sample_counts,
+        samples framebuffer_depth_sample_counts,
+        samples framebuffer_stencil_sample_counts,
+        samples framebuffer_no_attachments_sample_counts,
+        value max_color_attachments,
+        samples sampled_image_color_sample_counts,
+        samples sampled_image_integer_sample_counts,
+        samples sampled_image_depth_sample_counts,
+        samples sampled_image_stencil_sample_counts,
+        samples storage_image_sample_counts,
+        value max_sample_mask_words,
+        bool timestamp_compute_and_graphics,
+        value timestamp_period,
+        value max_clip_distances,
+        value max_cull_distances,
+        value max_combined_clip_and_cull_distances,
+        value discrete_queue_priorities,
+        value point_size_range,
+        value line_width_range,
+        value point_size_granularity,
+        value line_width_granularity,
+        bool strict_lines,
+        bool standard_sample_locations,
+        value optimal_buffer_copy_offset_alignment,
+        value optimal_buffer_copy_row_pitch_alignment,
+        value non_coherent_atom_size,
+    ]
+}
+
+/// ash prints empty flags as an empty string, which is easy to miss in a report.
+fn flags_string<T: fmt::Debug>(flags: T, is_empty: bool) -> String {
+    if is_empty {
+        "none".to_owned()
+    } else {
+        format!("{:?}", flags)
+    }
+}
+
+fn extensions_json(extensions: &[ExtensionReport]) -> Value {
+    extensions
+        .iter()
+        .map(|extension| (extension.name.clone(), json!(extension.spec_version)))
+        .collect::<serde_json::Map<_, _>>()
+        .into()
+}
+
+fn yes_no(value: bool) -> &'static str {
+    if value {
+        "yes"
+    } else {
+        "no"
+    }
+}
+
+impl fmt::Display for CapabilityReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.instance)?;
+        for device in self.devices.iter() {
+            writeln!(f)?;
+            write!(f, "{}", device)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for InstanceReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Instance")?;
+        match self.api_version {
+            Some(version) => writeln!(f, "  API version: {}", version_string(version))?,
+            None => writeln!(f, "  API version: 1.0")?,
+        }
+        writeln!(
+            f,
+            "  Validation layers available: {}",
+            yes_no(self.validation_layers_available)
+        )?;
+        writeln!(f, "  Layers ({}):", self.layers.len())?;
+        for layer in self.layers.iter() {
+            writeln!(
+                f,
+                "    {} {} (implementation {}): {}",
+                layer.name,
+                version_string(layer.spec_version),
+                layer.implementation_version,
+                layer.description
+            )?;
+        }
+        write_extensions(f, &self.extensions)
+    }
+}
+
+impl fmt::Display for DeviceReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "GPU {}: {}", self.index, self.name)?;
+        writeln!(f, "  Type: {}", self.device_type)?;
+        writeln!(f, "  ID: {:04x}:{:04x}", self.vendor_id, self.device_id)?;
+        writeln!(f, "  API version: {}", version_string(self.api_version))?;
+        writeln!(f, "  Driver version: {}", self.driver_version)?;
+        writeln!(
+            f,
+            "  Required extensions supported: {}",
+            yes_no(self.required_extensions_supported)
+        )?;
+        write_extensions(f, &self.extensions)?;
+
+        writeln!(f, "  Limits:")?;
+        for (name, value) in self.limits.iter() {
+            match value {
+                Value::String(value) => writeln!(f, "    {}: {}", name, value)?,
+                value => writeln!(f, "    {}: {}", name, value)?,
+            }
+        }
+
+        writeln!(f, "  Features:")?;
+        for &(name, supported) in self.features.iter() {
+            writeln!(f, "    {}: {}", name, yes_no(supported))?;
+        }
+
+        writeln!(f, "  Memory heaps:")?;
+        for (index, heap) in self.memory_heaps.iter().enumerate() {
+            writeln!(
+                f,
+                "    {}: {:.1} MiB, {}",
+                index,
+                heap.size as f64 / (1024.0 * 1024.0),
+                heap.flags
+            )?;
+        }
+        writeln!(f, "  Memory types:")?;
+        for (index, memory_type) in self.memory_types.iter().enumerate() {
+            writeln!(
+                f,
+                "    {}: heap {}, {}",
+                index, memory_type.heap_index, memory_type.flags
+            )?;
+        }
+
+        writeln!(f, "  Queue families:")?;
+        for (index, family) in self.queue_families.iter().enumerate() {
+            write!(
+                f,
+                "    {}: {} queue(s), {}, {} timestamp bits",
+                index, family.queue_count, family.flags, family.timestamp_valid_bits
+            )?;
+            match family.present_support {
+                Some(present_support) => writeln!(f, ", present: {}", yes_no(present_support))?,
+                None => writeln!(f)?,
+            }
+        }
+
+        writeln!(f, "  Formats:")?;
+        for format in self.formats.iter() {
+            writeln!(f, "    {}:", format.format)?;
+            writeln!(f, "      linear tiling: {}", format.linear_tiling)?;
+            writeln!(f, "      optimal tiling: {}", format.optimal_tiling)?;
+            writeln!(f, "      buffer: {}", format.buffer)?;
+        }
+        writeln!(
+            f,
+            "  Depth format: {}",
+            self.depth_format.as_deref().unwrap_or("none")
+        )?;
+
+        match &self.swapchain {
+            Some(swapchain) => {
+                writeln!(f, "  Swapchain:")?;
+                writeln!(
+                    f,
+                    "    Image count: {} to {}",
+                    swapchain.min_image_count,
+                    if swapchain.max_image_count == 0 {
+                        "unlimited".to_owned()
+                    } else {
+                        swapchain.max_image_count.to_string()
+                    }
+                )?;
+                writeln!(
+                    f,
+                    "    Current extent: {}x{}",
+                    swapchain.current_extent.0, swapchain.current_extent.1
+                )?;
+                writeln!(
+                    f,
+                    "    Extent range: {}x{} to {}x{}",
+                    swapchain.min_image_extent.0,
+                    swapchain.min_image_extent.1,
+                    swapchain.max_image_extent.0,
+                    swapchain.max_image_extent.1
+                )?;
+                writeln!(
+                    f,
+                    "    Surface formats: {}",
+                    swapchain.surface_formats.join(", ")
+                )?;
+                writeln!(
+                    f,
+                    "    Present modes: {}",
+                    swapchain.present_modes.join(", ")
+                )
+            }
+            None => writeln!(f, "  Swapchain: no surface"),
+        }
+    }
+}
+
+fn write_extensions(f: &mut fmt::Formatter, extensions: &[ExtensionReport]) -> fmt::Result {
+    writeln!(f, "  Extensions ({}):", extensions.len())?;
+    for extension in extensions.iter() {
+        writeln!(f, "    {} v{}", extension.name, extension.spec_version)?;
+    }
+    Ok(())
+}
@@ -1,4 +1,3 @@
-use crate::utility::debug::ValidationInfo;
 use crate::utility::structures::DeviceExtension;
 use ash::vk;
 
@@ -9,13 +8,61 @@ pub const API_VERSION: u32 = vk::make_version(1, 0, 92);
 pub const WINDOW_WIDTH: u32 = 800;
 pub const WINDOW_HEIGHT: u32 = 600;
 
-pub const VALIDATION: ValidationInfo = ValidationInfo {
-    is_enable: true,
-    required_validation_layers: ["VK_LAYER_KHRONOS_validation"],
-};
+// Enabled unless turned off, see `debug::ValidationInfo` for the settings.
+pub const VALIDATION_LAYER: &str = "VK_LAYER_KHRONOS_validation";
+// Validation settings read when it exists and no `--validation-config` is given.
+pub const VALIDATION_CONFIG_PATH: &str = "validation.cfg";
+pub const VALIDATION_ENV_VAR: &str = "RUST_GAME_VALIDATION";
+pub const VALIDATION_SEVERITY_ENV_VAR: &str = "RUST_GAME_VALIDATION_SEVERITY";
+pub const VALIDATION_TYPES_ENV_VAR: &str = "RUST_GAME_VALIDATION_TYPES";
+pub const VALIDATION_LAYERS_ENV_VAR: &str = "RUST_GAME_VALIDATION_LAYERS";
+pub const VALIDATION_SINK_ENV_VAR: &str = "RUST_GAME_VALIDATION_SINK";
+pub const VALIDATION_FAIL_ON_ERROR_ENV_VAR: &str = "RUST_GAME_VALIDATION_FAIL_ON_ERROR";
 
 pub const DEVICE_EXTENSIONS: DeviceExtension = DeviceExtension {
-    names: ["VK_KHR_swapchain"],
+    names: &["VK_KHR_swapchain"],
 };
+// Offscreen rendering does not present, so no swapchain is needed.
+pub const HEADLESS_DEVICE_EXTENSIONS: DeviceExtension = DeviceExtension { names: &[] };
+
+#[cfg(feature = "runtime-shaders")]
+pub const VERT_SHADER_PATH: &str = "shaders/shader.vert";
+#[cfg(feature = "runtime-shaders")]
+pub const FRAG_SHADER_PATH: &str = "shaders/shader.frag";
+#[cfg(not(feature = "runtime-shaders"))]
+pub const VERT_SHADER_PATH: &str = "shaders/vert.spv";
+#[cfg(not(feature = "runtime-shaders"))]
+pub const FRAG_SHADER_PATH: &str = "shaders/frag.spv";
+
+// Used with Shadertoy fragment shaders, which need the `runtime-shaders` feature.
+pub const FULLSCREEN_VERT_SHADER_PATH: &str = "shaders/fullscreen.vert";
+pub const FULLSCREEN_BUFFER_VERT_SHADER_PATH: &str = "shaders/fullscreen_buffer.vert";
+
+#[cfg(feature = "runtime-shaders")]
+pub const MESH_VERT_SHADER_PATH: &str = "shaders/mesh.vert";
+#[cfg(feature = "runtime-shaders")]
+pub const MESH_FRAG_SHADER_PATH: &str = "shaders/mesh.frag";
+#[cfg(not(feature = "runtime-shaders"))]
+pub const MESH_VERT_SHADER_PATH: &str = "shaders/mesh_vert.spv";
+#[cfg(not(feature = "runtime-shaders"))]
+pub const MESH_FRAG_SHADER_PATH: &str = "shaders/mesh_frag.spv";
+
+// Loaded when it exists and no other texture is given; a white texture is bound otherwise.
+pub const TEXTURE_PATH: &str = "assets/texture.jpg";
+// Rendered by `--model` when no path follows it.
+pub const MODEL_PATH: &str = "assets/viking_room.obj";
+
+// Pipelines compiled by previous runs, see `pipeline_cache::default_cache_path`.
+pub const PIPELINE_CACHE_FILE_NAME: &str = "pipeline_cache.bin";
+
+// Specialization constants of the window's shaders, used when it exists and no
+// `--specialization` is given. See `specialization::SpecializationConfig`.
+pub const SPECIALIZATION_CONFIG_PATH: &str = "shaders/specialization.cfg";
+
+// Selects the GPU like `--gpu`, see `device_selection::DeviceSelector`.
+pub const GPU_ENV_VAR: &str = "RUST_GAME_GPU";
+
+// Anti-aliasing of the window unless `--msaa` is given, lowered to what the device supports.
+pub const DEFAULT_MSAA_SAMPLES: u32 = 4;
 
 pub const MAX_FRAMES_IN_FLIGHT: usize = 2;
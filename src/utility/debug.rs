@@ -1,96 +1,359 @@
-use crate::utility::tools;
+use crate::error::{Error, Result, VkResultExt};
+use crate::utility::{
+    constants::{
+        VALIDATION_ENV_VAR, VALIDATION_FAIL_ON_ERROR_ENV_VAR, VALIDATION_LAYER,
+        VALIDATION_LAYERS_ENV_VAR, VALIDATION_SEVERITY_ENV_VAR, VALIDATION_SINK_ENV_VAR,
+        VALIDATION_TYPES_ENV_VAR,
+    },
+    debug_messenger::{DebugMessage, DebugMessenger, DebugSink, StderrSink},
+    tools,
+};
 
 use ash::{version::EntryV1_0, vk};
-use std::ffi::CStr;
+use std::ffi::CString;
 use std::os::raw::c_void;
+use std::path::Path;
 use std::ptr;
 
+/// Whether validation is on, which layers are enabled and which messages the debug
+/// messenger reports.
+///
+/// Settings are applied in order from a config file, the environment and the command line,
+/// so later ones win. Each source uses the same settings:
+///
+/// | Config file     | Environment                           | Command line                 |
+/// |-----------------|---------------------------------------|------------------------------|
+/// | `validation`    | `RUST_GAME_VALIDATION`                | `--validation`               |
+/// | `severity`      | `RUST_GAME_VALIDATION_SEVERITY`       | `--validation-severity`      |
+/// | `types`         | `RUST_GAME_VALIDATION_TYPES`          | `--validation-types`         |
+/// | `layers`        | `RUST_GAME_VALIDATION_LAYERS`         | `--validation-layers`        |
+/// | `sink`          | `RUST_GAME_VALIDATION_SINK`           | `--validation-sink`          |
+/// | `fail_on_error` | `RUST_GAME_VALIDATION_FAIL_ON_ERROR`  | `--validation-fail-on-error` |
+///
+/// `validation` and `fail_on_error` are `on` or `off`, `sink` is `stderr`, `log` or
+/// `memory`. The others take comma separated lists: severities out of `verbose`, `info`,
+/// `warning` and `error`, types out of `general`, `validation` and `performance`, and
+/// layers to enable next to the Khronos validation layer. In the config file each line is
+/// a setting name followed by its value, `#` starts a comment:
+///
+/// ```text
+/// validation on
+/// severity   warning, error
+/// layers     VK_LAYER_LUNARG_api_dump
+/// ```
+#[derive(Debug, Clone, PartialEq)]
 pub struct ValidationInfo {
     pub is_enable: bool,
-    pub required_validation_layers: [&'static str; 1],
+    /// The Khronos validation layer followed by any extra layers.
+    pub required_validation_layers: Vec<String>,
+    pub message_severity: vk::DebugUtilsMessageSeverityFlagsEXT,
+    pub message_type: vk::DebugUtilsMessageTypeFlagsEXT,
+    pub sink: DebugSinkKind,
+    /// Whether reported errors make `DebugMessenger::check` fail, so the application exits
+    /// with an error and tests fail.
+    pub fail_on_error: bool,
+}
+
+/// Where the debug messenger writes messages, see `debug_messenger::DebugSink`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DebugSinkKind {
+    Stderr,
+    /// The `log` crate, with the `vulkan` target.
+    Log,
+    /// Kept in memory, see `DebugMessenger::messages`.
+    Memory,
+}
+
+impl Default for ValidationInfo {
+    fn default() -> ValidationInfo {
+        ValidationInfo {
+            is_enable: true,
+            required_validation_layers: vec![VALIDATION_LAYER.to_owned()],
+            message_severity: vk::DebugUtilsMessageSeverityFlagsEXT::WARNING
+                | vk::DebugUtilsMessageSeverityFlagsEXT::ERROR,
+            message_type: vk::DebugUtilsMessageTypeFlagsEXT::GENERAL
+                | vk::DebugUtilsMessageTypeFlagsEXT::PERFORMANCE
+                | vk::DebugUtilsMessageTypeFlagsEXT::VALIDATION,
+            sink: DebugSinkKind::Stderr,
+            fail_on_error: false,
+        }
+    }
+}
+
+impl ValidationInfo {
+    pub fn disabled() -> ValidationInfo {
+        ValidationInfo {
+            is_enable: false,
+            ..ValidationInfo::default()
+        }
+    }
+
+    /// The defaults, overridden by the config file at `config_path` if there is one, then by
+    /// the environment and then by `args`.
+    pub fn resolve(config_path: Option<&Path>, args: &[String]) -> Result<ValidationInfo> {
+        let mut validation = ValidationInfo::default();
+        if let Some(config_path) = config_path {
+            validation.load(config_path)?;
+        }
+        validation.apply_env()?;
+        validation.apply_args(args)?;
+
+        Ok(validation)
+    }
+
+    pub fn load(&mut self, path: &Path) -> Result<()> {
+        let source = std::fs::read_to_string(path).map_err(|source| Error::Io {
+            path: path.to_path_buf(),
+            source,
+        })?;
+
+        for (index, line) in source.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+            let mut fields = line.splitn(2, char::is_whitespace);
+            let name = fields.next().unwrap_or("");
+            let value = fields.next().unwrap_or("").trim();
+            self.set(name, value)
+                .map_err(|reason| Error::InvalidValidationSetting {
+                    origin: format!("{}:{}", path.display(), index + 1),
+                    reason,
+                })?;
+        }
+
+        Ok(())
+    }
+
+    /// Applies the `RUST_GAME_VALIDATION*` variables that are set and not empty.
+    pub fn apply_env(&mut self) -> Result<()> {
+        for &(variable, name) in [
+            (VALIDATION_ENV_VAR, "validation"),
+            (VALIDATION_SEVERITY_ENV_VAR, "severity"),
+            (VALIDATION_TYPES_ENV_VAR, "types"),
+            (VALIDATION_LAYERS_ENV_VAR, "layers"),
+            (VALIDATION_SINK_ENV_VAR, "sink"),
+            (VALIDATION_FAIL_ON_ERROR_ENV_VAR, "fail_on_error"),
+        ]
+        .iter()
+        {
+            let value = match std::env::var(variable) {
+                Ok(value) if !value.trim().is_empty() => value,
+                _ => continue,
+            };
+            self.set(name, &value)
+                .map_err(|reason| Error::InvalidValidationSetting {
+                    origin: variable.to_owned(),
+                    reason,
+                })?;
+        }
+
+        Ok(())
+    }
+
+    /// Applies the `--validation*` flags from the command line arguments.
+    pub fn apply_args(&mut self, args: &[String]) -> Result<()> {
+        for &(flag, name) in [
+            ("--validation", "validation"),
+            ("--validation-severity", "severity"),
+            ("--validation-types", "types"),
+            ("--validation-layers", "layers"),
+            ("--validation-sink", "sink"),
+            ("--validation-fail-on-error", "fail_on_error"),
+        ]
+        .iter()
+        {
+            let position = match args.iter().position(|arg| arg == flag) {
+                Some(position) => position,
+                None => continue,
+            };
+            let value = args.get(position + 1).map(String::as_str).unwrap_or("");
+            self.set(name, value)
+                .map_err(|reason| Error::InvalidValidationSetting {
+                    origin: flag.to_owned(),
+                    reason,
+                })?;
+        }
+
+        Ok(())
+    }
+
+    fn set(&mut self, name: &str, value: &str) -> std::result::Result<(), String> {
+        let mut items = value
+            .split(',')
+            .map(str::trim)
+            .filter(|item| !item.is_empty())
+            .peekable();
+        if items.peek().is_none() {
+            return Err(format!("'{}' expects a value", name));
+        }
+
+        let switch = |value: &str| match value.trim() {
+            "on" | "true" | "1" => Ok(true),
+            "off" | "false" | "0" => Ok(false),
+            value => Err(format!("expected 'on' or 'off', got '{}'", value)),
+        };
+        match name {
+            "validation" => self.is_enable = switch(value)?,
+            "fail_on_error" => self.fail_on_error = switch(value)?,
+            "sink" => {
+                self.sink = match value.trim() {
+                    "stderr" => DebugSinkKind::Stderr,
+                    "log" => DebugSinkKind::Log,
+                    "memory" => DebugSinkKind::Memory,
+                    value => return Err(format!("unknown sink '{}'", value)),
+                }
+            }
+            "severity" => {
+                self.message_severity = vk::DebugUtilsMessageSeverityFlagsEXT::empty();
+                for item in items {
+                    self.message_severity |= match item {
+                        "verbose" => vk::DebugUtilsMessageSeverityFlagsEXT::VERBOSE,
+                        "info" => vk::DebugUtilsMessageSeverityFlagsEXT::INFO,
+                        "warning" => vk::DebugUtilsMessageSeverityFlagsEXT::WARNING,
+                        "error" => vk::DebugUtilsMessageSeverityFlagsEXT::ERROR,
+                        _ => return Err(format!("unknown severity '{}'", item)),
+                    };
+                }
+            }
+            "types" => {
+                self.message_type = vk::DebugUtilsMessageTypeFlagsEXT::empty();
+                for item in items {
+                    self.message_type |= match item {
+                        "general" => vk::DebugUtilsMessageTypeFlagsEXT::GENERAL,
+                        "validation" => vk::DebugUtilsMessageTypeFlagsEXT::VALIDATION,
+                        "performance" => vk::DebugUtilsMessageTypeFlagsEXT::PERFORMANCE,
+                        _ => return Err(format!("unknown message type '{}'", item)),
+                    };
+                }
+            }
+            "layers" => {
+                for layer in items {
+                    // Layer names are handed to Vulkan as C strings.
+                    if layer.contains('\0') {
+                        return Err(format!(
+                            "layer '{}' contains a NUL byte",
+                            layer.escape_debug()
+                        ));
+                    }
+                    if !self
+                        .required_validation_layers
+                        .iter()
+                        .any(|name| name == layer)
+                    {
+                        self.required_validation_layers.push(layer.to_owned());
+                    }
+                }
+            }
+            _ => return Err(format!("unknown setting '{}'", name)),
+        }
+
+        Ok(())
+    }
+
+    /// Drops the layers that are not installed, with a warning for each. Validation is
+    /// turned off when none of the layers is left, so a missing Vulkan SDK does not keep the
+    /// application from starting.
+    pub fn with_available_layers(&self, entry: &ash::Entry) -> Result<ValidationInfo> {
+        if !self.is_enable {
+            return Ok(self.clone());
+        }
+
+        let layer_properties = entry
+            .enumerate_instance_layer_properties()
+            .vk_context("Failed to enumerate Instance Layers Properties!")?;
+        let mut validation = self.clone();
+        validation.required_validation_layers.retain(|layer_name| {
+            let is_available = layer_properties
+                .iter()
+                .any(|layer| tools::vk_to_string(&layer.layer_name) == *layer_name);
+            if !is_available {
+                eprintln!("Warning: layer {} is not available", layer_name);
+            }
+            is_available
+        });
+
+        if validation.required_validation_layers.is_empty() {
+            eprintln!("Warning: validation is disabled, install the Vulkan SDK to enable it");
+            validation.is_enable = false;
+        }
+
+        Ok(validation)
+    }
 }
 
 unsafe extern "system" fn vulkan_debug_utils_callback(
     message_severity: vk::DebugUtilsMessageSeverityFlagsEXT,
     message_type: vk::DebugUtilsMessageTypeFlagsEXT,
     p_callback_data: *const vk::DebugUtilsMessengerCallbackDataEXT,
-    _p_user_data: *mut c_void,
+    p_user_data: *mut c_void,
 ) -> vk::Bool32 {
-    let severity = match message_severity {
-        vk::DebugUtilsMessageSeverityFlagsEXT::VERBOSE => "[Verbose]",
-        vk::DebugUtilsMessageSeverityFlagsEXT::WARNING => "[Warning]",
-        vk::DebugUtilsMessageSeverityFlagsEXT::ERROR => "[Error]",
-        vk::DebugUtilsMessageSeverityFlagsEXT::INFO => "[Info]",
-        _ => "[Unknown]",
-    };
-    let types = match message_type {
-        vk::DebugUtilsMessageTypeFlagsEXT::GENERAL => "[General]",
-        vk::DebugUtilsMessageTypeFlagsEXT::PERFORMANCE => "[Performance]",
-        vk::DebugUtilsMessageTypeFlagsEXT::VALIDATION => "[Validation]",
-        _ => "[Unknown]",
-    };
-    let message = CStr::from_ptr((*p_callback_data).p_message);
-    println!("[Debug]{}{}{:?}", severity, types, message);
+    let message = DebugMessage::from_callback_data(message_severity, message_type, p_callback_data);
+    match (p_user_data as *const DebugMessenger).as_ref() {
+        Some(messenger) => messenger.emit(&message),
+        None => StderrSink.write(&message),
+    }
 
     vk::FALSE
 }
 
-pub fn populate_debug_messenger_create_info() -> vk::DebugUtilsMessengerCreateInfoEXT {
+/// `messenger` receives the messages, so it has to outlive the messenger created from the
+/// returned info.
+pub fn populate_debug_messenger_create_info(
+    validation: &ValidationInfo,
+    messenger: &DebugMessenger,
+) -> vk::DebugUtilsMessengerCreateInfoEXT {
     vk::DebugUtilsMessengerCreateInfoEXT {
         s_type: vk::StructureType::DEBUG_UTILS_MESSENGER_CREATE_INFO_EXT,
         p_next: ptr::null(),
         flags: vk::DebugUtilsMessengerCreateFlagsEXT::empty(),
-        message_severity: vk::DebugUtilsMessageSeverityFlagsEXT::WARNING |
-            // vk::DebugUtilsMessageSeverityFlagsEXT::VERBOSE |
-            // vk::DebugUtilsMessageSeverityFlagsEXT::INFO |
-            vk::DebugUtilsMessageSeverityFlagsEXT::ERROR,
-        message_type: vk::DebugUtilsMessageTypeFlagsEXT::GENERAL
-            | vk::DebugUtilsMessageTypeFlagsEXT::PERFORMANCE
-            | vk::DebugUtilsMessageTypeFlagsEXT::VALIDATION,
+        message_severity: validation.message_severity,
+        message_type: validation.message_type,
         pfn_user_callback: Some(vulkan_debug_utils_callback),
-        p_user_data: ptr::null_mut(),
+        p_user_data: messenger as *const DebugMessenger as *mut c_void,
     }
 }
 
 pub fn setup_debug_utils(
-    is_enable_debug: bool,
+    validation: &ValidationInfo,
+    messenger: &DebugMessenger,
     entry: &ash::Entry,
     instance: &ash::Instance,
-) -> (ash::extensions::ext::DebugUtils, vk::DebugUtilsMessengerEXT) {
+) -> Result<(ash::extensions::ext::DebugUtils, vk::DebugUtilsMessengerEXT)> {
     let debug_utils_loader = ash::extensions::ext::DebugUtils::new(entry, instance);
 
-    if !is_enable_debug {
-        (debug_utils_loader, ash::vk::DebugUtilsMessengerEXT::null())
+    if !validation.is_enable {
+        Ok((debug_utils_loader, ash::vk::DebugUtilsMessengerEXT::null()))
     } else {
-        let messenger_ci = populate_debug_messenger_create_info();
+        let messenger_ci = populate_debug_messenger_create_info(validation, messenger);
         let utils_messenger = unsafe {
             debug_utils_loader
                 .create_debug_utils_messenger(&messenger_ci, None)
-                .expect("Debug Utils Callback")
+                .vk_context("Debug Utils Callback")?
         };
 
-        (debug_utils_loader, utils_messenger)
+        Ok((debug_utils_loader, utils_messenger))
     }
 }
 
 pub fn check_validation_layer_support(
     entry: &ash::Entry,
-    required_validation_layers: &Vec<&str>,
-) -> bool {
+    required_validation_layers: &[&str],
+) -> Result<bool> {
     // if support validation layer, then return true
 
     let layer_properties = entry
         .enumerate_instance_layer_properties()
-        .expect("Failed to enumerate Instance Layers Properties!");
+        .vk_context("Failed to enumerate Instance Layers Properties!")?;
 
-    if layer_properties.len() <= 0 {
+    if layer_properties.is_empty() {
         eprintln!("No available layers.");
-        return false;
+        return Ok(false);
     } else {
-        println!("Instance Available Layers: ");
+        eprintln!("Instance Available Layers: ");
         for layer in layer_properties.iter() {
             let layer_name = tools::vk_to_string(&layer.layer_name);
-            println!("\t{}", layer_name);
+            eprintln!("\t{}", layer_name);
         }
     }
 
@@ -105,10 +368,85 @@ pub fn check_validation_layer_support(
             }
         }
 
-        if is_layer_found == false {
-            return false;
+        if !is_layer_found {
+            return Ok(false);
+        }
+    }
+
+    Ok(true)
+}
+
+/// Names objects and labels command buffers through `VK_EXT_debug_utils`, so validation
+/// messages and captures show "depth image" instead of a raw handle.
+#[derive(Clone)]
+pub struct DebugMarker {
+    debug_utils_loader: ash::extensions::ext::DebugUtils,
+    device: vk::Device,
+}
+
+impl DebugMarker {
+    pub fn new(
+        debug_utils_loader: &ash::extensions::ext::DebugUtils,
+        device: &ash::Device,
+    ) -> DebugMarker {
+        DebugMarker {
+            debug_utils_loader: debug_utils_loader.clone(),
+            device: device.handle(),
+        }
+    }
+
+    /// Null handles, e.g. a color image that only exists with MSAA, are skipped.
+    pub fn set_name<T: vk::Handle + Copy>(&self, object: T, name: &str) -> Result<()> {
+        if object.as_raw() == 0 {
+            return Ok(());
+        }
+
+        let object_name = CString::new(name).unwrap();
+        let name_info = vk::DebugUtilsObjectNameInfoEXT {
+            s_type: vk::StructureType::DEBUG_UTILS_OBJECT_NAME_INFO_EXT,
+            p_next: ptr::null(),
+            object_type: T::TYPE,
+            object_handle: object.as_raw(),
+            p_object_name: object_name.as_ptr(),
+        };
+
+        unsafe {
+            self.debug_utils_loader
+                .debug_utils_set_object_name(self.device, &name_info)
+                .vk_context("Failed to set debug object name")
+        }
+    }
+
+    /// Names `objects` as "`name` 0", "`name` 1" and so on.
+    pub fn set_names<T: vk::Handle + Copy>(&self, objects: &[T], name: &str) -> Result<()> {
+        for (i, &object) in objects.iter().enumerate() {
+            self.set_name(object, &format!("{} {}", name, i))?;
         }
+
+        Ok(())
     }
 
-    true
+    /// Opens a label in `command_buffer` that lasts until the matching `cmd_end_label`.
+    pub fn cmd_begin_label(&self, command_buffer: vk::CommandBuffer, name: &str) {
+        let label_name = CString::new(name).unwrap();
+        let label = vk::DebugUtilsLabelEXT {
+            s_type: vk::StructureType::DEBUG_UTILS_LABEL_EXT,
+            p_next: ptr::null(),
+            p_label_name: label_name.as_ptr(),
+            // All zero means no color.
+            color: [0.0; 4],
+        };
+
+        unsafe {
+            self.debug_utils_loader
+                .cmd_begin_debug_utils_label(command_buffer, &label);
+        }
+    }
+
+    pub fn cmd_end_label(&self, command_buffer: vk::CommandBuffer) {
+        unsafe {
+            self.debug_utils_loader
+                .cmd_end_debug_utils_label(command_buffer);
+        }
+    }
 }
@@ -0,0 +1,308 @@
+use crate::error::{Error, Result};
+use crate::utility::debug::{DebugSinkKind, ValidationInfo};
+
+use ash::vk;
+use std::ffi::CStr;
+use std::fmt;
+use std::os::raw::c_char;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum DebugSeverity {
+    Verbose,
+    Info,
+    Warning,
+    Error,
+}
+
+impl DebugSeverity {
+    /// The highest severity in `flags`; the debug messenger only ever sets one bit.
+    pub fn from_flags(flags: vk::DebugUtilsMessageSeverityFlagsEXT) -> DebugSeverity {
+        if flags.contains(vk::DebugUtilsMessageSeverityFlagsEXT::ERROR) {
+            DebugSeverity::Error
+        } else if flags.contains(vk::DebugUtilsMessageSeverityFlagsEXT::WARNING) {
+            DebugSeverity::Warning
+        } else if flags.contains(vk::DebugUtilsMessageSeverityFlagsEXT::INFO) {
+            DebugSeverity::Info
+        } else {
+            DebugSeverity::Verbose
+        }
+    }
+
+    fn log_level(self) -> log::Level {
+        match self {
+            DebugSeverity::Verbose => log::Level::Trace,
+            DebugSeverity::Info => log::Level::Info,
+            DebugSeverity::Warning => log::Level::Warn,
+            DebugSeverity::Error => log::Level::Error,
+        }
+    }
+}
+
+impl fmt::Display for DebugSeverity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            DebugSeverity::Verbose => "Verbose",
+            DebugSeverity::Info => "Info",
+            DebugSeverity::Warning => "Warning",
+            DebugSeverity::Error => "Error",
+        })
+    }
+}
+
+/// An object a message is about, with the name given by `vkSetDebugUtilsObjectNameEXT`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DebugObject {
+    pub object_type: vk::ObjectType,
+    pub handle: u64,
+    pub name: Option<String>,
+}
+
+impl fmt::Display for DebugObject {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?} {:#x}", self.object_type, self.handle)?;
+        if let Some(name) = &self.name {
+            write!(f, " '{}'", name)?;
+        }
+        Ok(())
+    }
+}
+
+/// A message of the debug messenger with everything the callback data carries.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DebugMessage {
+    pub severity: DebugSeverity,
+    pub message_type: vk::DebugUtilsMessageTypeFlagsEXT,
+    /// The VUID for validation errors, e.g. `VUID-vkCmdDraw-None-02859`.
+    pub message_id_name: Option<String>,
+    pub message_id_number: i32,
+    pub message: String,
+    pub objects: Vec<DebugObject>,
+    /// Labels of the queue the message is about, innermost last.
+    pub queue_labels: Vec<String>,
+    /// Labels of the command buffer the message is about, innermost last.
+    pub command_buffer_labels: Vec<String>,
+}
+
+impl DebugMessage {
+    /// # Safety
+    ///
+    /// `callback_data` has to be the valid pointer passed to a debug messenger callback.
+    pub unsafe fn from_callback_data(
+        message_severity: vk::DebugUtilsMessageSeverityFlagsEXT,
+        message_type: vk::DebugUtilsMessageTypeFlagsEXT,
+        callback_data: *const vk::DebugUtilsMessengerCallbackDataEXT,
+    ) -> DebugMessage {
+        let callback_data = &*callback_data;
+        let objects = raw_slice(callback_data.p_objects, callback_data.object_count)
+            .iter()
+            .map(|object| DebugObject {
+                object_type: object.object_type,
+                handle: object.object_handle,
+                name: raw_string(object.p_object_name),
+            })
+            .collect();
+        let labels = |labels: *const vk::DebugUtilsLabelEXT, count: u32| {
+            raw_slice(labels, count)
+                .iter()
+                .filter_map(|label| raw_string(label.p_label_name))
+                .collect()
+        };
+
+        DebugMessage {
+            severity: DebugSeverity::from_flags(message_severity),
+            message_type,
+            message_id_name: raw_string(callback_data.p_message_id_name),
+            message_id_number: callback_data.message_id_number,
+            message: raw_string(callback_data.p_message).unwrap_or_default(),
+            objects,
+            queue_labels: labels(
+                callback_data.p_queue_labels,
+                callback_data.queue_label_count,
+            ),
+            command_buffer_labels: labels(
+                callback_data.p_cmd_buf_labels,
+                callback_data.cmd_buf_label_count,
+            ),
+        }
+    }
+}
+
+unsafe fn raw_string(raw: *const c_char) -> Option<String> {
+    if raw.is_null() {
+        None
+    } else {
+        Some(CStr::from_ptr(raw).to_string_lossy().into_owned())
+    }
+}
+
+unsafe fn raw_slice<'a, T>(raw: *const T, count: u32) -> &'a [T] {
+    if raw.is_null() || count == 0 {
+        &[]
+    } else {
+        std::slice::from_raw_parts(raw, count as usize)
+    }
+}
+
+impl fmt::Display for DebugMessage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "[{}][{:?}]", self.severity, self.message_type)?;
+        if let Some(message_id_name) = &self.message_id_name {
+            write!(f, " {} ({:#x})", message_id_name, self.message_id_number)?;
+        }
+        write!(f, " {}", self.message)?;
+        for object in self.objects.iter() {
+            write!(f, "\n    object: {}", object)?;
+        }
+        if !self.queue_labels.is_empty() {
+            write!(f, "\n    queue labels: {}", self.queue_labels.join(" > "))?;
+        }
+        if !self.command_buffer_labels.is_empty() {
+            write!(
+                f,
+                "\n    command buffer labels: {}",
+                self.command_buffer_labels.join(" > ")
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Where debug messages end up. Sinks are called from the driver's threads.
+pub trait DebugSink: Send + Sync {
+    fn write(&self, message: &DebugMessage);
+
+    /// Messages kept by the sink, only `MemorySink` keeps any.
+    fn messages(&self) -> Vec<DebugMessage> {
+        vec![]
+    }
+}
+
+pub struct StderrSink;
+
+impl DebugSink for StderrSink {
+    fn write(&self, message: &DebugMessage) {
+        eprintln!("[Debug]{}", message);
+    }
+}
+
+/// Forwards messages to the `log` crate with the `vulkan` target.
+pub struct LogSink;
+
+impl DebugSink for LogSink {
+    fn write(&self, message: &DebugMessage) {
+        log::log!(target: "vulkan", message.severity.log_level(), "{}", message);
+    }
+}
+
+/// Keeps every message, e.g. for tests to inspect.
+#[derive(Default)]
+pub struct MemorySink {
+    messages: Mutex<Vec<DebugMessage>>,
+}
+
+impl DebugSink for MemorySink {
+    fn write(&self, message: &DebugMessage) {
+        self.messages.lock().unwrap().push(message.clone());
+    }
+
+    fn messages(&self) -> Vec<DebugMessage> {
+        self.messages.lock().unwrap().clone()
+    }
+}
+
+/// Number of messages per severity.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DebugCounts {
+    pub verbose: u32,
+    pub info: u32,
+    pub warning: u32,
+    pub error: u32,
+}
+
+impl fmt::Display for DebugCounts {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} errors, {} warnings, {} info, {} verbose",
+            self.error, self.warning, self.info, self.verbose
+        )
+    }
+}
+
+/// State behind the debug messenger callback: dispatches every message to the sinks and
+/// counts them per severity.
+///
+/// The messenger is handed to Vulkan as user data, so it has to stay at the same address
+/// until the instance is destroyed; owners keep it in an `Arc`.
+pub struct DebugMessenger {
+    sinks: Vec<Arc<dyn DebugSink>>,
+    // Indexed by `DebugSeverity`.
+    counts: [AtomicU32; 4],
+    fail_on_error: bool,
+}
+
+impl DebugMessenger {
+    pub fn new(sinks: Vec<Arc<dyn DebugSink>>, fail_on_error: bool) -> DebugMessenger {
+        DebugMessenger {
+            sinks,
+            counts: Default::default(),
+            fail_on_error,
+        }
+    }
+
+    /// The messenger with the sink and fail on error mode `validation` asks for.
+    pub fn from_validation(validation: &ValidationInfo) -> DebugMessenger {
+        let sink: Arc<dyn DebugSink> = match validation.sink {
+            DebugSinkKind::Stderr => Arc::new(StderrSink),
+            DebugSinkKind::Log => Arc::new(LogSink),
+            DebugSinkKind::Memory => Arc::new(MemorySink::default()),
+        };
+
+        DebugMessenger::new(vec![sink], validation.fail_on_error)
+    }
+
+    pub fn emit(&self, message: &DebugMessage) {
+        self.counts[message.severity as usize].fetch_add(1, Ordering::Relaxed);
+        for sink in self.sinks.iter() {
+            sink.write(message);
+        }
+    }
+
+    pub fn counts(&self) -> DebugCounts {
+        let count =
+            |severity: DebugSeverity| self.counts[severity as usize].load(Ordering::Relaxed);
+        DebugCounts {
+            verbose: count(DebugSeverity::Verbose),
+            info: count(DebugSeverity::Info),
+            warning: count(DebugSeverity::Warning),
+            error: count(DebugSeverity::Error),
+        }
+    }
+
+    /// Messages kept by the sinks, see `MemorySink`.
+    pub fn messages(&self) -> Vec<DebugMessage> {
+        self.sinks.iter().flat_map(|sink| sink.messages()).collect()
+    }
+
+    /// In fail on error mode, fails once any error was reported.
+    pub fn check(&self) -> Result<()> {
+        let counts = self.counts();
+        if self.fail_on_error && counts.error > 0 {
+            Err(Error::ValidationFailed(counts))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl fmt::Debug for DebugMessenger {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("DebugMessenger")
+            .field("sinks", &self.sinks.len())
+            .field("counts", &self.counts())
+            .field("fail_on_error", &self.fail_on_error)
+            .finish()
+    }
+}
@@ -0,0 +1,140 @@
+use crate::utility::{constants::GPU_ENV_VAR, tools};
+
+use ash::vk;
+use std::fmt;
+
+/// Chooses the GPU by `--gpu` or the `RUST_GAME_GPU` environment variable instead of by
+/// score. Plain numbers are indices, `vendor:device` are hexadecimal PCI IDs like
+/// `10de:1c82`, and anything else is matched against the device name.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DeviceSelector {
+    /// Position in the order of `vkEnumeratePhysicalDevices`.
+    Index(usize),
+    /// Case-insensitive part of the device name.
+    Name(String),
+    Id {
+        vendor_id: u32,
+        device_id: u32,
+    },
+}
+
+impl DeviceSelector {
+    pub fn parse(selector: &str) -> DeviceSelector {
+        let selector = selector.trim();
+        if let Ok(index) = selector.parse() {
+            return DeviceSelector::Index(index);
+        }
+
+        let parse_hex = |id: &str| u32::from_str_radix(id.trim_start_matches("0x"), 16).ok();
+        let mut ids = selector.splitn(2, ':');
+        match (
+            ids.next().and_then(parse_hex),
+            ids.next().and_then(parse_hex),
+        ) {
+            (Some(vendor_id), Some(device_id)) => DeviceSelector::Id {
+                vendor_id,
+                device_id,
+            },
+            _ => DeviceSelector::Name(selector.to_owned()),
+        }
+    }
+
+    /// The selector in `RUST_GAME_GPU`, if it is set and not empty.
+    pub fn from_env() -> Option<DeviceSelector> {
+        std::env::var(GPU_ENV_VAR)
+            .ok()
+            .filter(|selector| !selector.trim().is_empty())
+            .map(|selector| DeviceSelector::parse(&selector))
+    }
+
+    /// Whether the device at `index` with `properties` is the one selected.
+    pub fn matches(&self, index: usize, properties: &vk::PhysicalDeviceProperties) -> bool {
+        match self {
+            DeviceSelector::Index(selected_index) => index == *selected_index,
+            DeviceSelector::Name(name) => tools::vk_to_string(&properties.device_name)
+                .to_lowercase()
+                .contains(&name.to_lowercase()),
+            DeviceSelector::Id {
+                vendor_id,
+                device_id,
+            } => properties.vendor_id == *vendor_id && properties.device_id == *device_id,
+        }
+    }
+}
+
+impl fmt::Display for DeviceSelector {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DeviceSelector::Index(index) => write!(f, "index {}", index),
+            DeviceSelector::Name(name) => write!(f, "name '{}'", name),
+            DeviceSelector::Id {
+                vendor_id,
+                device_id,
+            } => write!(f, "ID {:04x}:{:04x}", vendor_id, device_id),
+        }
+    }
+}
+
+/// How well a suitable device fits. Scores compare field by field, so the device type
+/// outweighs memory size, which outweighs optional features.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct DeviceScore {
+    /// Discrete > integrated > virtual > CPU > other.
+    pub type_rank: u32,
+    /// Total size of the device local heaps.
+    pub device_local_bytes: vk::DeviceSize,
+    /// Optional features the renderer makes use of.
+    pub optional_feature_count: u32,
+}
+
+impl DeviceScore {
+    pub fn new(
+        properties: &vk::PhysicalDeviceProperties,
+        memory_properties: &vk::PhysicalDeviceMemoryProperties,
+        features: &vk::PhysicalDeviceFeatures,
+    ) -> DeviceScore {
+        let type_rank = match properties.device_type {
+            vk::PhysicalDeviceType::DISCRETE_GPU => 4,
+            vk::PhysicalDeviceType::INTEGRATED_GPU => 3,
+            vk::PhysicalDeviceType::VIRTUAL_GPU => 2,
+            vk::PhysicalDeviceType::CPU => 1,
+            _ => 0,
+        };
+        let device_local_bytes = memory_properties.memory_heaps
+            [..memory_properties.memory_heap_count as usize]
+            .iter()
+            .filter(|heap| heap.flags.contains(vk::MemoryHeapFlags::DEVICE_LOCAL))
+            .map(|heap| heap.size)
+            .sum();
+        // The optional features `share::create_logical_device` enables when supported.
+        let optional_feature_count = [features.sample_rate_shading, features.fill_mode_non_solid]
+            .iter()
+            .filter(|&&feature| feature == vk::TRUE)
+            .count() as u32;
+
+        DeviceScore {
+            type_rank,
+            device_local_bytes,
+            optional_feature_count,
+        }
+    }
+}
+
+impl fmt::Display for DeviceScore {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let device_type = match self.type_rank {
+            4 => "discrete",
+            3 => "integrated",
+            2 => "virtual",
+            1 => "CPU",
+            _ => "other",
+        };
+        write!(
+            f,
+            "{}, {:.1} GiB device local memory, {} optional features",
+            device_type,
+            self.device_local_bytes as f64 / (1024.0 * 1024.0 * 1024.0),
+            self.optional_feature_count
+        )
+    }
+}
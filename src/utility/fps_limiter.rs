@@ -14,6 +14,12 @@ pub struct FPSLimiter {
     lag: Duration,
 }
 
+impl Default for FPSLimiter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl FPSLimiter {
     pub fn new() -> FPSLimiter {
         const DEFAULT_PREFER_FPS: f32 = 60.0;
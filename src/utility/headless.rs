@@ -0,0 +1,495 @@
+use crate::error::{Error, Result, VkResultExt};
+use crate::utility::{
+    allocator::{Allocation, MemoryAllocator},
+    constants::HEADLESS_DEVICE_EXTENSIONS,
+    debug::{self, DebugMarker, ValidationInfo},
+    debug_messenger::DebugMessenger,
+    device_selection::DeviceSelector,
+    platforms, share,
+    structures::{
+        DrawCommand, QueueFamilyIndices, UniformBufferObject, VertexV2, RECT_INDICES_DATA,
+        RECT_VERTICES_DATA,
+    },
+};
+
+use ash::version::{DeviceV1_0, EntryV1_0, InstanceV1_0};
+use ash::vk;
+use std::path::Path;
+use std::ptr;
+use std::sync::Arc;
+
+// Same channel order as the PNG we write, and sRGB like the swapchain format we prefer.
+const COLOR_FORMAT: vk::Format = vk::Format::R8G8B8A8_SRGB;
+
+/// Renders into an offscreen color+depth image instead of a swapchain, so that frames can
+/// be produced without a window (e.g. on lavapipe) and read back to the host.
+pub struct HeadlessRenderer {
+    _entry: ash::Entry,
+    instance: ash::Instance,
+    debug_utils_loader: ash::extensions::ext::DebugUtils,
+    debug_messenger: vk::DebugUtilsMessengerEXT,
+    is_validation_enabled: bool,
+    // Referenced by the debug messenger, so dropped after the instance is destroyed.
+    messenger: Arc<DebugMessenger>,
+
+    allocator: MemoryAllocator,
+    device: ash::Device,
+    graphics_queue: vk::Queue,
+
+    extent: vk::Extent2D,
+    color_image: vk::Image,
+    color_image_view: vk::ImageView,
+    color_image_memory: Allocation,
+    depth_image: vk::Image,
+    depth_image_view: vk::ImageView,
+    depth_image_memory: Allocation,
+    framebuffers: Vec<vk::Framebuffer>,
+
+    render_pass: vk::RenderPass,
+    ubo_layout: vk::DescriptorSetLayout,
+    pipeline_layout: vk::PipelineLayout,
+    graphics_pipeline: vk::Pipeline,
+
+    vertex_buffer: vk::Buffer,
+    vertex_buffer_memory: Allocation,
+    index_buffer: vk::Buffer,
+    index_buffer_memory: Allocation,
+
+    texture_image: vk::Image,
+    texture_image_memory: Allocation,
+    texture_image_view: vk::ImageView,
+    texture_sampler: vk::Sampler,
+
+    uniform_buffers: Vec<vk::Buffer>,
+    uniform_buffers_memory: Vec<Allocation>,
+
+    descriptor_pool: vk::DescriptorPool,
+    descriptor_sets: Vec<vk::DescriptorSet>,
+
+    command_pool: vk::CommandPool,
+    command_buffers: Vec<vk::CommandBuffer>,
+}
+
+impl HeadlessRenderer {
+    /// Checks whether a Vulkan loader and at least one physical device are present, so
+    /// callers such as tests can skip instead of panicking on machines without Vulkan.
+    pub fn is_available() -> bool {
+        let entry = match ash::Entry::new() {
+            Ok(entry) => entry,
+            Err(_) => return false,
+        };
+
+        let create_info = vk::InstanceCreateInfo::default();
+        let instance = match unsafe { entry.create_instance(&create_info, None) } {
+            Ok(instance) => instance,
+            Err(_) => return false,
+        };
+
+        let has_physical_device = unsafe { instance.enumerate_physical_devices() }
+            .map(|physical_devices| !physical_devices.is_empty())
+            .unwrap_or(false);
+
+        unsafe { instance.destroy_instance(None) };
+
+        has_physical_device
+    }
+
+    pub fn new(
+        width: u32,
+        height: u32,
+        validation: &ValidationInfo,
+        gpu_selector: Option<DeviceSelector>,
+        vert_shader_path: &Path,
+        frag_shader_path: &Path,
+    ) -> Result<HeadlessRenderer> {
+        let entry = ash::Entry::new()?;
+        let messenger = Arc::new(DebugMessenger::from_validation(validation));
+        let (instance, validation) = share::create_instance_with_extensions(
+            &entry,
+            "Headless",
+            validation,
+            &messenger,
+            &platforms::headless_extension_names(),
+        )?;
+        let (debug_utils_loader, debug_messenger) =
+            match debug::setup_debug_utils(&validation, &messenger, &entry, &instance) {
+                Ok(debug_utils) => debug_utils,
+                Err(err) => {
+                    unsafe { instance.destroy_instance(None) };
+                    return Err(err);
+                }
+            };
+        let device = share::pick_physical_device(
+            &instance,
+            None,
+            &HEADLESS_DEVICE_EXTENSIONS,
+            gpu_selector.as_ref(),
+        )
+        .and_then(|physical_device| {
+            share::create_logical_device(
+                &instance,
+                physical_device,
+                &validation,
+                &HEADLESS_DEVICE_EXTENSIONS,
+                None,
+            )
+            .map(|(device, queue_family)| (physical_device, device, queue_family))
+        });
+        let (physical_device, device, queue_family) = match device {
+            Ok(device) => device,
+            Err(err) => {
+                unsafe {
+                    if validation.is_enable {
+                        debug_utils_loader.destroy_debug_utils_messenger(debug_messenger, None);
+                    }
+                    instance.destroy_instance(None);
+                }
+                return Err(err);
+            }
+        };
+        let allocator = MemoryAllocator::new(&instance, physical_device);
+        let graphics_queue =
+            unsafe { device.get_device_queue(queue_family.graphics_family.unwrap(), 0) };
+
+        let mut renderer = HeadlessRenderer {
+            _entry: entry,
+            instance,
+            debug_utils_loader,
+            debug_messenger,
+            is_validation_enabled: validation.is_enable,
+            messenger,
+
+            allocator,
+            device,
+            graphics_queue,
+
+            extent: vk::Extent2D { width, height },
+            color_image: vk::Image::null(),
+            color_image_view: vk::ImageView::null(),
+            color_image_memory: Allocation::null(),
+            depth_image: vk::Image::null(),
+            depth_image_view: vk::ImageView::null(),
+            depth_image_memory: Allocation::null(),
+            framebuffers: vec![],
+
+            render_pass: vk::RenderPass::null(),
+            ubo_layout: vk::DescriptorSetLayout::null(),
+            pipeline_layout: vk::PipelineLayout::null(),
+            graphics_pipeline: vk::Pipeline::null(),
+
+            vertex_buffer: vk::Buffer::null(),
+            vertex_buffer_memory: Allocation::null(),
+            index_buffer: vk::Buffer::null(),
+            index_buffer_memory: Allocation::null(),
+
+            texture_image: vk::Image::null(),
+            texture_image_memory: Allocation::null(),
+            texture_image_view: vk::ImageView::null(),
+            texture_sampler: vk::Sampler::null(),
+
+            uniform_buffers: vec![],
+            uniform_buffers_memory: vec![],
+
+            descriptor_pool: vk::DescriptorPool::null(),
+            descriptor_sets: vec![],
+
+            command_pool: vk::CommandPool::null(),
+            command_buffers: vec![],
+        };
+
+        // Handles stay null until they are created, so `Drop` releases exactly what was
+        // created before a failure.
+        renderer.create_resources(
+            physical_device,
+            &queue_family,
+            vert_shader_path,
+            frag_shader_path,
+        )?;
+
+        Ok(renderer)
+    }
+
+    fn create_resources(
+        &mut self,
+        physical_device: vk::PhysicalDevice,
+        queue_family: &QueueFamilyIndices,
+        vert_shader_path: &Path,
+        frag_shader_path: &Path,
+    ) -> Result<()> {
+        let device = &self.device;
+        let allocator = &mut self.allocator;
+
+        let (color_image, color_image_view, color_image_memory) =
+            share::pipeline::create_offscreen_color_resources(
+                device,
+                COLOR_FORMAT,
+                self.extent,
+                allocator,
+            )?;
+        self.color_image = color_image;
+        self.color_image_view = color_image_view;
+        self.color_image_memory = color_image_memory;
+        self.render_pass = share::pipeline::create_render_pass(
+            &self.instance,
+            device,
+            physical_device,
+            COLOR_FORMAT,
+            vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+            vk::SampleCountFlags::TYPE_1,
+        )?;
+        self.ubo_layout = share::pipeline::create_descriptor_set_layout(device)?;
+        let (graphics_pipeline, pipeline_layout) =
+            share::pipeline::create_graphics_pipeline::<VertexV2>(
+                device,
+                self.render_pass,
+                // Golden runs start from scratch, so they do not touch the on-disk cache.
+                vk::PipelineCache::null(),
+                self.ubo_layout,
+                vk::FrontFace::CLOCKWISE,
+                vert_shader_path,
+                frag_shader_path,
+            )?;
+        self.graphics_pipeline = graphics_pipeline;
+        self.pipeline_layout = pipeline_layout;
+        self.command_pool = share::pipeline::create_command_pool(device, queue_family)?;
+        let (depth_image, depth_image_view, depth_image_memory) =
+            share::pipeline::create_depth_resources(
+                &self.instance,
+                device,
+                physical_device,
+                self.command_pool,
+                self.graphics_queue,
+                self.extent,
+                vk::SampleCountFlags::TYPE_1,
+                allocator,
+            )?;
+        self.depth_image = depth_image;
+        self.depth_image_view = depth_image_view;
+        self.depth_image_memory = depth_image_memory;
+        self.framebuffers = share::pipeline::create_framebuffers(
+            device,
+            self.render_pass,
+            &[self.color_image_view],
+            self.depth_image_view,
+            None,
+            self.extent,
+        )?;
+        let (vertex_buffer, vertex_buffer_memory) = share::pipeline::create_vertex_buffer(
+            device,
+            allocator,
+            self.command_pool,
+            self.graphics_queue,
+            &RECT_VERTICES_DATA,
+        )?;
+        self.vertex_buffer = vertex_buffer;
+        self.vertex_buffer_memory = vertex_buffer_memory;
+        let (index_buffer, index_buffer_memory) = share::pipeline::create_index_buffer(
+            device,
+            allocator,
+            self.command_pool,
+            self.graphics_queue,
+            &RECT_INDICES_DATA,
+        )?;
+        self.index_buffer = index_buffer;
+        self.index_buffer_memory = index_buffer_memory;
+        let (uniform_buffers, uniform_buffers_memory) = share::pipeline::create_uniform_buffers(
+            device,
+            allocator,
+            std::mem::size_of::<UniformBufferObject>(),
+            1,
+        )?;
+        self.uniform_buffers = uniform_buffers;
+        self.uniform_buffers_memory = uniform_buffers_memory;
+        // A white texture keeps the output equal to the vertex colors, as golden images expect.
+        let (texture_image, texture_image_memory, mip_levels) =
+            share::pipeline::create_white_texture_image(
+                device,
+                self.command_pool,
+                self.graphics_queue,
+                allocator,
+            )?;
+        self.texture_image = texture_image;
+        self.texture_image_memory = texture_image_memory;
+        self.texture_image_view =
+            share::pipeline::create_texture_image_view(device, self.texture_image, mip_levels)?;
+        self.texture_sampler = share::pipeline::create_texture_sampler(device, mip_levels)?;
+        self.descriptor_pool = share::pipeline::create_descriptor_pool(device, 1)?;
+        self.descriptor_sets = share::pipeline::create_descriptor_sets(
+            device,
+            self.descriptor_pool,
+            self.ubo_layout,
+            &self.uniform_buffers,
+            (self.texture_image_view, self.texture_sampler),
+            1,
+        )?;
+        let debug_marker = DebugMarker::new(&self.debug_utils_loader, device);
+        self.command_buffers = share::pipeline::create_command_buffers(
+            device,
+            &debug_marker,
+            self.command_pool,
+            self.graphics_pipeline,
+            &self.framebuffers,
+            self.render_pass,
+            self.extent,
+            self.pipeline_layout,
+            &self.descriptor_sets,
+            DrawCommand::Indexed {
+                vertex_buffer: self.vertex_buffer,
+                index_buffer: self.index_buffer,
+                index_count: RECT_INDICES_DATA.len() as u32,
+            },
+        )?;
+
+        self.set_debug_names(&debug_marker)
+    }
+
+    /// Renders a single frame with the given uniforms and waits for it to finish.
+    pub fn render_frame(&mut self, ubo: &UniformBufferObject) -> Result<()> {
+        let ubos = [*ubo];
+
+        unsafe {
+            let data_ptr = self.uniform_buffers_memory[0].mapped_ptr()? as *mut UniformBufferObject;
+
+            data_ptr.copy_from_nonoverlapping(ubos.as_ptr(), ubos.len());
+        }
+
+        let submit_infos = [vk::SubmitInfo {
+            s_type: vk::StructureType::SUBMIT_INFO,
+            p_next: ptr::null(),
+            wait_semaphore_count: 0,
+            p_wait_semaphores: ptr::null(),
+            p_wait_dst_stage_mask: ptr::null(),
+            command_buffer_count: 1,
+            p_command_buffers: &self.command_buffers[0],
+            signal_semaphore_count: 0,
+            p_signal_semaphores: ptr::null(),
+        }];
+
+        unsafe {
+            self.device
+                .queue_submit(self.graphics_queue, &submit_infos, vk::Fence::null())
+                .vk_context("Failed to execute queue submit")?;
+            self.device
+                .queue_wait_idle(self.graphics_queue)
+                .vk_context("Failed to wait Queue idle")?;
+        }
+
+        Ok(())
+    }
+
+    /// Reads back the last rendered frame.
+    pub fn capture(&mut self) -> Result<image::RgbaImage> {
+        let pixels = share::pipeline::read_color_image(
+            &self.device,
+            self.command_pool,
+            self.graphics_queue,
+            self.color_image,
+            self.extent,
+            &mut self.allocator,
+        )?;
+
+        let byte_count = pixels.len();
+        image::RgbaImage::from_raw(self.extent.width, self.extent.height, pixels).ok_or(
+            Error::CaptureSizeMismatch {
+                extent: self.extent,
+                byte_count,
+            },
+        )
+    }
+
+    fn set_debug_names(&self, debug_marker: &DebugMarker) -> Result<()> {
+        debug_marker.set_name(self.color_image, "color image")?;
+        debug_marker.set_name(self.color_image_view, "color image view")?;
+        debug_marker.set_name(self.depth_image, "depth image")?;
+        debug_marker.set_name(self.depth_image_view, "depth image view")?;
+        debug_marker.set_names(&self.framebuffers, "framebuffer")?;
+        debug_marker.set_name(self.render_pass, "render pass")?;
+        debug_marker.set_name(self.pipeline_layout, "pipeline layout")?;
+        debug_marker.set_name(self.graphics_pipeline, "graphics pipeline")?;
+        debug_marker.set_name(self.vertex_buffer, "vertex buffer")?;
+        debug_marker.set_name(self.index_buffer, "index buffer")?;
+        debug_marker.set_names(&self.uniform_buffers, "uniform buffer")?;
+        debug_marker.set_name(self.texture_image, "texture image")?;
+        debug_marker.set_names(&self.command_buffers, "command buffer")
+    }
+
+    /// Counts and, with the memory sink, messages of the validation layers so far.
+    pub fn messenger(&self) -> &DebugMessenger {
+        &self.messenger
+    }
+
+    pub fn save_png(&mut self, path: &Path) -> Result<()> {
+        self.capture()?
+            .save_with_format(path, image::ImageFormat::Png)
+            .map_err(|source| Error::Image {
+                path: path.to_path_buf(),
+                source,
+            })
+    }
+}
+
+impl Drop for HeadlessRenderer {
+    fn drop(&mut self) {
+        unsafe {
+            self.device
+                .device_wait_idle()
+                .expect("Failed to wait device idle!");
+
+            if !self.command_buffers.is_empty() {
+                self.device
+                    .free_command_buffers(self.command_pool, &self.command_buffers);
+            }
+
+            for i in 0..self.uniform_buffers.len() {
+                self.device.destroy_buffer(self.uniform_buffers[i], None);
+                self.allocator
+                    .free(&self.device, self.uniform_buffers_memory[i]);
+            }
+            self.device
+                .destroy_descriptor_pool(self.descriptor_pool, None);
+
+            for &framebuffer in self.framebuffers.iter() {
+                self.device.destroy_framebuffer(framebuffer, None);
+            }
+            self.device.destroy_pipeline(self.graphics_pipeline, None);
+            self.device
+                .destroy_pipeline_layout(self.pipeline_layout, None);
+            self.device.destroy_render_pass(self.render_pass, None);
+
+            self.device.destroy_image_view(self.depth_image_view, None);
+            self.device.destroy_image(self.depth_image, None);
+            self.allocator.free(&self.device, self.depth_image_memory);
+
+            self.device.destroy_image_view(self.color_image_view, None);
+            self.device.destroy_image(self.color_image, None);
+            self.allocator.free(&self.device, self.color_image_memory);
+
+            self.device.destroy_sampler(self.texture_sampler, None);
+            self.device
+                .destroy_image_view(self.texture_image_view, None);
+            self.device.destroy_image(self.texture_image, None);
+            self.allocator.free(&self.device, self.texture_image_memory);
+
+            self.device.destroy_buffer(self.index_buffer, None);
+            self.allocator.free(&self.device, self.index_buffer_memory);
+
+            self.device.destroy_buffer(self.vertex_buffer, None);
+            self.allocator.free(&self.device, self.vertex_buffer_memory);
+
+            self.device
+                .destroy_descriptor_set_layout(self.ubo_layout, None);
+
+            self.device.destroy_command_pool(self.command_pool, None);
+
+            self.allocator.destroy(&self.device);
+            self.device.destroy_device(None);
+
+            if self.is_validation_enabled {
+                self.debug_utils_loader
+                    .destroy_debug_utils_messenger(self.debug_messenger, None);
+            }
+
+            self.instance.destroy_instance(None);
+        }
+    }
+}
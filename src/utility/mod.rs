@@ -1,11 +1,25 @@
+pub mod allocator;
+pub mod camera;
+pub mod capability_report;
 pub mod constants;
 pub mod debug;
+pub mod debug_messenger;
+pub mod device_selection;
 pub mod fps_limiter;
+pub mod headless;
+pub mod multipass;
+pub mod pipeline_cache;
 pub mod platforms;
 pub mod program_proc;
+#[cfg(feature = "runtime-shaders")]
+pub mod shader_compiler;
+pub mod shader_watcher;
+pub mod shadertoy;
+pub mod specialization;
 pub mod structures;
 pub mod tools;
 pub mod traits;
+pub mod vertex;
 pub mod window;
 
 pub mod share;
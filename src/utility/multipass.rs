@@ -0,0 +1,558 @@
+use crate::error::Result;
+use crate::utility::{
+    allocator::{Allocation, MemoryAllocator},
+    constants::FULLSCREEN_BUFFER_VERT_SHADER_PATH,
+    debug::DebugMarker,
+    shadertoy::{PassesDescription, CHANNEL_COUNT},
+    share,
+};
+
+use ash::version::DeviceV1_0;
+use ash::vk;
+use std::path::Path;
+use std::ptr;
+
+// Floating point, so buffers can carry simulation state from frame to frame like on Shadertoy.
+const BUFFER_FORMAT: vk::Format = vk::Format::R16G16B16A16_SFLOAT;
+
+/// The two ping-pong images of one Shadertoy buffer and the pipeline rendering into them.
+struct BufferTarget {
+    images: [vk::Image; 2],
+    image_views: [vk::ImageView; 2],
+    image_memories: [Allocation; 2],
+    framebuffers: [vk::Framebuffer; 2],
+    pipeline: vk::Pipeline,
+    pipeline_layout: vk::PipelineLayout,
+}
+
+/// Offscreen Shadertoy buffers (A-D) rendered before the image pass, and the descriptor sets
+/// binding them as `iChannel0..3` of every pass including the image pass.
+///
+/// On frame `n` each buffer is rendered into image `n % 2` while the other image still holds
+/// the previous frame, so a buffer can sample its own last output.
+pub struct Multipass {
+    description: PassesDescription,
+    extent: vk::Extent2D,
+    descriptor_set_layout: vk::DescriptorSetLayout,
+    pipeline_cache: vk::PipelineCache,
+
+    render_pass: vk::RenderPass,
+    sampler: vk::Sampler,
+    // Bound to channels that have no input.
+    placeholder_image: vk::Image,
+    placeholder_image_view: vk::ImageView,
+    placeholder_image_memory: Allocation,
+    buffers: Vec<BufferTarget>,
+
+    descriptor_pool: vk::DescriptorPool,
+    // Indexed by [frame parity][pass][swapchain image]; the image pass comes last.
+    descriptor_sets: [Vec<Vec<vk::DescriptorSet>>; 2],
+}
+
+impl Multipass {
+    /// `descriptor_set_layout` comes from `create_shadertoy_descriptor_set_layout` and stays
+    /// owned by the caller, which also uses it for the image pass pipeline. So does
+    /// `pipeline_cache`.
+    pub fn new(
+        device: &ash::Device,
+        allocator: &mut MemoryAllocator,
+        command_pool: vk::CommandPool,
+        submit_queue: vk::Queue,
+        description: &PassesDescription,
+        descriptor_set_layout: vk::DescriptorSetLayout,
+        pipeline_cache: vk::PipelineCache,
+        extent: vk::Extent2D,
+        uniform_buffers: &[vk::Buffer],
+    ) -> Result<Multipass> {
+        let mut multipass = Multipass {
+            description: description.clone(),
+            extent,
+            descriptor_set_layout,
+            pipeline_cache,
+
+            render_pass: vk::RenderPass::null(),
+            sampler: vk::Sampler::null(),
+            placeholder_image: vk::Image::null(),
+            placeholder_image_view: vk::ImageView::null(),
+            placeholder_image_memory: Allocation::null(),
+            buffers: vec![],
+
+            descriptor_pool: vk::DescriptorPool::null(),
+            descriptor_sets: [vec![], vec![]],
+        };
+
+        // Handles stay null until they are created, so `destroy` releases exactly what was
+        // created before a failure.
+        if let Err(err) = multipass.create_resources(
+            device,
+            allocator,
+            command_pool,
+            submit_queue,
+            uniform_buffers,
+        ) {
+            multipass.destroy(device, allocator);
+            return Err(err);
+        }
+
+        Ok(multipass)
+    }
+
+    /// Shaders of every pass, plus the vertex shader of the buffer passes.
+    pub fn shader_paths(&self) -> Vec<std::path::PathBuf> {
+        let mut shader_paths = self.description.shader_paths();
+        if !self.buffers.is_empty() {
+            shader_paths.push(FULLSCREEN_BUFFER_VERT_SHADER_PATH.into());
+        }
+
+        shader_paths
+    }
+
+    /// Builds a pipeline for every buffer pass from the current shader files. Nothing is
+    /// leaked if one of them fails to compile.
+    pub fn create_pipelines(
+        &self,
+        device: &ash::Device,
+    ) -> Result<Vec<(vk::Pipeline, vk::PipelineLayout)>> {
+        let mut pipelines = vec![];
+
+        for pass in self.description.buffers.iter() {
+            // The fullscreen triangle is generated in the vertex shader, so it has no vertex input.
+            let pipeline = share::pipeline::create_graphics_pipeline::<()>(
+                device,
+                self.render_pass,
+                self.pipeline_cache,
+                self.descriptor_set_layout,
+                vk::FrontFace::CLOCKWISE,
+                Path::new(FULLSCREEN_BUFFER_VERT_SHADER_PATH),
+                &pass.shader_path,
+            );
+
+            match pipeline {
+                Ok(pipeline) => pipelines.push(pipeline),
+                Err(err) => {
+                    destroy_pipelines(device, &pipelines);
+                    return Err(err);
+                }
+            }
+        }
+
+        Ok(pipelines)
+    }
+
+    /// Swaps in pipelines from `create_pipelines`. The device must be idle.
+    pub fn replace_pipelines(
+        &mut self,
+        device: &ash::Device,
+        pipelines: Vec<(vk::Pipeline, vk::PipelineLayout)>,
+    ) {
+        for (buffer, (pipeline, pipeline_layout)) in self.buffers.iter_mut().zip(pipelines) {
+            destroy_pipelines(device, &[(buffer.pipeline, buffer.pipeline_layout)]);
+            buffer.pipeline = pipeline;
+            buffer.pipeline_layout = pipeline_layout;
+        }
+    }
+
+    /// Records the buffer passes of frame `frame` in order, before the image pass that draws
+    /// with `image_descriptor_set`.
+    pub fn cmd_buffer_passes(
+        &self,
+        device: &ash::Device,
+        debug_marker: &DebugMarker,
+        command_buffer: vk::CommandBuffer,
+        frame: usize,
+        image_index: usize,
+    ) {
+        let parity = frame % 2;
+        for (pass, buffer) in self.buffers.iter().enumerate() {
+            let label = format!("{:?} pass", self.description.buffers[pass].name);
+            debug_marker.cmd_begin_label(command_buffer, &label);
+            self.record_pass(
+                device,
+                command_buffer,
+                buffer.framebuffers[parity],
+                buffer.pipeline,
+                buffer.pipeline_layout,
+                self.descriptor_sets[parity][pass][image_index],
+            );
+            debug_marker.cmd_end_label(command_buffer);
+        }
+    }
+
+    /// Descriptor set of the image pass of frame `frame`, binding the buffers rendered by
+    /// `cmd_buffer_passes`.
+    pub fn image_descriptor_set(&self, frame: usize, image_index: usize) -> vk::DescriptorSet {
+        let image_pass = self.buffers.len();
+        self.descriptor_sets[frame % 2][image_pass][image_index]
+    }
+
+    /// Names the images, framebuffers and pipelines of every buffer after its pass.
+    pub fn set_debug_names(&self, debug_marker: &DebugMarker) -> Result<()> {
+        for (pass, buffer) in self.buffers.iter().enumerate() {
+            let name = format!("{:?}", self.description.buffers[pass].name);
+            debug_marker.set_names(&buffer.images, &format!("{} image", name))?;
+            debug_marker.set_names(&buffer.image_views, &format!("{} image view", name))?;
+            debug_marker.set_names(&buffer.framebuffers, &format!("{} framebuffer", name))?;
+            debug_marker.set_name(buffer.pipeline, &format!("{} pipeline", name))?;
+            debug_marker.set_name(buffer.pipeline_layout, &format!("{} pipeline layout", name))?;
+        }
+        debug_marker.set_name(self.render_pass, "buffer render pass")
+    }
+
+    /// Recreates the buffer images for `extent`, cleared like on the first frame. The render
+    /// pass and pipelines do not depend on the size and are kept. The device must be idle.
+    pub fn resize(
+        &mut self,
+        device: &ash::Device,
+        allocator: &mut MemoryAllocator,
+        command_pool: vk::CommandPool,
+        submit_queue: vk::Queue,
+        extent: vk::Extent2D,
+        uniform_buffers: &[vk::Buffer],
+    ) -> Result<()> {
+        self.destroy_targets(device, allocator);
+        self.extent = extent;
+        self.create_targets(
+            device,
+            allocator,
+            command_pool,
+            submit_queue,
+            uniform_buffers,
+        )
+    }
+
+    pub fn destroy(&mut self, device: &ash::Device, allocator: &mut MemoryAllocator) {
+        self.destroy_targets(device, allocator);
+        for buffer in self.buffers.iter() {
+            destroy_pipelines(device, &[(buffer.pipeline, buffer.pipeline_layout)]);
+        }
+
+        unsafe {
+            device.destroy_image_view(self.placeholder_image_view, None);
+            device.destroy_image(self.placeholder_image, None);
+            allocator.free(device, self.placeholder_image_memory);
+            device.destroy_sampler(self.sampler, None);
+            device.destroy_render_pass(self.render_pass, None);
+        }
+    }
+
+    fn create_resources(
+        &mut self,
+        device: &ash::Device,
+        allocator: &mut MemoryAllocator,
+        command_pool: vk::CommandPool,
+        submit_queue: vk::Queue,
+        uniform_buffers: &[vk::Buffer],
+    ) -> Result<()> {
+        self.render_pass = share::pipeline::create_offscreen_render_pass(device, BUFFER_FORMAT)?;
+        self.sampler = share::pipeline::create_channel_sampler(device)?;
+        let (placeholder_image, placeholder_image_view, placeholder_image_memory) =
+            create_target_image(
+                device,
+                vk::Extent2D {
+                    width: 1,
+                    height: 1,
+                },
+                allocator,
+            )?;
+        self.placeholder_image = placeholder_image;
+        self.placeholder_image_view = placeholder_image_view;
+        self.placeholder_image_memory = placeholder_image_memory;
+
+        for (pipeline, pipeline_layout) in self.create_pipelines(device)? {
+            self.buffers.push(BufferTarget {
+                images: [vk::Image::null(); 2],
+                image_views: [vk::ImageView::null(); 2],
+                image_memories: [Allocation::null(); 2],
+                framebuffers: [vk::Framebuffer::null(); 2],
+                pipeline,
+                pipeline_layout,
+            });
+        }
+
+        self.create_targets(
+            device,
+            allocator,
+            command_pool,
+            submit_queue,
+            uniform_buffers,
+        )
+    }
+
+    /// Creates the images and framebuffers of every buffer plus the descriptor sets binding
+    /// them, all of which depend on `extent`.
+    fn create_targets(
+        &mut self,
+        device: &ash::Device,
+        allocator: &mut MemoryAllocator,
+        command_pool: vk::CommandPool,
+        submit_queue: vk::Queue,
+        uniform_buffers: &[vk::Buffer],
+    ) -> Result<()> {
+        for buffer in self.buffers.iter_mut() {
+            for i in 0..2 {
+                let (image, image_view, image_memory) =
+                    create_target_image(device, self.extent, allocator)?;
+                buffer.images[i] = image;
+                buffer.image_views[i] = image_view;
+                buffer.image_memories[i] = image_memory;
+                buffer.framebuffers[i] = share::pipeline::create_offscreen_framebuffer(
+                    device,
+                    self.render_pass,
+                    image_view,
+                    self.extent,
+                )?;
+            }
+        }
+
+        self.clear_images(device, command_pool, submit_queue)?;
+        self.create_descriptor_sets(device, uniform_buffers)
+    }
+
+    /// Destroys what `create_targets` created and resets it to null handles.
+    fn destroy_targets(&mut self, device: &ash::Device, allocator: &mut MemoryAllocator) {
+        unsafe {
+            device.destroy_descriptor_pool(self.descriptor_pool, None);
+            self.descriptor_pool = vk::DescriptorPool::null();
+            self.descriptor_sets = [vec![], vec![]];
+
+            for buffer in self.buffers.iter_mut() {
+                for i in 0..2 {
+                    device.destroy_framebuffer(buffer.framebuffers[i], None);
+                    device.destroy_image_view(buffer.image_views[i], None);
+                    device.destroy_image(buffer.images[i], None);
+                    allocator.free(device, buffer.image_memories[i]);
+                }
+                buffer.images = [vk::Image::null(); 2];
+                buffer.image_views = [vk::ImageView::null(); 2];
+                buffer.image_memories = [Allocation::null(); 2];
+                buffer.framebuffers = [vk::Framebuffer::null(); 2];
+            }
+        }
+    }
+
+    fn record_pass(
+        &self,
+        device: &ash::Device,
+        command_buffer: vk::CommandBuffer,
+        framebuffer: vk::Framebuffer,
+        pipeline: vk::Pipeline,
+        pipeline_layout: vk::PipelineLayout,
+        descriptor_set: vk::DescriptorSet,
+    ) {
+        let render_pass_begin_info = vk::RenderPassBeginInfo {
+            s_type: vk::StructureType::RENDER_PASS_BEGIN_INFO,
+            p_next: ptr::null(),
+            render_pass: self.render_pass,
+            framebuffer,
+            render_area: vk::Rect2D {
+                offset: vk::Offset2D { x: 0, y: 0 },
+                extent: self.extent,
+            },
+            // The fullscreen triangle covers every pixel, so the target is not cleared.
+            clear_value_count: 0,
+            p_clear_values: ptr::null(),
+        };
+
+        unsafe {
+            device.cmd_begin_render_pass(
+                command_buffer,
+                &render_pass_begin_info,
+                vk::SubpassContents::INLINE,
+            );
+            device.cmd_bind_pipeline(command_buffer, vk::PipelineBindPoint::GRAPHICS, pipeline);
+            share::pipeline::cmd_set_viewport_and_scissor(device, command_buffer, self.extent);
+            device.cmd_bind_descriptor_sets(
+                command_buffer,
+                vk::PipelineBindPoint::GRAPHICS,
+                pipeline_layout,
+                0,
+                &[descriptor_set],
+                &[],
+            );
+            device.cmd_draw(command_buffer, 3, 1, 0, 0);
+            device.cmd_end_render_pass(command_buffer);
+        }
+    }
+
+    /// Clears every target to transparent black and leaves it ready for sampling, so the first
+    /// frame reads zeros from the "previous" images like Shadertoy does.
+    fn clear_images(
+        &self,
+        device: &ash::Device,
+        command_pool: vk::CommandPool,
+        submit_queue: vk::Queue,
+    ) -> Result<()> {
+        let images: Vec<vk::Image> = self
+            .buffers
+            .iter()
+            .flat_map(|buffer| buffer.images.iter().copied())
+            .chain(std::iter::once(self.placeholder_image))
+            .collect();
+        let subresource_range = vk::ImageSubresourceRange {
+            aspect_mask: vk::ImageAspectFlags::COLOR,
+            base_mip_level: 0,
+            level_count: 1,
+            base_array_layer: 0,
+            layer_count: 1,
+        };
+        let barriers = |old_layout, new_layout, src_access_mask, dst_access_mask| {
+            images
+                .iter()
+                .map(|&image| vk::ImageMemoryBarrier {
+                    s_type: vk::StructureType::IMAGE_MEMORY_BARRIER,
+                    p_next: ptr::null(),
+                    src_access_mask,
+                    dst_access_mask,
+                    old_layout,
+                    new_layout,
+                    src_queue_family_index: vk::QUEUE_FAMILY_IGNORED,
+                    dst_queue_family_index: vk::QUEUE_FAMILY_IGNORED,
+                    image,
+                    subresource_range,
+                })
+                .collect::<Vec<_>>()
+        };
+
+        let command_buffer = share::begin_single_time_command(device, command_pool)?;
+
+        unsafe {
+            device.cmd_pipeline_barrier(
+                command_buffer,
+                vk::PipelineStageFlags::TOP_OF_PIPE,
+                vk::PipelineStageFlags::TRANSFER,
+                vk::DependencyFlags::empty(),
+                &[],
+                &[],
+                &barriers(
+                    vk::ImageLayout::UNDEFINED,
+                    vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                    vk::AccessFlags::empty(),
+                    vk::AccessFlags::TRANSFER_WRITE,
+                ),
+            );
+            for &image in images.iter() {
+                device.cmd_clear_color_image(
+                    command_buffer,
+                    image,
+                    vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                    &vk::ClearColorValue {
+                        float32: [0.0, 0.0, 0.0, 0.0],
+                    },
+                    &[subresource_range],
+                );
+            }
+            device.cmd_pipeline_barrier(
+                command_buffer,
+                vk::PipelineStageFlags::TRANSFER,
+                vk::PipelineStageFlags::FRAGMENT_SHADER,
+                vk::DependencyFlags::empty(),
+                &[],
+                &[],
+                &barriers(
+                    vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                    vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+                    vk::AccessFlags::TRANSFER_WRITE,
+                    vk::AccessFlags::SHADER_READ,
+                ),
+            );
+        }
+
+        share::end_single_time_command(device, command_pool, submit_queue, command_buffer)
+    }
+
+    fn create_descriptor_sets(
+        &mut self,
+        device: &ash::Device,
+        uniform_buffers: &[vk::Buffer],
+    ) -> Result<()> {
+        let pass_count = self.buffers.len() + 1;
+        self.descriptor_pool = share::pipeline::create_shadertoy_descriptor_pool(
+            device,
+            2 * pass_count * uniform_buffers.len(),
+        )?;
+
+        let passes: Vec<_> = self
+            .description
+            .buffers
+            .iter()
+            .chain(std::iter::once(&self.description.image))
+            .collect();
+        for parity in 0..2 {
+            for (pass_index, pass) in passes.iter().enumerate() {
+                let mut channel_image_views = [self.placeholder_image_view; CHANNEL_COUNT];
+                for (image_view, channel) in channel_image_views.iter_mut().zip(&pass.channels) {
+                    if let Some(buffer_index) = *channel {
+                        // Buffers rendered earlier this frame have written `parity`; the
+                        // pass itself and later ones still hold last frame's output.
+                        let image = if buffer_index < pass_index {
+                            parity
+                        } else {
+                            1 - parity
+                        };
+                        *image_view = self.buffers[buffer_index].image_views[image];
+                    }
+                }
+
+                let descriptor_sets = share::pipeline::create_shadertoy_descriptor_sets(
+                    device,
+                    self.descriptor_pool,
+                    self.descriptor_set_layout,
+                    uniform_buffers,
+                    self.sampler,
+                    &channel_image_views,
+                )?;
+                self.descriptor_sets[parity].push(descriptor_sets);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn create_target_image(
+    device: &ash::Device,
+    extent: vk::Extent2D,
+    allocator: &mut MemoryAllocator,
+) -> Result<(vk::Image, vk::ImageView, Allocation)> {
+    let (image, image_memory) = share::pipeline::create_image(
+        device,
+        extent.width,
+        extent.height,
+        1,
+        vk::SampleCountFlags::TYPE_1,
+        BUFFER_FORMAT,
+        vk::ImageTiling::OPTIMAL,
+        vk::ImageUsageFlags::COLOR_ATTACHMENT
+            | vk::ImageUsageFlags::SAMPLED
+            | vk::ImageUsageFlags::TRANSFER_DST,
+        vk::MemoryPropertyFlags::DEVICE_LOCAL,
+        allocator,
+    )?;
+    let image_view = match share::pipeline::create_image_view(
+        device,
+        image,
+        BUFFER_FORMAT,
+        vk::ImageAspectFlags::COLOR,
+        1,
+    ) {
+        Ok(image_view) => image_view,
+        Err(err) => {
+            unsafe {
+                device.destroy_image(image, None);
+            }
+            allocator.free(device, image_memory);
+            return Err(err);
+        }
+    };
+
+    Ok((image, image_view, image_memory))
+}
+
+fn destroy_pipelines(device: &ash::Device, pipelines: &[(vk::Pipeline, vk::PipelineLayout)]) {
+    for &(pipeline, pipeline_layout) in pipelines {
+        unsafe {
+            device.destroy_pipeline(pipeline, None);
+            device.destroy_pipeline_layout(pipeline_layout, None);
+        }
+    }
+}
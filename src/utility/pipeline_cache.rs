@@ -0,0 +1,112 @@
+use crate::error::{Error, Result, VkResultExt};
+use crate::utility::constants::PIPELINE_CACHE_FILE_NAME;
+
+use ash::version::{DeviceV1_0, InstanceV1_0};
+use ash::vk;
+use std::path::{Path, PathBuf};
+use std::ptr;
+
+// Size of the version one header: length, version, vendor ID, device ID and cache UUID.
+const HEADER_SIZE: usize = 16 + vk::UUID_SIZE;
+
+/// The cache file next to the executable, so the cache is shared by runs from any working
+/// directory. Falls back to the working directory if the executable path is unknown.
+pub fn default_cache_path() -> PathBuf {
+    std::env::current_exe()
+        .ok()
+        .and_then(|exe_path| exe_path.parent().map(Path::to_path_buf))
+        .unwrap_or_default()
+        .join(PIPELINE_CACHE_FILE_NAME)
+}
+
+/// Creates a pipeline cache primed with the contents of `cache_path`.
+///
+/// A missing file, or one written by another driver or GPU, gives an empty cache instead of
+/// an error, since the cache only makes pipeline creation faster.
+pub fn load_pipeline_cache(
+    instance: &ash::Instance,
+    device: &ash::Device,
+    physical_device: vk::PhysicalDevice,
+    cache_path: &Path,
+) -> Result<vk::PipelineCache> {
+    let properties = unsafe { instance.get_physical_device_properties(physical_device) };
+    let initial_data = match std::fs::read(cache_path) {
+        Ok(data) if is_compatible_cache_data(&data, &properties) => data,
+        Ok(_) => {
+            eprintln!(
+                "Ignoring pipeline cache {:?} written for another device",
+                cache_path
+            );
+            vec![]
+        }
+        Err(_) => vec![],
+    };
+
+    match create_pipeline_cache(device, &initial_data) {
+        // Drivers may still reject data they wrote themselves, e.g. after an update.
+        Err(_) if !initial_data.is_empty() => create_pipeline_cache(device, &[]),
+        result => result,
+    }
+}
+
+/// Writes the contents of `pipeline_cache` to `cache_path`, creating its directory.
+pub fn save_pipeline_cache(
+    device: &ash::Device,
+    pipeline_cache: vk::PipelineCache,
+    cache_path: &Path,
+) -> Result<()> {
+    let data = unsafe {
+        device
+            .get_pipeline_cache_data(pipeline_cache)
+            .vk_context("Failed to get pipeline cache data!")?
+    };
+
+    let io_error = |source| Error::Io {
+        path: cache_path.to_path_buf(),
+        source,
+    };
+    if let Some(parent) = cache_path.parent() {
+        std::fs::create_dir_all(parent).map_err(io_error)?;
+    }
+    std::fs::write(cache_path, data).map_err(io_error)
+}
+
+/// Whether `data` starts with a pipeline cache header matching the vendor, device and
+/// pipeline cache UUID in `properties`.
+pub fn is_compatible_cache_data(data: &[u8], properties: &vk::PhysicalDeviceProperties) -> bool {
+    if data.len() < HEADER_SIZE {
+        return false;
+    }
+
+    // `VkPipelineCacheHeaderVersionOne` is laid out by the spec, not by the host.
+    let read_u32 = |offset: usize| {
+        let mut bytes = [0; 4];
+        bytes.copy_from_slice(&data[offset..offset + 4]);
+        u32::from_le_bytes(bytes)
+    };
+    let header_length = read_u32(0) as usize;
+    let header_version = read_u32(4) as i32;
+
+    header_length >= HEADER_SIZE
+        && header_length <= data.len()
+        && header_version == vk::PipelineCacheHeaderVersion::ONE.as_raw()
+        && read_u32(8) == properties.vendor_id
+        && read_u32(12) == properties.device_id
+        && data[16..HEADER_SIZE] == properties.pipeline_cache_uuid
+}
+
+fn create_pipeline_cache(device: &ash::Device, initial_data: &[u8]) -> Result<vk::PipelineCache> {
+    let pipeline_cache_create_info = vk::PipelineCacheCreateInfo {
+        s_type: vk::StructureType::PIPELINE_CACHE_CREATE_INFO,
+        p_next: ptr::null(),
+        flags: vk::PipelineCacheCreateFlags::empty(),
+        initial_data_size: initial_data.len(),
+        p_initial_data: initial_data.as_ptr() as *const std::ffi::c_void,
+    };
+
+    unsafe {
+        device
+            .create_pipeline_cache(&pipeline_cache_create_info, None)
+            .vk_context("Failed to create pipeline cache!")
+    }
+}
@@ -30,7 +30,7 @@ pub fn required_extension_names() -> Vec<*const i8> {
     ]
 }
 
-#[cfg(all(windows))]
+#[cfg(windows)]
 pub fn required_extension_names() -> Vec<*const i8> {
     vec![
         Surface::name().as_ptr(),
@@ -48,6 +48,11 @@ pub fn required_extension_names() -> Vec<*const i8> {
     ]
 }
 
+// Offscreen rendering needs no surface extensions at all.
+pub fn headless_extension_names() -> Vec<*const i8> {
+    vec![DebugUtils::name().as_ptr()]
+}
+
 // create surface ---------------------------------------------------------
 #[cfg(all(unix, not(target_os = "android"), not(target_os = "macos")))]
 pub unsafe fn create_surface<E: EntryV1_0, I: InstanceV1_0>(
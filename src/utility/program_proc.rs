@@ -1,12 +1,20 @@
+use crate::utility::shader_watcher::ShaderWatcher;
 use crate::utility::traits::VulkanApp;
 
-use winit::event::{ElementState, Event, KeyboardInput, VirtualKeyCode, WindowEvent};
+use winit::event::{ElementState, Event, KeyboardInput, MouseButton, VirtualKeyCode, WindowEvent};
 use winit::event_loop::{ControlFlow, EventLoop};
+use winit::platform::desktop::EventLoopExtDesktop;
 
 pub struct ProgramProc {
     pub event_loop: EventLoop<()>,
 }
 
+impl Default for ProgramProc {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl ProgramProc {
     pub fn new() -> ProgramProc {
         let event_loop = EventLoop::new();
@@ -14,29 +22,42 @@ impl ProgramProc {
         ProgramProc { event_loop }
     }
 
-    pub fn main_loop<A: 'static + VulkanApp>(self, mut vulkan_app: A) -> ! {
+    /// Runs until the window is closed, then drops `vulkan_app` and returns, so the caller
+    /// can still report errors and pick the exit code.
+    pub fn main_loop<A: VulkanApp>(mut self, mut vulkan_app: A) {
         let mut tick_counter = super::fps_limiter::FPSLimiter::new();
+        let mut shader_watcher = ShaderWatcher::new(vulkan_app.shader_paths());
 
         self.event_loop
-            .run(move |event, _, control_flow| match event {
+            .run_return(|event, _, control_flow| match event {
                 Event::WindowEvent { event, .. } => match event {
                     WindowEvent::CloseRequested => {
                         vulkan_app.wait_device_idle();
                         *control_flow = ControlFlow::Exit;
                     }
-                    WindowEvent::KeyboardInput { input, .. } => match input {
-                        KeyboardInput {
+                    WindowEvent::KeyboardInput { input, .. } => {
+                        let KeyboardInput {
                             virtual_keycode,
                             state,
                             ..
-                        } => match (virtual_keycode, state) {
-                            (Some(VirtualKeyCode::Escape), ElementState::Pressed) => {
-                                vulkan_app.wait_device_idle();
-                                *control_flow = ControlFlow::Exit;
-                            }
-                            _ => {}
-                        },
-                    },
+                        } = input;
+                        if let (Some(VirtualKeyCode::Escape), ElementState::Pressed) =
+                            (virtual_keycode, state)
+                        {
+                            vulkan_app.wait_device_idle();
+                            *control_flow = ControlFlow::Exit;
+                        }
+                    }
+                    WindowEvent::CursorMoved { position, .. } => {
+                        vulkan_app.cursor_moved(position.x, position.y);
+                    }
+                    WindowEvent::MouseInput {
+                        state,
+                        button: MouseButton::Left,
+                        ..
+                    } => {
+                        vulkan_app.mouse_button_changed(state == ElementState::Pressed);
+                    }
                     WindowEvent::Resized(_) => {
                         vulkan_app.wait_device_idle();
                         vulkan_app.resize_framebuffer();
@@ -44,6 +65,16 @@ impl ProgramProc {
                     _ => {}
                 },
                 Event::MainEventsCleared => {
+                    if shader_watcher.poll() {
+                        match vulkan_app.reload_shaders() {
+                            Ok(()) => println!("Reloaded shaders"),
+                            Err(err) => eprintln!(
+                                "Failed to reload shaders, keeping the previous pipeline: {}",
+                                err
+                            ),
+                        }
+                    }
+
                     vulkan_app.window_ref().request_redraw();
                 }
                 Event::RedrawRequested(_) => {
@@ -52,7 +83,12 @@ impl ProgramProc {
                     let mut void = ();
                     tick_counter.run_update(&mut void, |_| {});
 
-                    vulkan_app.draw_frame(delta_time);
+                    if let Err(err) = vulkan_app.draw_frame(delta_time) {
+                        eprintln!("Failed to draw frame: {}", err);
+                        vulkan_app.wait_device_idle();
+                        *control_flow = ControlFlow::Exit;
+                        return;
+                    }
 
                     print!("FPS: {}\r", tick_counter.fps());
 
@@ -0,0 +1,76 @@
+use crate::error::{Error, Result};
+use crate::utility::specialization::SpecializationConstants;
+
+use naga::back::{pipeline_constants, spv, PipelineConstants};
+use naga::front::glsl;
+use naga::valid::{Capabilities, ValidationFlags, Validator};
+use naga::ShaderStage;
+use std::path::Path;
+
+/// Compiles GLSL source into SPIR-V bytes ready for `vkCreateShaderModule`.
+///
+/// Specialization constants (`layout(constant_id = N) const`) are replaced by their value in
+/// `constants`, or by their default, as the SPIR-V writer cannot emit them.
+pub fn compile_glsl(
+    shader_path: &Path,
+    source: &str,
+    stage: ShaderStage,
+    constants: &SpecializationConstants,
+) -> Result<Vec<u8>> {
+    let module = glsl::Frontend::default()
+        .parse(&glsl::Options::from(stage), source)
+        .map_err(|errors| {
+            // Report the first error; later ones are usually a consequence of it.
+            let error = &errors.errors[0];
+            compile_error(shader_path, error.location(source), error.kind.to_string())
+        })?;
+
+    let module_info = Validator::new(ValidationFlags::all(), Capabilities::all())
+        .validate(&module)
+        .map_err(|error| {
+            compile_error(
+                shader_path,
+                error.location(source),
+                error.as_inner().to_string(),
+            )
+        })?;
+
+    // Only pass values for declared constants; the others are ignored like Vulkan does.
+    let mut pipeline_constants = PipelineConstants::default();
+    for (id, value) in constants.iter() {
+        let is_declared = module
+            .overrides
+            .iter()
+            .any(|(_, constant)| constant.id.map(u32::from) == Some(id));
+        if is_declared {
+            pipeline_constants.insert(id.to_string(), value.as_f64());
+        }
+    }
+    let (module, module_info) =
+        pipeline_constants::process_overrides(&module, &module_info, None, &pipeline_constants)
+            .map_err(|error| compile_error(shader_path, None, error.to_string()))?;
+
+    // The source is Vulkan GLSL, so gl_Position is already in Vulkan clip space.
+    let mut options = spv::Options::default();
+    options
+        .flags
+        .remove(spv::WriterFlags::ADJUST_COORDINATE_SPACE);
+
+    let words = spv::write_vec(&module, &module_info, &options, None)
+        .map_err(|error| compile_error(shader_path, None, error.to_string()))?;
+
+    Ok(words.iter().flat_map(|word| word.to_le_bytes()).collect())
+}
+
+fn compile_error(
+    shader_path: &Path,
+    location: Option<naga::SourceLocation>,
+    message: String,
+) -> Error {
+    Error::ShaderCompile {
+        path: shader_path.to_path_buf(),
+        line: location.map(|location| location.line_number),
+        column: location.map(|location| location.line_position),
+        message,
+    }
+}
@@ -0,0 +1,55 @@
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Polls the modification times of shader files so they can be reloaded while running.
+pub struct ShaderWatcher {
+    shader_paths: Vec<PathBuf>,
+    modified_times: Vec<Option<SystemTime>>,
+    last_poll: Instant,
+}
+
+impl ShaderWatcher {
+    pub fn new(shader_paths: Vec<PathBuf>) -> ShaderWatcher {
+        let modified_times = shader_paths
+            .iter()
+            .map(PathBuf::as_path)
+            .map(modified_time)
+            .collect();
+
+        ShaderWatcher {
+            shader_paths,
+            modified_times,
+            last_poll: Instant::now(),
+        }
+    }
+
+    /// Returns true when any watched file has been modified since the last change was reported.
+    ///
+    /// The file system is checked at most once per `POLL_INTERVAL`. A file that is missing
+    /// (e.g. while an editor replaces it) is not reported until it reappears.
+    pub fn poll(&mut self) -> bool {
+        if self.last_poll.elapsed() < POLL_INTERVAL {
+            return false;
+        }
+        self.last_poll = Instant::now();
+
+        let mut is_changed = false;
+        for (path, last_modified) in self.shader_paths.iter().zip(self.modified_times.iter_mut()) {
+            let modified = modified_time(path);
+            if modified.is_some() && modified != *last_modified {
+                *last_modified = modified;
+                is_changed = true;
+            }
+        }
+
+        is_changed
+    }
+}
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
@@ -0,0 +1,320 @@
+use crate::error::{Error, Result};
+use crate::utility::structures::ShadertoyUniforms;
+
+use ash::vk;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Number of `iChannel` inputs each pass can sample.
+pub const CHANNEL_COUNT: usize = 4;
+
+const PRELUDE: &str = include_str!("../../shaders/shadertoy/prelude.glsl");
+const EPILOGUE: &str = include_str!("../../shaders/shadertoy/epilogue.glsl");
+
+/// Shadertoy fragment shaders define `mainImage` and never carry a `#version` directive.
+pub fn is_shadertoy_source(source: &str) -> bool {
+    source.contains("mainImage") && !source.contains("#version")
+}
+
+/// Wraps a pasted Shadertoy shader with the uniform block and a `main` calling `mainImage`.
+pub fn wrap_source(source: &str) -> String {
+    format!("{}{}\n{}", PRELUDE, source, EPILOGUE)
+}
+
+/// Maps a line of the wrapped source back to the line of the pasted shader.
+pub fn source_line(wrapped_line: u32) -> u32 {
+    let prelude_line_count = PRELUDE.lines().count() as u32;
+
+    wrapped_line.saturating_sub(prelude_line_count).max(1)
+}
+
+/// The offscreen buffers a pass description can declare, plus the final on-screen pass.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PassName {
+    BufferA,
+    BufferB,
+    BufferC,
+    BufferD,
+    Image,
+}
+
+impl PassName {
+    fn parse(name: &str) -> Option<PassName> {
+        match name {
+            "buffer_a" => Some(PassName::BufferA),
+            "buffer_b" => Some(PassName::BufferB),
+            "buffer_c" => Some(PassName::BufferC),
+            "buffer_d" => Some(PassName::BufferD),
+            "image" => Some(PassName::Image),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PassDescription {
+    pub name: PassName,
+    pub shader_path: PathBuf,
+    /// Index into `PassesDescription::buffers` sampled as `iChannel0..3`, if any.
+    pub channels: [Option<usize>; CHANNEL_COUNT],
+}
+
+/// Passes rendered each frame: the offscreen buffers in order, then the image pass.
+///
+/// A description file has one pass per line, `#` starts a comment:
+///
+/// ```text
+/// # pass    shader          iChannel0  iChannel1 ...
+/// buffer_a  buffer_a.frag   buffer_a
+/// image     image.frag      buffer_a   -
+/// ```
+///
+/// Shader paths are relative to the description file and `-` leaves a channel unbound.
+/// Every pass sees the buffers rendered before it from the current frame; its own output
+/// and the buffers rendered after it are seen from the previous frame.
+#[derive(Debug, Clone)]
+pub struct PassesDescription {
+    pub buffers: Vec<PassDescription>,
+    pub image: PassDescription,
+}
+
+impl PassesDescription {
+    /// Loads a description file, or treats a `.frag` file as a lone image pass.
+    pub fn load(path: &Path) -> Result<PassesDescription> {
+        if path.extension().and_then(|extension| extension.to_str()) == Some("frag") {
+            return Ok(PassesDescription {
+                buffers: vec![],
+                image: PassDescription {
+                    name: PassName::Image,
+                    shader_path: path.to_path_buf(),
+                    channels: [None; CHANNEL_COUNT],
+                },
+            });
+        }
+
+        let source = std::fs::read_to_string(path).map_err(|source| Error::Io {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        let base_dir = path.parent().unwrap_or_else(|| Path::new(""));
+
+        PassesDescription::parse(&source, base_dir).map_err(|(line, reason)| Error::InvalidPasses {
+            path: path.to_path_buf(),
+            line,
+            reason,
+        })
+    }
+
+    /// On failure returns the 1-based line number and the reason.
+    fn parse(
+        source: &str,
+        base_dir: &Path,
+    ) -> std::result::Result<PassesDescription, (usize, String)> {
+        // Channels may name buffers declared further down, so resolve them after all lines.
+        let mut passes = vec![];
+        for (index, line) in source.lines().enumerate() {
+            let line_number = index + 1;
+            let line = line.split('#').next().unwrap_or("");
+            let mut fields = line.split_whitespace();
+            let name = match fields.next() {
+                Some(name) => name,
+                None => continue,
+            };
+
+            let pass_name = PassName::parse(name)
+                .ok_or_else(|| (line_number, format!("unknown pass '{}'", name)))?;
+            if passes.iter().any(|(_, pass, _, _)| *pass == pass_name) {
+                return Err((line_number, format!("pass '{}' is declared twice", name)));
+            }
+            let shader = fields
+                .next()
+                .ok_or_else(|| (line_number, format!("pass '{}' has no shader", name)))?;
+            let channels: Vec<&str> = fields.collect();
+            if channels.len() > CHANNEL_COUNT {
+                return Err((
+                    line_number,
+                    format!("at most {} channels are supported", CHANNEL_COUNT),
+                ));
+            }
+
+            passes.push((line_number, pass_name, base_dir.join(shader), channels));
+        }
+
+        let image_position = passes
+            .iter()
+            .position(|(_, name, _, _)| *name == PassName::Image)
+            .ok_or_else(|| (source.lines().count().max(1), "no image pass".to_owned()))?;
+        if image_position != passes.len() - 1 {
+            return Err((
+                passes[image_position].0,
+                "the image pass has to be the last one".to_owned(),
+            ));
+        }
+
+        let buffer_names: Vec<PassName> = passes[..image_position]
+            .iter()
+            .map(|(_, name, _, _)| *name)
+            .collect();
+        let mut descriptions = vec![];
+        for (line_number, name, shader_path, channel_names) in passes {
+            let mut channels = [None; CHANNEL_COUNT];
+            for (channel, channel_name) in channels.iter_mut().zip(channel_names) {
+                if channel_name == "-" {
+                    continue;
+                }
+                let buffer = PassName::parse(channel_name)
+                    .and_then(|channel_name| {
+                        buffer_names.iter().position(|name| *name == channel_name)
+                    })
+                    .ok_or_else(|| {
+                        (
+                            line_number,
+                            format!("channel '{}' is not a declared buffer", channel_name),
+                        )
+                    })?;
+                *channel = Some(buffer);
+            }
+
+            descriptions.push(PassDescription {
+                name,
+                shader_path,
+                channels,
+            });
+        }
+
+        let image = descriptions.pop().expect("image pass was checked above");
+        Ok(PassesDescription {
+            buffers: descriptions,
+            image,
+        })
+    }
+
+    /// Every shader used by the passes, for hot-reloading.
+    pub fn shader_paths(&self) -> Vec<PathBuf> {
+        self.buffers
+            .iter()
+            .chain(std::iter::once(&self.image))
+            .map(|pass| pass.shader_path.clone())
+            .collect()
+    }
+}
+
+/// Left mouse button state in the form Shadertoy exposes through `iMouse`.
+#[derive(Debug, Default)]
+pub struct ShadertoyMouse {
+    cursor: [f32; 2],
+    position: [f32; 2],
+    click: [f32; 2],
+    is_pressed: bool,
+    is_clicked: bool,
+    has_clicked: bool,
+}
+
+impl ShadertoyMouse {
+    /// `x` and `y` are window pixel coordinates with the origin in the top-left corner.
+    pub fn cursor_moved(&mut self, x: f64, y: f64) {
+        self.cursor = [x as f32, y as f32];
+        if self.is_pressed {
+            self.position = self.cursor;
+        }
+    }
+
+    pub fn button_changed(&mut self, is_pressed: bool) {
+        if is_pressed && !self.is_pressed {
+            self.click = self.cursor;
+            self.position = self.cursor;
+            self.is_clicked = true;
+            self.has_clicked = true;
+        }
+        self.is_pressed = is_pressed;
+    }
+
+    /// xy is the last dragged position, zw the last click position. z is negative once
+    /// the button is released and w is only positive on the frame of the click.
+    fn take_uniform(&mut self, height: f32) -> [f32; 4] {
+        // Like Shadertoy, report zeros until the first click.
+        if !self.has_clicked {
+            return [0.0; 4];
+        }
+
+        let click_x = if self.is_pressed {
+            self.click[0]
+        } else {
+            -self.click[0]
+        };
+        let click_y = height - self.click[1];
+        let click_y = if self.is_clicked { click_y } else { -click_y };
+        self.is_clicked = false;
+
+        [
+            self.position[0],
+            height - self.position[1],
+            click_x,
+            click_y,
+        ]
+    }
+}
+
+/// Accumulates time, frame count and mouse input into `ShadertoyUniforms`.
+#[derive(Debug, Default)]
+pub struct ShadertoyInput {
+    pub mouse: ShadertoyMouse,
+    time: f32,
+    frame: i32,
+}
+
+impl ShadertoyInput {
+    pub fn new() -> ShadertoyInput {
+        ShadertoyInput::default()
+    }
+
+    /// Number of frames produced so far, i.e. `iFrame` of the next frame.
+    pub fn frame(&self) -> usize {
+        self.frame as usize
+    }
+
+    /// Advances the clock by `delta_time` seconds and returns the uniforms for the next frame.
+    pub fn next_frame(&mut self, extent: vk::Extent2D, delta_time: f32) -> ShadertoyUniforms {
+        self.time += delta_time;
+
+        let uniforms = ShadertoyUniforms {
+            i_resolution: [extent.width as f32, extent.height as f32, 1.0],
+            i_time: self.time,
+            i_mouse: self.mouse.take_uniform(extent.height as f32),
+            i_date: current_date(),
+            i_time_delta: delta_time,
+            i_frame: self.frame,
+        };
+        self.frame += 1;
+
+        uniforms
+    }
+}
+
+/// Returns (year, month starting at 0, day, seconds since midnight) in UTC, like `iDate`.
+fn current_date() -> [f32; 4] {
+    let since_epoch = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    let days = (since_epoch.as_secs() / 86_400) as i64;
+    let seconds =
+        (since_epoch.as_secs() % 86_400) as f32 + since_epoch.subsec_micros() as f32 * 1e-6;
+
+    // Converts days since 1970-01-01 to a civil date (Howard Hinnant's algorithm).
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    [year as f32, (month - 1) as f32, day as f32, seconds]
+}
@@ -0,0 +1,191 @@
+use crate::error::{Error, Result};
+use crate::utility::structures::{Material, MeshRange, Model, TextureSource, VertexV3};
+
+use cgmath::{InnerSpace, Matrix, Matrix3, Matrix4, SquareMatrix, Vector3, Vector4};
+use gltf::image::Format;
+use gltf::mesh::Mode;
+use std::path::Path;
+
+/// Loads the default scene of a glTF or GLB file, appending every triangle primitive to one
+/// vertex and index buffer with the node transforms applied.
+///
+/// Buffers and images may be embedded or external. Base color textures are decoded to RGBA
+/// since glTF texture coordinates start at the top row, unlike the files
+/// `create_texture_image` loads.
+pub fn load_gltf(model_path: &Path) -> Result<Model> {
+    let (document, buffers, images) = gltf::import(model_path).map_err(|source| Error::Gltf {
+        path: model_path.to_path_buf(),
+        source,
+    })?;
+    let invalid_model = |reason| Error::InvalidModel {
+        path: model_path.to_path_buf(),
+        reason,
+    };
+
+    let scene = document
+        .default_scene()
+        .or_else(|| document.scenes().next())
+        .ok_or_else(|| invalid_model("No scene to load"))?;
+
+    let mut model = Model {
+        vertices: vec![],
+        indices: vec![],
+        meshes: vec![],
+        materials: vec![],
+    };
+
+    // Walk the node hierarchy depth-first, accumulating transforms from the roots.
+    let mut nodes: Vec<(gltf::Node, Matrix4<f32>)> = scene
+        .nodes()
+        .map(|node| (node, Matrix4::identity()))
+        .collect();
+    while let Some((node, parent_transform)) = nodes.pop() {
+        let transform = parent_transform * Matrix4::from(node.transform().matrix());
+        nodes.extend(node.children().map(|child| (child, transform)));
+
+        let mesh = match node.mesh() {
+            Some(mesh) => mesh,
+            None => continue,
+        };
+        // Normals need the inverse transpose so non-uniform scales keep them perpendicular.
+        let linear_transform = Matrix3::from_cols(
+            transform.x.truncate(),
+            transform.y.truncate(),
+            transform.z.truncate(),
+        );
+        let normal_transform = linear_transform
+            .invert()
+            .map(|inverse| inverse.transpose())
+            .unwrap_or(linear_transform);
+
+        for primitive in mesh.primitives() {
+            if primitive.mode() != Mode::Triangles {
+                return Err(invalid_model("Only triangle list primitives are supported"));
+            }
+
+            let reader = primitive.reader(|buffer| Some(&buffers[buffer.index()]));
+            let positions: Vec<[f32; 3]> = reader
+                .read_positions()
+                .ok_or_else(|| invalid_model("Primitive has no positions"))?
+                .collect();
+            let vertex_count = positions.len();
+            let normals: Vec<[f32; 3]> = match reader.read_normals() {
+                Some(normals) => normals.collect(),
+                None => vec![[0.0; 3]; vertex_count],
+            };
+            let tangents: Vec<[f32; 4]> = match reader.read_tangents() {
+                Some(tangents) => tangents.collect(),
+                None => vec![[0.0; 4]; vertex_count],
+            };
+            let tex_coords: Vec<[f32; 2]> = match reader.read_tex_coords(0) {
+                Some(tex_coords) => tex_coords.into_f32().collect(),
+                None => vec![[0.0; 2]; vertex_count],
+            };
+            let colors: Vec<[f32; 4]> = match reader.read_colors(0) {
+                Some(colors) => colors.into_rgba_f32().collect(),
+                None => vec![[1.0; 4]; vertex_count],
+            };
+            if normals.len() != vertex_count
+                || tangents.len() != vertex_count
+                || tex_coords.len() != vertex_count
+                || colors.len() != vertex_count
+            {
+                return Err(invalid_model("Primitive attributes differ in length"));
+            }
+
+            let base_vertex = model.vertices.len() as u32;
+            for i in 0..vertex_count {
+                let [x, y, z] = positions[i];
+                let position = transform * Vector4::new(x, y, z, 1.0);
+                let normal = normal_transform * Vector3::from(normals[i]);
+                let normal = if normal.magnitude2() > 0.0 {
+                    normal.normalize()
+                } else {
+                    normal
+                };
+                let [tx, ty, tz, handedness] = tangents[i];
+                let tangent = linear_transform * Vector3::new(tx, ty, tz);
+                let tangent = if tangent.magnitude2() > 0.0 {
+                    tangent.normalize()
+                } else {
+                    tangent
+                };
+
+                model.vertices.push(VertexV3 {
+                    pos: position.into(),
+                    color: colors[i],
+                    tex_coord: tex_coords[i],
+                    normal: normal.into(),
+                    tangent: tangent.extend(handedness).into(),
+                });
+            }
+
+            let indices: Vec<u32> = match reader.read_indices() {
+                Some(indices) => indices.into_u32().collect(),
+                None => (0..vertex_count as u32).collect(),
+            };
+            if indices.iter().any(|&index| index as usize >= vertex_count) {
+                return Err(invalid_model("Primitive index is out of range"));
+            }
+
+            model.meshes.push(MeshRange {
+                first_index: model.indices.len() as u32,
+                index_count: indices.len() as u32,
+                material_index: primitive.material().index(),
+            });
+            model
+                .indices
+                .extend(indices.iter().map(|index| base_vertex + index));
+        }
+    }
+
+    for material in document.materials() {
+        let pbr = material.pbr_metallic_roughness();
+        let [red, green, blue, _alpha] = pbr.base_color_factor();
+        let diffuse_texture = match pbr.base_color_texture() {
+            Some(info) => {
+                let image = &images[info.texture().source().index()];
+                Some(rgba_texture(model_path, image)?)
+            }
+            None => None,
+        };
+
+        model.materials.push(Material {
+            name: material.name().unwrap_or_default().to_owned(),
+            diffuse_color: [red, green, blue],
+            diffuse_texture,
+        });
+    }
+
+    Ok(model)
+}
+
+fn rgba_texture(model_path: &Path, image: &gltf::image::Data) -> Result<TextureSource> {
+    let pixels = match image.format {
+        Format::R8G8B8A8 => image.pixels.clone(),
+        Format::R8G8B8 => image
+            .pixels
+            .chunks(3)
+            .flat_map(|rgb| [rgb[0], rgb[1], rgb[2], 255])
+            .collect(),
+        // Grayscale images decode to one or two channels: luma, then alpha.
+        Format::R8G8 => image
+            .pixels
+            .chunks(2)
+            .flat_map(|la| [la[0], la[0], la[0], la[1]])
+            .collect(),
+        Format::R8 => image.pixels.iter().flat_map(|&l| [l, l, l, 255]).collect(),
+        _ => {
+            return Err(Error::UnsupportedImage {
+                path: model_path.to_path_buf(),
+                reason: "glTF texture is not an 8-bit image",
+            })
+        }
+    };
+
+    Ok(TextureSource::Rgba {
+        width: image.width,
+        height: image.height,
+        pixels,
+    })
+}
@@ -1,6 +1,17 @@
+pub mod gltf_loader;
 pub mod pipeline;
-
-use crate::utility::{constants, debug, platforms, structures::*, tools};
+pub mod pipeline_builder;
+
+use crate::error::{Error, Result, VkResultExt};
+use crate::utility::{
+    allocator::{Allocation, MemoryAllocator, ResourceKind},
+    constants, debug,
+    debug_messenger::DebugMessenger,
+    device_selection::{DeviceScore, DeviceSelector},
+    platforms,
+    structures::*,
+    tools,
+};
 
 use ash::version::{DeviceV1_0, EntryV1_0, InstanceV1_0};
 use ash::vk;
@@ -10,17 +21,37 @@ use std::os::raw::{c_char, c_void};
 use std::path::Path;
 use std::ptr;
 
+/// Creates the instance with the layers of `validation` that are installed. Returns the
+/// validation settings that are actually in effect, see `ValidationInfo::with_available_layers`.
+///
+/// Messages about instance creation and destruction go to `messenger`, which has to outlive
+/// the instance.
 pub fn create_instance(
     entry: &ash::Entry,
     window_title: &str,
-    is_debug_enabled: bool,
-    required_validation_layers: &Vec<&str>,
-) -> ash::Instance {
-    if is_debug_enabled
-        && debug::check_validation_layer_support(entry, required_validation_layers) == false
-    {
-        panic!("Validation layers requested, but not available!")
-    }
+    validation: &debug::ValidationInfo,
+    messenger: &DebugMessenger,
+) -> Result<(ash::Instance, debug::ValidationInfo)> {
+    create_instance_with_extensions(
+        entry,
+        window_title,
+        validation,
+        messenger,
+        &platforms::required_extension_names(),
+    )
+}
+
+/// Same as `create_instance`, but with an explicit list of instance extensions
+/// (e.g. `platforms::headless_extension_names()` when no window is involved).
+pub fn create_instance_with_extensions(
+    entry: &ash::Entry,
+    window_title: &str,
+    validation: &debug::ValidationInfo,
+    messenger: &DebugMessenger,
+    extension_names: &[*const i8],
+) -> Result<(ash::Instance, debug::ValidationInfo)> {
+    let validation = validation.with_available_layers(entry)?;
+    let is_debug_enabled = validation.is_enable;
 
     let app_name = CString::new(window_title).unwrap();
     let engine_name = CString::new("Vulkan Engine").unwrap();
@@ -35,13 +66,13 @@ pub fn create_instance(
     };
 
     // This create info used to debug issues in vk::createInstance and vk::destroyInstance.
-    let debug_utils_create_info = debug::populate_debug_messenger_create_info();
+    let debug_utils_create_info =
+        debug::populate_debug_messenger_create_info(&validation, messenger);
 
-    let extension_names = platforms::required_extension_names();
-
-    let required_validation_layer_raw_names: Vec<CString> = required_validation_layers
+    let required_validation_layer_raw_names: Vec<CString> = validation
+        .required_validation_layers
         .iter()
-        .map(|layer_name| CString::new(*layer_name).unwrap())
+        .map(|layer_name| CString::new(layer_name.as_str()).unwrap())
         .collect();
     let enable_layer_names: Vec<*const i8> = required_validation_layer_raw_names
         .iter()
@@ -71,13 +102,9 @@ pub fn create_instance(
         enabled_extension_count: extension_names.len() as u32,
     };
 
-    let instance: ash::Instance = unsafe {
-        entry
-            .create_instance(&create_info, None)
-            .expect("Failed to create instance!")
-    };
+    let instance: ash::Instance = unsafe { entry.create_instance(&create_info, None)? };
 
-    instance
+    Ok((instance, validation))
 }
 
 pub fn create_surface(
@@ -86,71 +113,123 @@ pub fn create_surface(
     window: &winit::window::Window,
     screen_width: u32,
     screen_height: u32,
-) -> SurfaceStuff {
+) -> Result<SurfaceStuff> {
     let surface = unsafe {
-        platforms::create_surface(entry, instance, window).expect("Failed to create surface.")
+        platforms::create_surface(entry, instance, window)
+            .vk_context("Failed to create surface.")?
     };
     let surface_loader = ash::extensions::khr::Surface::new(entry, instance);
 
-    SurfaceStuff {
+    Ok(SurfaceStuff {
         surface_loader,
         surface,
         screen_width,
         screen_height,
-    }
+    })
 }
 
+/// Picks the highest scoring suitable device, or the best one matching `selector`, and
+/// logs why the other devices were not used.
+///
+/// Pass `None` as `surface_stuff` for headless rendering, where presentation support
+/// and the swapchain extension are not required.
 pub fn pick_physical_device(
     instance: &ash::Instance,
-    surface_stuff: &SurfaceStuff,
+    surface_stuff: Option<&SurfaceStuff>,
     required_device_extensions: &DeviceExtension,
-) -> vk::PhysicalDevice {
+    selector: Option<&DeviceSelector>,
+) -> Result<vk::PhysicalDevice> {
     let physical_devices = unsafe {
         instance
             .enumerate_physical_devices()
-            .expect("Failed to enumerate Physical Devices!")
+            .vk_context("Failed to enumerate Physical Devices!")?
     };
 
-    let result = physical_devices.iter().find(|physical_device| {
-        is_physical_device_suitable(
+    let mut candidates = vec![];
+    let mut rejections = vec![];
+    for (index, &physical_device) in physical_devices.iter().enumerate() {
+        let properties = unsafe { instance.get_physical_device_properties(physical_device) };
+        let name = tools::vk_to_string(&properties.device_name);
+        if let Some(selector) = selector {
+            if !selector.matches(index, &properties) {
+                rejections.push((index, name, format!("does not match {}", selector)));
+                continue;
+            }
+        }
+
+        match find_missing_requirement(
             instance,
-            **physical_device,
+            physical_device,
             surface_stuff,
             required_device_extensions,
-        )
-    });
+        )? {
+            Some(missing_requirement) => rejections.push((index, name, missing_requirement)),
+            None => {
+                let score = DeviceScore::new(
+                    &properties,
+                    &unsafe { instance.get_physical_device_memory_properties(physical_device) },
+                    &unsafe { instance.get_physical_device_features(physical_device) },
+                );
+                candidates.push((index, physical_device, name, score));
+            }
+        }
+    }
 
-    match result {
-        None => panic!("Failed to find a suitable GPU!"),
-        Some(physical_device) => *physical_device,
+    // The first device wins ties, so the driver order decides between identical GPUs.
+    let best = candidates
+        .iter()
+        .enumerate()
+        .max_by_key(|&(position, &(_, _, _, score))| (score, std::cmp::Reverse(position)))
+        .map(|(position, _)| position);
+    let chosen = best.map(|position| candidates.remove(position));
+    for (index, _, name, score) in candidates {
+        rejections.push((index, name, format!("lower score ({})", score)));
+    }
+
+    if let Some((index, _, name, score)) = &chosen {
+        eprintln!("Using GPU {} '{}' ({})", index, name, score);
+    }
+    for (index, name, reason) in rejections {
+        eprintln!("Not using GPU {} '{}': {}", index, name, reason);
+    }
+
+    match (chosen, selector) {
+        (Some((_, physical_device, _, _)), _) => Ok(physical_device),
+        (None, Some(selector)) => Err(Error::NoMatchingGpu(selector.to_string())),
+        (None, None) => Err(Error::NoSuitableGpu),
     }
 }
 
-fn is_physical_device_suitable(
+/// Describes the first requirement `physical_device` does not meet, if any.
+fn find_missing_requirement(
     instance: &ash::Instance,
     physical_device: vk::PhysicalDevice,
-    surface_stuff: &SurfaceStuff,
+    surface_stuff: Option<&SurfaceStuff>,
     required_device_extensions: &DeviceExtension,
-) -> bool {
+) -> Result<Option<String>> {
     let device_features = unsafe { instance.get_physical_device_features(physical_device) };
 
     let indices = find_queue_family(instance, physical_device, surface_stuff);
+    if !is_queue_family_complete(&indices, surface_stuff) {
+        return Ok(Some("no graphics or present queue family".to_owned()));
+    }
+    if !check_device_extension_support(instance, physical_device, required_device_extensions)? {
+        return Ok(Some(format!(
+            "lacks one of the device extensions {}",
+            required_device_extensions.names.join(", ")
+        )));
+    }
+    if let Some(surface_stuff) = surface_stuff {
+        let swapchain_support = query_swapchain_support(physical_device, surface_stuff)?;
+        if swapchain_support.formats.is_empty() || swapchain_support.present_modes.is_empty() {
+            return Ok(Some("no surface formats or present modes".to_owned()));
+        }
+    }
+    if device_features.sampler_anisotropy != vk::TRUE {
+        return Ok(Some("no sampler anisotropy".to_owned()));
+    }
 
-    let is_queue_family_supported = indices.is_complete();
-    let is_device_extensions_supported =
-        check_device_extension_support(instance, physical_device, required_device_extensions);
-    let is_anisotropy_supported = device_features.sampler_anisotropy == 1;
-    let is_swapchain_supported = if is_device_extensions_supported {
-        let swapchain_support = query_swapchain_support(physical_device, surface_stuff);
-        !swapchain_support.formats.is_empty() && !swapchain_support.present_modes.is_empty()
-    } else {
-        false
-    };
-
-    return is_queue_family_supported
-        && is_device_extensions_supported
-        && is_swapchain_supported
-        && is_anisotropy_supported;
+    Ok(None)
 }
 
 pub fn create_logical_device(
@@ -158,13 +237,15 @@ pub fn create_logical_device(
     physical_device: vk::PhysicalDevice,
     validation: &debug::ValidationInfo,
     device_extensions: &DeviceExtension,
-    surface_stuff: &SurfaceStuff,
-) -> (ash::Device, QueueFamilyIndices) {
+    surface_stuff: Option<&SurfaceStuff>,
+) -> Result<(ash::Device, QueueFamilyIndices)> {
     let indices = find_queue_family(instance, physical_device, surface_stuff);
 
     let mut unique_queue_families = HashSet::new();
     unique_queue_families.insert(indices.graphics_family.unwrap());
-    unique_queue_families.insert(indices.present_family.unwrap());
+    if let Some(present_family) = indices.present_family {
+        unique_queue_families.insert(present_family);
+    }
 
     let queue_priorities = [1.0_f32];
     let mut queue_create_infos = vec![];
@@ -180,22 +261,35 @@ pub fn create_logical_device(
         queue_create_infos.push(queue_create_info);
     }
 
+    let supported_features = unsafe { instance.get_physical_device_features(physical_device) };
     let physical_device_features = vk::PhysicalDeviceFeatures {
         sampler_anisotropy: vk::TRUE,
-        ..Default::default() // no features
+        // Optional, sample shading is skipped without it.
+        sample_rate_shading: supported_features.sample_rate_shading,
+        // Optional, needed by `LINE` and `POINT` polygon modes.
+        fill_mode_non_solid: supported_features.fill_mode_non_solid,
+        ..Default::default()
     };
 
     let required_validation_layer_raw_names: Vec<CString> = validation
         .required_validation_layers
         .iter()
-        .map(|layer_name| CString::new(*layer_name).unwrap())
+        .map(|layer_name| CString::new(layer_name.as_str()).unwrap())
         .collect();
     let enable_layer_names: Vec<*const c_char> = required_validation_layer_raw_names
         .iter()
         .map(|layer_name| layer_name.as_ptr())
         .collect();
 
-    let enable_extension_names = device_extensions.get_extensions_raw_names();
+    let enable_extension_raw_names: Vec<CString> = device_extensions
+        .names
+        .iter()
+        .map(|extension_name| CString::new(*extension_name).unwrap())
+        .collect();
+    let enable_extension_names: Vec<*const c_char> = enable_extension_raw_names
+        .iter()
+        .map(|extension_name| extension_name.as_ptr())
+        .collect();
 
     let device_create_info = vk::DeviceCreateInfo {
         s_type: vk::StructureType::DEVICE_CREATE_INFO,
@@ -221,64 +315,76 @@ pub fn create_logical_device(
     let device: ash::Device = unsafe {
         instance
             .create_device(physical_device, &device_create_info, None)
-            .expect("Failed to create logical device!")
+            .vk_context("Failed to create logical device!")?
     };
 
-    (device, indices)
+    Ok((device, indices))
 }
 
 fn find_queue_family(
     instance: &ash::Instance,
     physical_device: vk::PhysicalDevice,
-    surface_stuff: &SurfaceStuff,
+    surface_stuff: Option<&SurfaceStuff>,
 ) -> QueueFamilyIndices {
     let queue_families =
         unsafe { instance.get_physical_device_queue_family_properties(physical_device) };
 
     let mut queue_family_indices = QueueFamilyIndices::new();
 
-    let mut index = 0;
-    for queue_family in queue_families.iter() {
+    for (index, queue_family) in queue_families.iter().enumerate() {
+        let index = index as u32;
+
         if queue_family.queue_count > 0
             && queue_family.queue_flags.contains(vk::QueueFlags::GRAPHICS)
         {
             queue_family_indices.graphics_family = Some(index);
         }
 
-        let is_present_support = unsafe {
-            surface_stuff
-                .surface_loader
-                .get_physical_device_surface_support(
-                    physical_device,
-                    index as u32,
-                    surface_stuff.surface,
-                )
-        };
-        if queue_family.queue_count > 0 && is_present_support.unwrap_or(false) {
-            queue_family_indices.present_family = Some(index);
+        if let Some(surface_stuff) = surface_stuff {
+            let is_present_support = unsafe {
+                surface_stuff
+                    .surface_loader
+                    .get_physical_device_surface_support(
+                        physical_device,
+                        index,
+                        surface_stuff.surface,
+                    )
+            };
+            if queue_family.queue_count > 0 && is_present_support.unwrap_or(false) {
+                queue_family_indices.present_family = Some(index);
+            }
         }
 
-        if queue_family_indices.is_complete() {
+        if is_queue_family_complete(&queue_family_indices, surface_stuff) {
             break;
         } else {
             queue_family_indices = QueueFamilyIndices::new();
         }
-
-        index += 1;
     }
 
     queue_family_indices
 }
 
-fn check_device_extension_support(
+/// Without a surface only the graphics family is needed.
+fn is_queue_family_complete(
+    indices: &QueueFamilyIndices,
+    surface_stuff: Option<&SurfaceStuff>,
+) -> bool {
+    match surface_stuff {
+        Some(_) => indices.is_complete(),
+        None => indices.graphics_family.is_some(),
+    }
+}
+
+pub fn check_device_extension_support(
     instance: &ash::Instance,
     physical_device: vk::PhysicalDevice,
     device_extensions: &DeviceExtension,
-) -> bool {
+) -> Result<bool> {
     let available_extensions = unsafe {
         instance
             .enumerate_device_extension_properties(physical_device)
-            .expect("Failed to get device extension properties.")
+            .vk_context("Failed to get device extension properties.")?
     };
 
     let available_extension_names: Vec<String> = available_extensions
@@ -295,32 +401,32 @@ fn check_device_extension_support(
         required_extensions.remove(extension_name);
     }
 
-    required_extensions.is_empty()
+    Ok(required_extensions.is_empty())
 }
 
-fn query_swapchain_support(
+pub fn query_swapchain_support(
     physical_device: vk::PhysicalDevice,
     surface_stuff: &SurfaceStuff,
-) -> SwapChainSupportDetail {
+) -> Result<SwapChainSupportDetail> {
     unsafe {
         let capabilities = surface_stuff
             .surface_loader
             .get_physical_device_surface_capabilities(physical_device, surface_stuff.surface)
-            .expect("Failed to query for surface capabilities");
+            .vk_context("Failed to query for surface capabilities")?;
         let formats = surface_stuff
             .surface_loader
             .get_physical_device_surface_formats(physical_device, surface_stuff.surface)
-            .expect("Failed to query for surface formats.");
+            .vk_context("Failed to query for surface formats.")?;
         let present_modes = surface_stuff
             .surface_loader
             .get_physical_device_surface_present_modes(physical_device, surface_stuff.surface)
-            .expect("Failed to query for surface present mode.");
+            .vk_context("Failed to query for surface present mode.")?;
 
-        SwapChainSupportDetail {
+        Ok(SwapChainSupportDetail {
             capabilities,
             formats,
             present_modes,
-        }
+        })
     }
 }
 
@@ -331,8 +437,8 @@ pub fn create_swapchain(
     window: &winit::window::Window,
     surface_stuff: &SurfaceStuff,
     queue_family: &QueueFamilyIndices,
-) -> SwapChainStuff {
-    let swapchain_support = query_swapchain_support(physical_device, surface_stuff);
+) -> Result<SwapChainStuff> {
+    let swapchain_support = query_swapchain_support(physical_device, surface_stuff)?;
 
     let surface_format = choose_swapchain_format(&swapchain_support.formats);
     let present_mode = choose_swapchain_present_mode(&swapchain_support.present_modes);
@@ -384,38 +490,38 @@ pub fn create_swapchain(
     let swapchain = unsafe {
         swapchain_loader
             .create_swapchain(&swapchain_create_info, None)
-            .expect("Failed to create Swapchain!")
+            .vk_context("Failed to create Swapchain!")?
     };
 
     let swapchain_images = unsafe {
         swapchain_loader
             .get_swapchain_images(swapchain)
-            .expect("Failed to get Swapchain Images.")
+            .vk_context("Failed to get Swapchain Images.")?
     };
 
-    SwapChainStuff {
+    Ok(SwapChainStuff {
         swapchain_loader,
         swapchain,
         swapchain_format: surface_format.format,
         swapchain_extent: extent,
         swapchain_images,
-    }
+    })
 }
 
-fn choose_swapchain_format(available_formats: &Vec<vk::SurfaceFormatKHR>) -> vk::SurfaceFormatKHR {
+fn choose_swapchain_format(available_formats: &[vk::SurfaceFormatKHR]) -> vk::SurfaceFormatKHR {
     for available_format in available_formats {
         if available_format.format == vk::Format::B8G8R8A8_SRGB
             && available_format.color_space == vk::ColorSpaceKHR::SRGB_NONLINEAR
         {
-            return available_format.clone();
+            return *available_format;
         }
     }
 
-    return available_formats.first().unwrap().clone();
+    *available_formats.first().unwrap()
 }
 
 fn choose_swapchain_present_mode(
-    available_present_modes: &Vec<vk::PresentModeKHR>,
+    available_present_modes: &[vk::PresentModeKHR],
 ) -> vk::PresentModeKHR {
     for &available_present_mode in available_present_modes.iter() {
         if available_present_mode == vk::PresentModeKHR::MAILBOX {
@@ -423,14 +529,14 @@ fn choose_swapchain_present_mode(
         }
     }
 
-    return vk::PresentModeKHR::FIFO;
+    vk::PresentModeKHR::FIFO
 }
 
 fn choose_swapchain_extent(
     capabilities: &vk::SurfaceCapabilitiesKHR,
     window: &winit::window::Window,
 ) -> vk::Extent2D {
-    if capabilities.current_extent.width != u32::max_value() {
+    if capabilities.current_extent.width != u32::MAX {
         capabilities.current_extent
     } else {
         use num::clamp;
@@ -438,12 +544,12 @@ fn choose_swapchain_extent(
 
         vk::Extent2D {
             width: clamp(
-                window_size.width as u32,
+                window_size.width,
                 capabilities.min_image_extent.width,
                 capabilities.max_image_extent.width,
             ),
             height: clamp(
-                window_size.height as u32,
+                window_size.height,
                 capabilities.min_image_extent.height,
                 capabilities.max_image_extent.height,
             ),
@@ -451,7 +557,7 @@ fn choose_swapchain_extent(
     }
 }
 
-pub fn create_shader_module(device: &ash::Device, code: Vec<u8>) -> vk::ShaderModule {
+pub fn create_shader_module(device: &ash::Device, code: Vec<u8>) -> Result<vk::ShaderModule> {
     let shader_module_create_info = vk::ShaderModuleCreateInfo {
         s_type: vk::StructureType::SHADER_MODULE_CREATE_INFO,
         p_next: ptr::null(),
@@ -463,7 +569,7 @@ pub fn create_shader_module(device: &ash::Device, code: Vec<u8>) -> vk::ShaderMo
     unsafe {
         device
             .create_shader_module(&shader_module_create_info, None)
-            .expect("Failed to create a Shader Module!")
+            .vk_context("Failed to create a Shader Module!")
     }
 }
 
@@ -472,8 +578,8 @@ pub fn create_buffer(
     size: vk::DeviceSize,
     usage: vk::BufferUsageFlags,
     required_memory_properties: vk::MemoryPropertyFlags,
-    device_memory_properties: &vk::PhysicalDeviceMemoryProperties,
-) -> (vk::Buffer, vk::DeviceMemory) {
+    allocator: &mut MemoryAllocator,
+) -> Result<(vk::Buffer, Allocation)> {
     let buffer_create_info = vk::BufferCreateInfo {
         s_type: vk::StructureType::BUFFER_CREATE_INFO,
         p_next: ptr::null(),
@@ -488,51 +594,68 @@ pub fn create_buffer(
     let buffer = unsafe {
         device
             .create_buffer(&buffer_create_info, None)
-            .expect("Failed to create Buffer")
+            .vk_context("Failed to create Buffer")?
     };
 
     let mem_requirements = unsafe { device.get_buffer_memory_requirements(buffer) };
-    let memory_type = find_memory_type(
-        mem_requirements.memory_type_bits,
+    let buffer_memory = match allocator.allocate(
+        device,
+        mem_requirements,
         required_memory_properties,
-        device_memory_properties,
-    );
-
-    let allocate_info = vk::MemoryAllocateInfo {
-        s_type: vk::StructureType::MEMORY_ALLOCATE_INFO,
-        p_next: ptr::null(),
-        allocation_size: mem_requirements.size,
-        memory_type_index: memory_type,
+        ResourceKind::Buffer,
+    ) {
+        Ok(buffer_memory) => buffer_memory,
+        Err(err) => {
+            unsafe {
+                device.destroy_buffer(buffer, None);
+            }
+            return Err(err);
+        }
     };
 
-    let buffer_memory = unsafe {
+    let bind_result = unsafe {
         device
-            .allocate_memory(&allocate_info, None)
-            .expect("Failed to allocate buffer memory")
+            .bind_buffer_memory(buffer, buffer_memory.memory(), buffer_memory.offset())
+            .vk_context("Failed to bind Buffer")
     };
-
-    unsafe {
-        device
-            .bind_buffer_memory(buffer, buffer_memory, 0)
-            .expect("Failed to bind Buffer");
+    if let Err(err) = bind_result {
+        unsafe {
+            device.destroy_buffer(buffer, None);
+        }
+        allocator.free(device, buffer_memory);
+        return Err(err);
     }
 
-    (buffer, buffer_memory)
+    Ok((buffer, buffer_memory))
 }
 
 pub fn find_memory_type(
     type_filter: u32,
     required_properties: vk::MemoryPropertyFlags,
     mem_properties: &vk::PhysicalDeviceMemoryProperties,
-) -> u32 {
-    for (i, memory_type) in mem_properties.memory_types.iter().enumerate() {
-        if (type_filter & (1 << i)) > 0 && memory_type.property_flags.contains(required_properties)
-        {
-            return i as u32;
-        }
-    }
+) -> Result<u32> {
+    memory_type_candidates(type_filter, required_properties, mem_properties)
+        .next()
+        .ok_or(Error::NoSuitableMemoryType {
+            type_filter,
+            required_properties,
+        })
+}
 
-    panic!("Failed to find suitable memory type!")
+/// Indices of the memory types in `type_filter` that have `required_properties`, in the
+/// order the driver lists them, which puts the faster types of a heap first.
+pub fn memory_type_candidates(
+    type_filter: u32,
+    required_properties: vk::MemoryPropertyFlags,
+    mem_properties: &vk::PhysicalDeviceMemoryProperties,
+) -> impl Iterator<Item = u32> + '_ {
+    mem_properties.memory_types[..mem_properties.memory_type_count as usize]
+        .iter()
+        .enumerate()
+        .filter(move |&(i, memory_type)| {
+            (type_filter & (1 << i)) > 0 && memory_type.property_flags.contains(required_properties)
+        })
+        .map(|(i, _)| i as u32)
 }
 
 pub fn copy_buffer(
@@ -542,8 +665,8 @@ pub fn copy_buffer(
     src_buffer: vk::Buffer,
     dst_buffer: vk::Buffer,
     size: vk::DeviceSize,
-) {
-    let command_buffer = begin_single_time_command(device, command_pool);
+) -> Result<()> {
+    let command_buffer = begin_single_time_command(device, command_pool)?;
 
     let copy_regions = [vk::BufferCopy {
         src_offset: 0,
@@ -555,13 +678,13 @@ pub fn copy_buffer(
         device.cmd_copy_buffer(command_buffer, src_buffer, dst_buffer, &copy_regions);
     }
 
-    end_single_time_command(device, command_pool, submit_queue, command_buffer);
+    end_single_time_command(device, command_pool, submit_queue, command_buffer)
 }
 
 pub fn begin_single_time_command(
     device: &ash::Device,
     command_pool: vk::CommandPool,
-) -> vk::CommandBuffer {
+) -> Result<vk::CommandBuffer> {
     let command_buffer_allocate_info = vk::CommandBufferAllocateInfo {
         s_type: vk::StructureType::COMMAND_BUFFER_ALLOCATE_INFO,
         p_next: ptr::null(),
@@ -573,7 +696,7 @@ pub fn begin_single_time_command(
     let command_buffer = unsafe {
         device
             .allocate_command_buffers(&command_buffer_allocate_info)
-            .expect("Failed to allocate Command Buffer")
+            .vk_context("Failed to allocate Command Buffer")?
     }[0];
 
     let command_buffer_begin_info = vk::CommandBufferBeginInfo {
@@ -586,10 +709,10 @@ pub fn begin_single_time_command(
     unsafe {
         device
             .begin_command_buffer(command_buffer, &command_buffer_begin_info)
-            .expect("Failed to begin Command Buffer");
+            .vk_context("Failed to begin Command Buffer")?;
     }
 
-    command_buffer
+    Ok(command_buffer)
 }
 
 pub fn end_single_time_command(
@@ -597,11 +720,11 @@ pub fn end_single_time_command(
     command_pool: vk::CommandPool,
     submit_queue: vk::Queue,
     command_buffer: vk::CommandBuffer,
-) {
+) -> Result<()> {
     unsafe {
         device
             .end_command_buffer(command_buffer)
-            .expect("Failed to end Command Buffer");
+            .vk_context("Failed to end Command Buffer")?;
     }
 
     let buffers_to_submit = [command_buffer];
@@ -621,31 +744,63 @@ pub fn end_single_time_command(
     unsafe {
         device
             .queue_submit(submit_queue, &submit_info, vk::Fence::null())
-            .expect("Failed to Submit Queue");
+            .vk_context("Failed to Submit Queue")?;
         device
             .queue_wait_idle(submit_queue)
-            .expect("Failed to wait Queue idle");
+            .vk_context("Failed to wait Queue idle")?;
 
         device.free_command_buffers(command_pool, &buffers_to_submit);
     }
+
+    Ok(())
+}
+
+/// Loads a glTF (`.gltf`/`.glb`) or OBJ model, chosen by the file extension.
+pub fn load_model(model_path: &Path) -> Result<Model> {
+    match model_path
+        .extension()
+        .and_then(|extension| extension.to_str())
+    {
+        Some("gltf") | Some("glb") => gltf_loader::load_gltf(model_path),
+        _ => load_obj(model_path),
+    }
 }
 
-pub fn load_model(model_path: &Path) -> (Vec<VertexV3>, Vec<u32>) {
-    let (models, _materials) =
-        tobj::load_obj(model_path, false).expect("Failed to load model object!");
+/// Loads an OBJ file, appending all of its meshes to one vertex and index buffer.
+///
+/// Missing texture coordinates and normals are filled with zeros.
+fn load_obj(model_path: &Path) -> Result<Model> {
+    let (models, materials) = tobj::load_obj(model_path, true).map_err(|source| Error::Model {
+        path: model_path.to_path_buf(),
+        source,
+    })?;
+    let model_dir = model_path.parent().unwrap_or_else(|| Path::new(""));
 
     let mut vertices = vec![];
     let mut indices = vec![];
+    let mut meshes = vec![];
 
     for m in models.iter() {
         let mesh = &m.mesh;
-
-        if mesh.texcoords.len() == 0 {
-            panic!("Missing texture coordinate for the model")
-        }
+        let base_vertex = vertices.len() as u32;
 
         let total_vertices_count = mesh.positions.len() / 3;
         for i in 0..total_vertices_count {
+            let tex_coord = if mesh.texcoords.is_empty() {
+                [0.0, 0.0]
+            } else {
+                [mesh.texcoords[i * 2], mesh.texcoords[i * 2 + 1]]
+            };
+            let normal = if mesh.normals.is_empty() {
+                [0.0, 0.0, 0.0]
+            } else {
+                [
+                    mesh.normals[i * 3],
+                    mesh.normals[i * 3 + 1],
+                    mesh.normals[i * 3 + 2],
+                ]
+            };
+
             let vertex = VertexV3 {
                 pos: [
                     mesh.positions[i * 3],
@@ -654,13 +809,40 @@ pub fn load_model(model_path: &Path) -> (Vec<VertexV3>, Vec<u32>) {
                     1.0,
                 ],
                 color: [1.0, 1.0, 1.0, 1.0],
-                tex_coord: [mesh.texcoords[i * 2], mesh.texcoords[i * 2 + 1]],
+                tex_coord,
+                normal,
+                tangent: [0.0, 0.0, 0.0, 0.0],
             };
             vertices.push(vertex);
         }
 
-        indices = mesh.indices.clone();
+        meshes.push(MeshRange {
+            first_index: indices.len() as u32,
+            index_count: mesh.indices.len() as u32,
+            material_index: mesh.material_id,
+        });
+        indices.extend(mesh.indices.iter().map(|index| base_vertex + index));
     }
 
-    (vertices, indices)
+    let materials = materials
+        .into_iter()
+        .map(|material| Material {
+            diffuse_texture: if material.diffuse_texture.is_empty() {
+                None
+            } else {
+                Some(TextureSource::Path(
+                    model_dir.join(&material.diffuse_texture),
+                ))
+            },
+            name: material.name,
+            diffuse_color: material.diffuse,
+        })
+        .collect();
+
+    Ok(Model {
+        vertices,
+        indices,
+        meshes,
+        materials,
+    })
 }
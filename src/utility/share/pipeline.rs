@@ -1,22 +1,28 @@
+use crate::error::{Error, Result, VkResultExt};
 use crate::utility::{
-    share,
-    structures::{QueueFamilyIndices, SyncObjects, UniformBufferObject},
-    tools,
+    allocator::{Allocation, MemoryAllocator, ResourceKind},
+    debug::DebugMarker,
+    shadertoy::CHANNEL_COUNT,
+    share::{
+        self,
+        pipeline_builder::{self, GraphicsPipelineBuilder},
+    },
+    structures::{DrawCommand, DrawItem, MeshPushConstants, QueueFamilyIndices, SyncObjects},
+    vertex::Vertex,
 };
 
 use ash::version::{DeviceV1_0, InstanceV1_0};
 use ash::vk;
 use image::GenericImageView;
 use std::cmp::max;
-use std::ffi::CString;
 use std::path::Path;
 use std::ptr;
 
 pub fn create_image_views(
     device: &ash::Device,
     surface_format: vk::Format,
-    images: &Vec<vk::Image>,
-) -> Vec<vk::ImageView> {
+    images: &[vk::Image],
+) -> Result<Vec<vk::ImageView>> {
     images
         .iter()
         .map(|&image| {
@@ -35,7 +41,7 @@ pub fn create_texture_image_view(
     device: &ash::Device,
     texture_image: vk::Image,
     mip_levels: u32,
-) -> vk::ImageView {
+) -> Result<vk::ImageView> {
     create_image_view(
         device,
         texture_image,
@@ -51,7 +57,7 @@ pub fn create_image_view(
     format: vk::Format,
     aspect_flags: vk::ImageAspectFlags,
     mip_levels: u32,
-) -> vk::ImageView {
+) -> Result<vk::ImageView> {
     let imageview_create_info = vk::ImageViewCreateInfo {
         s_type: vk::StructureType::IMAGE_VIEW_CREATE_INFO,
         p_next: ptr::null(),
@@ -77,11 +83,11 @@ pub fn create_image_view(
     unsafe {
         device
             .create_image_view(&imageview_create_info, None)
-            .expect("Failed to create Image View!")
+            .vk_context("Failed to create Image View!")
     }
 }
 
-pub fn create_texture_sampler(device: &ash::Device, mip_levels: u32) -> vk::Sampler {
+pub fn create_texture_sampler(device: &ash::Device, mip_levels: u32) -> Result<vk::Sampler> {
     let sampler_create_info = vk::SamplerCreateInfo {
         s_type: vk::StructureType::SAMPLER_CREATE_INFO,
         p_next: ptr::null(),
@@ -106,7 +112,7 @@ pub fn create_texture_sampler(device: &ash::Device, mip_levels: u32) -> vk::Samp
     unsafe {
         device
             .create_sampler(&sampler_create_info, None)
-            .expect("Failed to create Sampler!")
+            .vk_context("Failed to create Sampler!")
     }
 }
 
@@ -114,56 +120,106 @@ pub fn create_texture_image(
     device: &ash::Device,
     command_pool: vk::CommandPool,
     submit_queue: vk::Queue,
-    device_memory_properties: &vk::PhysicalDeviceMemoryProperties,
+    allocator: &mut MemoryAllocator,
     image_path: &Path,
-) -> (vk::Image, vk::DeviceMemory, u32) {
-    let mut image_object = image::open(image_path).unwrap(); // this function is slow in debug mode
+) -> Result<(vk::Image, Allocation, u32)> {
+    let mut image_object = image::open(image_path).map_err(|source| Error::Image {
+        path: image_path.to_path_buf(),
+        source,
+    })?; // this function is slow in debug mode
     image_object = image_object.flipv();
     let (image_width, image_height) = (image_object.width(), image_object.height());
     let image_size =
         (std::mem::size_of::<u8>() as u32 * image_width * image_height * 4) as vk::DeviceSize;
+    // Every 8-bit layout is converted, so gray, gray-alpha and BGR(A) images upload as RGBA.
     let image_data = match &image_object {
         image::DynamicImage::ImageLuma8(_)
+        | image::DynamicImage::ImageLumaA8(_)
         | image::DynamicImage::ImageBgr8(_)
-        | image::DynamicImage::ImageRgb8(_) => image_object.to_rgba().into_raw(),
-        image::DynamicImage::ImageLumaA8(_)
         | image::DynamicImage::ImageBgra8(_)
-        | image::DynamicImage::ImageRgba8(_) => image_object.to_bytes(),
+        | image::DynamicImage::ImageRgb8(_)
+        | image::DynamicImage::ImageRgba8(_) => image_object.to_rgba().into_raw(),
         image::DynamicImage::ImageLuma16(_)
         | image::DynamicImage::ImageLumaA16(_)
         | image::DynamicImage::ImageRgb16(_)
-        | image::DynamicImage::ImageRgba16(_) => panic!("Image object is 16-bit image"),
+        | image::DynamicImage::ImageRgba16(_) => {
+            return Err(Error::UnsupportedImage {
+                path: image_path.to_path_buf(),
+                reason: "Image object is 16-bit image",
+            })
+        }
     };
+
+    if image_size == 0 {
+        return Err(Error::UnsupportedImage {
+            path: image_path.to_path_buf(),
+            reason: "Image is empty",
+        });
+    }
+
+    create_texture_image_from_pixels(
+        device,
+        command_pool,
+        submit_queue,
+        allocator,
+        image_width,
+        image_height,
+        &image_data,
+    )
+}
+
+/// Creates a 1x1 opaque white texture, bound when no texture is loaded so that shaders
+/// sampling it see the vertex color unchanged.
+pub fn create_white_texture_image(
+    device: &ash::Device,
+    command_pool: vk::CommandPool,
+    submit_queue: vk::Queue,
+    allocator: &mut MemoryAllocator,
+) -> Result<(vk::Image, Allocation, u32)> {
+    create_texture_image_from_pixels(
+        device,
+        command_pool,
+        submit_queue,
+        allocator,
+        1,
+        1,
+        &[255, 255, 255, 255],
+    )
+}
+
+/// Uploads tightly packed RGBA8 pixels into a sampled image with a full mip chain.
+pub fn create_texture_image_from_pixels(
+    device: &ash::Device,
+    command_pool: vk::CommandPool,
+    submit_queue: vk::Queue,
+    allocator: &mut MemoryAllocator,
+    image_width: u32,
+    image_height: u32,
+    image_data: &[u8],
+) -> Result<(vk::Image, Allocation, u32)> {
+    assert_eq!(
+        image_data.len(),
+        (image_width * image_height * 4) as usize,
+        "Texture pixels have to be tightly packed RGBA8"
+    );
+    let image_size = image_data.len() as vk::DeviceSize;
     let mip_levels = ((::std::cmp::max(image_width, image_height) as f32)
         .log2()
         .floor() as u32)
         + 1;
 
-    if image_size <= 0 {
-        panic!("Failed to load texture image!")
-    }
-
     let (staging_buffer, staging_buffer_memory) = share::create_buffer(
         device,
         image_size,
         vk::BufferUsageFlags::TRANSFER_SRC,
         vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
-        device_memory_properties,
-    );
+        allocator,
+    )?;
 
     unsafe {
-        let data_ptr = device
-            .map_memory(
-                staging_buffer_memory,
-                0,
-                image_size,
-                vk::MemoryMapFlags::empty(),
-            )
-            .expect("Failed to map memory") as *mut u8;
+        let data_ptr = staging_buffer_memory.mapped_ptr()? as *mut u8;
 
         data_ptr.copy_from_nonoverlapping(image_data.as_ptr(), image_data.len());
-
-        device.unmap_memory(staging_buffer_memory);
     }
 
     let (texture_image, texture_image_memory) = create_image(
@@ -178,8 +234,8 @@ pub fn create_texture_image(
             | vk::ImageUsageFlags::TRANSFER_DST
             | vk::ImageUsageFlags::SAMPLED,
         vk::MemoryPropertyFlags::DEVICE_LOCAL,
-        device_memory_properties,
-    );
+        allocator,
+    )?;
 
     transition_image_layout(
         device,
@@ -190,7 +246,7 @@ pub fn create_texture_image(
         vk::ImageLayout::UNDEFINED,
         vk::ImageLayout::TRANSFER_DST_OPTIMAL,
         mip_levels,
-    );
+    )?;
 
     copy_buffer_to_image(
         device,
@@ -200,7 +256,7 @@ pub fn create_texture_image(
         texture_image,
         image_width,
         image_height,
-    );
+    )?;
 
     generate_mipmaps(
         device,
@@ -210,14 +266,14 @@ pub fn create_texture_image(
         image_width,
         image_height,
         mip_levels,
-    );
+    )?;
 
     unsafe {
         device.destroy_buffer(staging_buffer, None);
-        device.free_memory(staging_buffer_memory, None);
+        allocator.free(device, staging_buffer_memory);
     }
 
-    (texture_image, texture_image_memory, mip_levels)
+    Ok((texture_image, texture_image_memory, mip_levels))
 }
 
 fn generate_mipmaps(
@@ -228,8 +284,8 @@ fn generate_mipmaps(
     tex_width: u32,
     tex_height: u32,
     mip_levels: u32,
-) {
-    let command_buffer = share::begin_single_time_command(device, command_pool);
+) -> Result<()> {
+    let command_buffer = share::begin_single_time_command(device, command_pool)?;
 
     let mut image_barrier = vk::ImageMemoryBarrier {
         s_type: vk::StructureType::IMAGE_MEMORY_BARRIER,
@@ -268,7 +324,7 @@ fn generate_mipmaps(
                 vk::DependencyFlags::empty(),
                 &[],
                 &[],
-                &[image_barrier.clone()],
+                &[image_barrier],
             );
         }
 
@@ -328,7 +384,7 @@ fn generate_mipmaps(
                 vk::DependencyFlags::empty(),
                 &[],
                 &[],
-                &[image_barrier.clone()],
+                &[image_barrier],
             );
         }
 
@@ -350,11 +406,11 @@ fn generate_mipmaps(
             vk::DependencyFlags::empty(),
             &[],
             &[],
-            &[image_barrier.clone()],
+            &[image_barrier],
         );
     }
 
-    share::end_single_time_command(device, command_pool, submit_queue, command_buffer);
+    share::end_single_time_command(device, command_pool, submit_queue, command_buffer)
 }
 
 pub fn create_image(
@@ -367,8 +423,8 @@ pub fn create_image(
     tiling: vk::ImageTiling,
     usage: vk::ImageUsageFlags,
     required_memory_properties: vk::MemoryPropertyFlags,
-    device_memory_properties: &vk::PhysicalDeviceMemoryProperties,
-) -> (vk::Image, vk::DeviceMemory) {
+    allocator: &mut MemoryAllocator,
+) -> Result<(vk::Image, Allocation)> {
     let image_create_info = vk::ImageCreateInfo {
         s_type: vk::StructureType::IMAGE_CREATE_INFO,
         p_next: ptr::null(),
@@ -394,34 +450,47 @@ pub fn create_image(
     let texture_image = unsafe {
         device
             .create_image(&image_create_info, None)
-            .expect("Failed to create Texture Image")
+            .vk_context("Failed to create Texture Image")?
     };
 
     let image_memory_requirement = unsafe { device.get_image_memory_requirements(texture_image) };
-    let memory_allocate_info = vk::MemoryAllocateInfo {
-        s_type: vk::StructureType::MEMORY_ALLOCATE_INFO,
-        p_next: ptr::null(),
-        allocation_size: image_memory_requirement.size,
-        memory_type_index: share::find_memory_type(
-            image_memory_requirement.memory_type_bits,
-            required_memory_properties,
-            device_memory_properties,
-        ),
+    let texture_image_memory = match allocator.allocate(
+        device,
+        image_memory_requirement,
+        required_memory_properties,
+        if tiling == vk::ImageTiling::LINEAR {
+            ResourceKind::LinearImage
+        } else {
+            ResourceKind::OptimalImage
+        },
+    ) {
+        Ok(texture_image_memory) => texture_image_memory,
+        Err(err) => {
+            unsafe {
+                device.destroy_image(texture_image, None);
+            }
+            return Err(err);
+        }
     };
 
-    let texture_image_memory = unsafe {
+    let bind_result = unsafe {
         device
-            .allocate_memory(&memory_allocate_info, None)
-            .expect("Failed to allocate Texture Image memory!")
+            .bind_image_memory(
+                texture_image,
+                texture_image_memory.memory(),
+                texture_image_memory.offset(),
+            )
+            .vk_context("Failed to bind Image Memory")
     };
-
-    unsafe {
-        device
-            .bind_image_memory(texture_image, texture_image_memory, 0)
-            .expect("Failed to bind Image Memory");
+    if let Err(err) = bind_result {
+        unsafe {
+            device.destroy_image(texture_image, None);
+        }
+        allocator.free(device, texture_image_memory);
+        return Err(err);
     }
 
-    (texture_image, texture_image_memory)
+    Ok((texture_image, texture_image_memory))
 }
 
 fn transition_image_layout(
@@ -433,8 +502,8 @@ fn transition_image_layout(
     old_layout: vk::ImageLayout,
     new_layout: vk::ImageLayout,
     mip_levels: u32,
-) {
-    let command_buffer = share::begin_single_time_command(device, command_pool);
+) -> Result<()> {
+    let command_buffer = share::begin_single_time_command(device, command_pool)?;
 
     let src_access_mask;
     let dst_access_mask;
@@ -456,7 +525,10 @@ fn transition_image_layout(
         source_stage = vk::PipelineStageFlags::TRANSFER;
         destination_stage = vk::PipelineStageFlags::FRAGMENT_SHADER;
     } else {
-        panic!("Unsupported layout transition")
+        return Err(Error::UnsupportedLayoutTransition {
+            old_layout,
+            new_layout,
+        });
     }
 
     let image_barriers = [vk::ImageMemoryBarrier {
@@ -490,7 +562,7 @@ fn transition_image_layout(
         );
     }
 
-    share::end_single_time_command(device, command_pool, submit_queue, command_buffer);
+    share::end_single_time_command(device, command_pool, submit_queue, command_buffer)
 }
 
 fn copy_buffer_to_image(
@@ -501,8 +573,8 @@ fn copy_buffer_to_image(
     image: vk::Image,
     width: u32,
     height: u32,
-) {
-    let command_buffer = share::begin_single_time_command(device, command_pool);
+) -> Result<()> {
+    let command_buffer = share::begin_single_time_command(device, command_pool)?;
 
     let buffer_image_regions = [vk::BufferImageCopy {
         image_subresource: vk::ImageSubresourceLayers {
@@ -532,31 +604,49 @@ fn copy_buffer_to_image(
         );
     }
 
-    share::end_single_time_command(device, command_pool, submit_queue, command_buffer);
+    share::end_single_time_command(device, command_pool, submit_queue, command_buffer)
 }
 
+/// `color_final_layout` is `PRESENT_SRC_KHR` for swapchain images and `TRANSFER_SRC_OPTIMAL`
+/// for offscreen images that get read back afterwards.
+///
+/// With more than one sample, the color and depth attachments are multisampled and the color
+/// is resolved into a third attachment, which gets `color_final_layout`; see
+/// `create_framebuffers`.
 pub fn create_render_pass(
     instance: &ash::Instance,
     device: &ash::Device,
     physical_device: vk::PhysicalDevice,
     surface_format: vk::Format,
-) -> vk::RenderPass {
+    color_final_layout: vk::ImageLayout,
+    msaa_samples: vk::SampleCountFlags,
+) -> Result<vk::RenderPass> {
+    let is_multisampled = msaa_samples != vk::SampleCountFlags::TYPE_1;
     let color_attachment = vk::AttachmentDescription {
         flags: vk::AttachmentDescriptionFlags::empty(),
         format: surface_format,
-        samples: vk::SampleCountFlags::TYPE_1,
+        samples: msaa_samples,
         load_op: vk::AttachmentLoadOp::CLEAR,
-        store_op: vk::AttachmentStoreOp::STORE,
+        // Only the resolved image is kept when multisampling.
+        store_op: if is_multisampled {
+            vk::AttachmentStoreOp::DONT_CARE
+        } else {
+            vk::AttachmentStoreOp::STORE
+        },
         stencil_load_op: vk::AttachmentLoadOp::DONT_CARE,
         stencil_store_op: vk::AttachmentStoreOp::DONT_CARE,
         initial_layout: vk::ImageLayout::UNDEFINED,
-        final_layout: vk::ImageLayout::PRESENT_SRC_KHR,
+        final_layout: if is_multisampled {
+            vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL
+        } else {
+            color_final_layout
+        },
     };
 
     let depth_attachment = vk::AttachmentDescription {
         flags: vk::AttachmentDescriptionFlags::empty(),
-        format: find_depth_format(instance, physical_device),
-        samples: vk::SampleCountFlags::TYPE_1,
+        format: find_depth_format(instance, physical_device)?,
+        samples: msaa_samples,
         load_op: vk::AttachmentLoadOp::CLEAR,
         store_op: vk::AttachmentStoreOp::DONT_CARE,
         stencil_load_op: vk::AttachmentLoadOp::DONT_CARE,
@@ -573,6 +663,10 @@ pub fn create_render_pass(
         attachment: 1,
         layout: vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL,
     };
+    let resolve_attachment_ref = vk::AttachmentReference {
+        attachment: 2,
+        layout: vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+    };
     let subpasses = [vk::SubpassDescription {
         flags: vk::SubpassDescriptionFlags::empty(),
         pipeline_bind_point: vk::PipelineBindPoint::GRAPHICS,
@@ -580,13 +674,30 @@ pub fn create_render_pass(
         p_input_attachments: ptr::null(),
         color_attachment_count: 1,
         p_color_attachments: &color_attachment_ref,
-        p_resolve_attachments: ptr::null(),
+        p_resolve_attachments: if is_multisampled {
+            &resolve_attachment_ref
+        } else {
+            ptr::null()
+        },
         p_depth_stencil_attachment: &depth_attachment_ref,
         preserve_attachment_count: 0,
         p_preserve_attachments: ptr::null(),
     }];
 
-    let render_pass_attachments = [color_attachment, depth_attachment];
+    let mut render_pass_attachments = vec![color_attachment, depth_attachment];
+    if is_multisampled {
+        render_pass_attachments.push(vk::AttachmentDescription {
+            flags: vk::AttachmentDescriptionFlags::empty(),
+            format: surface_format,
+            samples: vk::SampleCountFlags::TYPE_1,
+            load_op: vk::AttachmentLoadOp::DONT_CARE,
+            store_op: vk::AttachmentStoreOp::STORE,
+            stencil_load_op: vk::AttachmentLoadOp::DONT_CARE,
+            stencil_store_op: vk::AttachmentStoreOp::DONT_CARE,
+            initial_layout: vk::ImageLayout::UNDEFINED,
+            final_layout: color_final_layout,
+        });
+    }
 
     let subpass_dependencies = [vk::SubpassDependency {
         src_subpass: vk::SUBPASS_EXTERNAL,
@@ -614,14 +725,92 @@ pub fn create_render_pass(
     unsafe {
         device
             .create_render_pass(&renderpass_create_info, None)
-            .expect("Failed to create render pass!")
+            .vk_context("Failed to create render pass!")
+    }
+}
+
+/// Color-only render pass for offscreen targets that later passes sample from. The image
+/// ends up in `SHADER_READ_ONLY_OPTIMAL`, and the dependencies order the write against
+/// fragment shader reads both before and after the pass.
+pub fn create_offscreen_render_pass(
+    device: &ash::Device,
+    color_format: vk::Format,
+) -> Result<vk::RenderPass> {
+    let color_attachment = vk::AttachmentDescription {
+        flags: vk::AttachmentDescriptionFlags::empty(),
+        format: color_format,
+        samples: vk::SampleCountFlags::TYPE_1,
+        load_op: vk::AttachmentLoadOp::DONT_CARE,
+        store_op: vk::AttachmentStoreOp::STORE,
+        stencil_load_op: vk::AttachmentLoadOp::DONT_CARE,
+        stencil_store_op: vk::AttachmentStoreOp::DONT_CARE,
+        initial_layout: vk::ImageLayout::UNDEFINED,
+        final_layout: vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+    };
+
+    let color_attachment_ref = vk::AttachmentReference {
+        attachment: 0,
+        layout: vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+    };
+    let subpasses = [vk::SubpassDescription {
+        flags: vk::SubpassDescriptionFlags::empty(),
+        pipeline_bind_point: vk::PipelineBindPoint::GRAPHICS,
+        input_attachment_count: 0,
+        p_input_attachments: ptr::null(),
+        color_attachment_count: 1,
+        p_color_attachments: &color_attachment_ref,
+        p_resolve_attachments: ptr::null(),
+        p_depth_stencil_attachment: ptr::null(),
+        preserve_attachment_count: 0,
+        p_preserve_attachments: ptr::null(),
+    }];
+
+    let render_pass_attachments = [color_attachment];
+
+    let subpass_dependencies = [
+        vk::SubpassDependency {
+            src_subpass: vk::SUBPASS_EXTERNAL,
+            dst_subpass: 0,
+            src_stage_mask: vk::PipelineStageFlags::FRAGMENT_SHADER,
+            dst_stage_mask: vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+            src_access_mask: vk::AccessFlags::SHADER_READ,
+            dst_access_mask: vk::AccessFlags::COLOR_ATTACHMENT_WRITE,
+            dependency_flags: vk::DependencyFlags::empty(),
+        },
+        vk::SubpassDependency {
+            src_subpass: 0,
+            dst_subpass: vk::SUBPASS_EXTERNAL,
+            src_stage_mask: vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+            dst_stage_mask: vk::PipelineStageFlags::FRAGMENT_SHADER,
+            src_access_mask: vk::AccessFlags::COLOR_ATTACHMENT_WRITE,
+            dst_access_mask: vk::AccessFlags::SHADER_READ,
+            dependency_flags: vk::DependencyFlags::empty(),
+        },
+    ];
+
+    let renderpass_create_info = vk::RenderPassCreateInfo {
+        s_type: vk::StructureType::RENDER_PASS_CREATE_INFO,
+        flags: vk::RenderPassCreateFlags::empty(),
+        p_next: ptr::null(),
+        attachment_count: render_pass_attachments.len() as u32,
+        p_attachments: render_pass_attachments.as_ptr(),
+        subpass_count: subpasses.len() as u32,
+        p_subpasses: subpasses.as_ptr(),
+        dependency_count: subpass_dependencies.len() as u32,
+        p_dependencies: subpass_dependencies.as_ptr(),
+    };
+
+    unsafe {
+        device
+            .create_render_pass(&renderpass_create_info, None)
+            .vk_context("Failed to create render pass!")
     }
 }
 
 pub fn find_depth_format(
     instance: &ash::Instance,
     physical_device: vk::PhysicalDevice,
-) -> vk::Format {
+) -> Result<vk::Format> {
     find_supported_format(
         instance,
         physical_device,
@@ -641,254 +830,111 @@ fn find_supported_format(
     candidate_formats: &[vk::Format],
     tiling: vk::ImageTiling,
     features: vk::FormatFeatureFlags,
-) -> vk::Format {
+) -> Result<vk::Format> {
     for &format in candidate_formats.iter() {
         let format_properties =
             unsafe { instance.get_physical_device_format_properties(physical_device, format) };
-        if tiling == vk::ImageTiling::LINEAR
-            && format_properties.linear_tiling_features.contains(features)
-        {
-            return format.clone();
-        } else if tiling == vk::ImageTiling::OPTIMAL
-            && format_properties.optimal_tiling_features.contains(features)
-        {
-            return format.clone();
+        let is_supported = (tiling == vk::ImageTiling::LINEAR
+            && format_properties.linear_tiling_features.contains(features))
+            || (tiling == vk::ImageTiling::OPTIMAL
+                && format_properties.optimal_tiling_features.contains(features));
+        if is_supported {
+            return Ok(format);
         }
     }
 
-    panic!("Failed to find supported format!")
+    Err(Error::UnsupportedFormat {
+        candidates: candidate_formats.to_vec(),
+        features,
+    })
 }
 
-pub fn create_graphics_pipeline(
+/// Creates a pipeline reading vertices of type `V`, or no vertex input for `()`, with the
+/// `GraphicsPipelineBuilder` defaults, including dynamic viewport and scissor.
+///
+/// `front_face` is `COUNTER_CLOCKWISE` for meshes drawn through `Camera::projection`,
+/// whose Y flip mirrors the winding of their triangles.
+pub fn create_graphics_pipeline<V: Vertex>(
     device: &ash::Device,
     render_pass: vk::RenderPass,
-    swapchain_extent: vk::Extent2D,
+    pipeline_cache: vk::PipelineCache,
     ubo_set_layout: vk::DescriptorSetLayout,
-    vertex_binding_description: &[vk::VertexInputBindingDescription],
-    vertex_attribute_description: &[vk::VertexInputAttributeDescription],
-) -> (vk::Pipeline, vk::PipelineLayout) {
-    let vert_shader_code = tools::read_shader_code(Path::new("shaders/vert.spv"));
-    let frag_shader_code = tools::read_shader_code(Path::new("shaders/frag.spv"));
-
-    let vert_shader_module = share::create_shader_module(device, vert_shader_code);
-    let frag_shader_module = share::create_shader_module(device, frag_shader_code);
-
-    let main_function_name = CString::new("main").unwrap();
-
-    // -#- Programmable pipeline -#-
-    let shader_stages = [
-        // Vertex Shader
-        vk::PipelineShaderStageCreateInfo {
-            s_type: vk::StructureType::PIPELINE_SHADER_STAGE_CREATE_INFO,
-            p_next: ptr::null(),
-            flags: vk::PipelineShaderStageCreateFlags::empty(),
-            module: vert_shader_module,
-            p_name: main_function_name.as_ptr(),
-            p_specialization_info: ptr::null(),
-            stage: vk::ShaderStageFlags::VERTEX,
-        },
-        // Fragment Shader
-        vk::PipelineShaderStageCreateInfo {
-            s_type: vk::StructureType::PIPELINE_SHADER_STAGE_CREATE_INFO,
-            p_next: ptr::null(),
-            flags: vk::PipelineShaderStageCreateFlags::empty(),
-            module: frag_shader_module,
-            p_name: main_function_name.as_ptr(),
-            p_specialization_info: ptr::null(),
-            stage: vk::ShaderStageFlags::FRAGMENT,
-        },
-    ];
-
-    // -#- Fixed pipeline -#-
-    // * Vertex input
-    let vertex_input_state_create_info = vk::PipelineVertexInputStateCreateInfo {
-        s_type: vk::StructureType::PIPELINE_VERTEX_INPUT_STATE_CREATE_INFO,
-        p_next: ptr::null(),
-        flags: vk::PipelineVertexInputStateCreateFlags::empty(),
-        vertex_attribute_description_count: vertex_attribute_description.len() as u32,
-        p_vertex_attribute_descriptions: vertex_attribute_description.as_ptr(),
-        vertex_binding_description_count: vertex_binding_description.len() as u32,
-        p_vertex_binding_descriptions: vertex_binding_description.as_ptr(),
-    };
-    // * Input assembly
-    let vertex_input_assembly_state_info = vk::PipelineInputAssemblyStateCreateInfo {
-        s_type: vk::StructureType::PIPELINE_INPUT_ASSEMBLY_STATE_CREATE_INFO,
-        flags: vk::PipelineInputAssemblyStateCreateFlags::empty(),
-        p_next: ptr::null(),
-        primitive_restart_enable: vk::FALSE,
-        topology: vk::PrimitiveTopology::TRIANGLE_LIST,
-    };
-
-    // * Viewport and scissors
-    let viewports = [vk::Viewport {
-        x: 0.0,
-        y: 0.0,
-        width: swapchain_extent.width as f32,
-        height: swapchain_extent.height as f32,
-        min_depth: 0.0,
-        max_depth: 1.0,
-    }];
-    let scissors = [vk::Rect2D {
-        offset: vk::Offset2D { x: 0, y: 0 },
-        extent: swapchain_extent,
-    }];
-    let viewport_state_create_info = vk::PipelineViewportStateCreateInfo {
-        s_type: vk::StructureType::PIPELINE_VIEWPORT_STATE_CREATE_INFO,
-        p_next: ptr::null(),
-        flags: vk::PipelineViewportStateCreateFlags::empty(),
-        scissor_count: scissors.len() as u32,
-        p_scissors: scissors.as_ptr(),
-        viewport_count: viewports.len() as u32,
-        p_viewports: viewports.as_ptr(),
-    };
-
-    // * Rasterizer
-    let rasterization_statue_create_info = vk::PipelineRasterizationStateCreateInfo {
-        s_type: vk::StructureType::PIPELINE_RASTERIZATION_STATE_CREATE_INFO,
-        p_next: ptr::null(),
-        flags: vk::PipelineRasterizationStateCreateFlags::empty(),
-        depth_clamp_enable: vk::FALSE,
-        cull_mode: vk::CullModeFlags::BACK,
-        front_face: vk::FrontFace::CLOCKWISE,
-        line_width: 1.0,
-        polygon_mode: vk::PolygonMode::FILL,
-        rasterizer_discard_enable: vk::FALSE,
-        depth_bias_clamp: 0.0,
-        depth_bias_constant_factor: 0.0,
-        depth_bias_enable: vk::FALSE,
-        depth_bias_slope_factor: 0.0,
-    };
-    // * Multisampling
-    let multisample_state_create_info = vk::PipelineMultisampleStateCreateInfo {
-        s_type: vk::StructureType::PIPELINE_MULTISAMPLE_STATE_CREATE_INFO,
-        flags: vk::PipelineMultisampleStateCreateFlags::empty(),
-        p_next: ptr::null(),
-        rasterization_samples: vk::SampleCountFlags::TYPE_1,
-        sample_shading_enable: vk::FALSE,
-        min_sample_shading: 0.0,
-        p_sample_mask: ptr::null(),
-        alpha_to_one_enable: vk::FALSE,
-        alpha_to_coverage_enable: vk::FALSE,
-    };
-
-    // * Depth/stencil test
-    let stencil_state = vk::StencilOpState {
-        fail_op: vk::StencilOp::KEEP,
-        pass_op: vk::StencilOp::KEEP,
-        depth_fail_op: vk::StencilOp::KEEP,
-        compare_op: vk::CompareOp::ALWAYS,
-        compare_mask: 0,
-        write_mask: 0,
-        reference: 0,
-    };
-    let depth_state_create_info = vk::PipelineDepthStencilStateCreateInfo {
-        s_type: vk::StructureType::PIPELINE_DEPTH_STENCIL_STATE_CREATE_INFO,
-        p_next: ptr::null(),
-        flags: vk::PipelineDepthStencilStateCreateFlags::empty(),
-        depth_test_enable: vk::TRUE,
-        depth_write_enable: vk::TRUE,
-        depth_compare_op: vk::CompareOp::LESS,
-        depth_bounds_test_enable: vk::FALSE,
-        stencil_test_enable: vk::FALSE,
-        front: stencil_state,
-        back: stencil_state,
-        max_depth_bounds: 1.0,
-        min_depth_bounds: 0.0,
-    };
-
-    // * Color blending
-    let color_blend_attachment_states = [vk::PipelineColorBlendAttachmentState {
-        blend_enable: vk::FALSE,
-        color_write_mask: vk::ColorComponentFlags::all(),
-        src_color_blend_factor: vk::BlendFactor::ONE,
-        dst_color_blend_factor: vk::BlendFactor::ZERO,
-        color_blend_op: vk::BlendOp::ADD,
-        src_alpha_blend_factor: vk::BlendFactor::ONE,
-        dst_alpha_blend_factor: vk::BlendFactor::ZERO,
-        alpha_blend_op: vk::BlendOp::ADD,
-    }];
-    let color_blend_state = vk::PipelineColorBlendStateCreateInfo {
-        s_type: vk::StructureType::PIPELINE_COLOR_BLEND_STATE_CREATE_INFO,
-        p_next: ptr::null(),
-        flags: vk::PipelineColorBlendStateCreateFlags::empty(),
-        logic_op_enable: vk::FALSE,
-        logic_op: vk::LogicOp::COPY,
-        attachment_count: color_blend_attachment_states.len() as u32,
-        p_attachments: color_blend_attachment_states.as_ptr(),
-        blend_constants: [0.0, 0.0, 0.0, 0.0],
-    };
-
-    // * Dynamic state
-    // nothing here
-
-    let set_layouts = [ubo_set_layout];
-
-    let pipeline_layout_create_info = vk::PipelineLayoutCreateInfo {
-        s_type: vk::StructureType::PIPELINE_LAYOUT_CREATE_INFO,
-        p_next: ptr::null(),
-        flags: vk::PipelineLayoutCreateFlags::empty(),
-        set_layout_count: set_layouts.len() as u32,
-        p_set_layouts: set_layouts.as_ptr(),
-        push_constant_range_count: 0,
-        p_push_constant_ranges: ptr::null(),
-    };
-    let pipeline_layout = unsafe {
-        device
-            .create_pipeline_layout(&pipeline_layout_create_info, None)
-            .expect("Failed to create pipeline layout!")
-    };
+    front_face: vk::FrontFace,
+    vert_shader_path: &Path,
+    frag_shader_path: &Path,
+) -> Result<(vk::Pipeline, vk::PipelineLayout)> {
+    GraphicsPipelineBuilder::new()
+        .shader(vk::ShaderStageFlags::VERTEX, vert_shader_path)
+        .shader(vk::ShaderStageFlags::FRAGMENT, frag_shader_path)
+        .vertex_input::<V>()
+        .front_face(front_face)
+        .descriptor_set_layout(ubo_set_layout)
+        .pipeline_cache(pipeline_cache)
+        .build(device, render_pass)
+}
 
-    let graphic_pipeline_create_infos = [vk::GraphicsPipelineCreateInfo {
-        s_type: vk::StructureType::GRAPHICS_PIPELINE_CREATE_INFO,
-        p_next: ptr::null(),
-        flags: vk::PipelineCreateFlags::empty(),
-        stage_count: shader_stages.len() as u32,
-        p_stages: shader_stages.as_ptr(),
-        p_vertex_input_state: &vertex_input_state_create_info,
-        p_input_assembly_state: &vertex_input_assembly_state_info,
-        p_tessellation_state: ptr::null(),
-        p_viewport_state: &viewport_state_create_info,
-        p_rasterization_state: &rasterization_statue_create_info,
-        p_multisample_state: &multisample_state_create_info,
-        p_depth_stencil_state: &depth_state_create_info,
-        p_color_blend_state: &color_blend_state,
-        p_dynamic_state: ptr::null(),
-        layout: pipeline_layout,
-        render_pass,
-        subpass: 0,
-        base_pipeline_handle: vk::Pipeline::null(),
-        base_pipeline_index: -1,
-    }];
+/// Sets the dynamic viewport and scissor of `GraphicsPipelineBuilder` pipelines to cover
+/// all of `extent`.
+pub fn cmd_set_viewport_and_scissor(
+    device: &ash::Device,
+    command_buffer: vk::CommandBuffer,
+    extent: vk::Extent2D,
+) {
+    unsafe {
+        device.cmd_set_viewport(
+            command_buffer,
+            0,
+            &[pipeline_builder::full_viewport(extent)],
+        );
+        device.cmd_set_scissor(command_buffer, 0, &[pipeline_builder::full_scissor(extent)]);
+    }
+}
 
-    let graphics_pipelines = unsafe {
-        device
-            .create_graphics_pipelines(
-                vk::PipelineCache::null(),
-                &graphic_pipeline_create_infos,
-                None,
-            )
-            .expect("Failed to create Graphics Pipeline!")
+/// Updates the push constants in `push_constant_range` with `constants`, which has to be
+/// exactly as large as the range.
+pub fn cmd_push_constants<T: Copy>(
+    device: &ash::Device,
+    command_buffer: vk::CommandBuffer,
+    pipeline_layout: vk::PipelineLayout,
+    push_constant_range: vk::PushConstantRange,
+    constants: &T,
+) {
+    debug_assert_eq!(std::mem::size_of::<T>(), push_constant_range.size as usize);
+    let bytes = unsafe {
+        std::slice::from_raw_parts(constants as *const T as *const u8, std::mem::size_of::<T>())
     };
 
     unsafe {
-        device.destroy_shader_module(vert_shader_module, None);
-        device.destroy_shader_module(frag_shader_module, None);
+        device.cmd_push_constants(
+            command_buffer,
+            pipeline_layout,
+            push_constant_range.stage_flags,
+            push_constant_range.offset,
+            bytes,
+        );
     }
-
-    (graphics_pipelines[0], pipeline_layout)
 }
 
+/// `msaa_color_image_view` is the multisampled color attachment from `create_color_resources`
+/// if `render_pass` was created with more than one sample. `image_views` are then the resolve
+/// targets.
 pub fn create_framebuffers(
     device: &ash::Device,
     render_pass: vk::RenderPass,
-    image_views: &Vec<vk::ImageView>,
+    image_views: &[vk::ImageView],
     depth_image_view: vk::ImageView,
+    msaa_color_image_view: Option<vk::ImageView>,
     swapchain_extent: vk::Extent2D,
-) -> Vec<vk::Framebuffer> {
+) -> Result<Vec<vk::Framebuffer>> {
     let mut framebuffers = vec![];
 
     for &image_view in image_views.iter() {
-        let attachments = [image_view, depth_image_view];
+        let attachments = match msaa_color_image_view {
+            Some(msaa_color_image_view) => {
+                vec![msaa_color_image_view, depth_image_view, image_view]
+            }
+            None => vec![image_view, depth_image_view],
+        };
 
         let framebuffer_create_info = vk::FramebufferCreateInfo {
             s_type: vk::StructureType::FRAMEBUFFER_CREATE_INFO,
@@ -905,142 +951,300 @@ pub fn create_framebuffers(
         let framebuffer = unsafe {
             device
                 .create_framebuffer(&framebuffer_create_info, None)
-                .expect("Failed to create Framebuffer!")
+                .vk_context("Failed to create Framebuffer!")?
         };
 
         framebuffers.push(framebuffer);
     }
 
-    framebuffers
+    Ok(framebuffers)
+}
+
+pub fn create_offscreen_framebuffer(
+    device: &ash::Device,
+    render_pass: vk::RenderPass,
+    image_view: vk::ImageView,
+    extent: vk::Extent2D,
+) -> Result<vk::Framebuffer> {
+    let attachments = [image_view];
+
+    let framebuffer_create_info = vk::FramebufferCreateInfo {
+        s_type: vk::StructureType::FRAMEBUFFER_CREATE_INFO,
+        p_next: ptr::null(),
+        flags: vk::FramebufferCreateFlags::empty(),
+        render_pass,
+        attachment_count: attachments.len() as u32,
+        p_attachments: attachments.as_ptr(),
+        width: extent.width,
+        height: extent.height,
+        layers: 1,
+    };
+
+    unsafe {
+        device
+            .create_framebuffer(&framebuffer_create_info, None)
+            .vk_context("Failed to create Framebuffer!")
+    }
 }
 
 pub fn create_command_pool(
     device: &ash::Device,
     queue_families: &QueueFamilyIndices,
-) -> vk::CommandPool {
+) -> Result<vk::CommandPool> {
     let command_pool_create_info = vk::CommandPoolCreateInfo {
         s_type: vk::StructureType::COMMAND_POOL_CREATE_INFO,
         p_next: ptr::null(),
-        flags: vk::CommandPoolCreateFlags::empty(),
+        // Frame command buffers are reset and recorded again every frame.
+        flags: vk::CommandPoolCreateFlags::RESET_COMMAND_BUFFER,
         queue_family_index: queue_families.graphics_family.unwrap(),
     };
 
     unsafe {
         device
             .create_command_pool(&command_pool_create_info, None)
-            .expect("Failed to create Command Pool")
+            .vk_context("Failed to create Command Pool")
     }
 }
 
+/// Allocates and records one command buffer per framebuffer, drawing `draw_command` with
+/// `descriptor_sets[i]` into `framebuffers[i]`. They can be submitted any number of times.
 pub fn create_command_buffers(
     device: &ash::Device,
+    debug_marker: &DebugMarker,
     command_pool: vk::CommandPool,
     graphics_pipeline: vk::Pipeline,
-    framebuffers: &Vec<vk::Framebuffer>,
+    framebuffers: &[vk::Framebuffer],
     render_pass: vk::RenderPass,
     surface_extent: vk::Extent2D,
-    vertex_buffer: vk::Buffer,
-    index_buffer: vk::Buffer,
     pipeline_layout: vk::PipelineLayout,
-    descriptor_sets: &Vec<vk::DescriptorSet>,
-    indices_len: u32,
-) -> Vec<vk::CommandBuffer> {
+    descriptor_sets: &[vk::DescriptorSet],
+    draw_command: DrawCommand,
+) -> Result<Vec<vk::CommandBuffer>> {
+    let command_buffers = allocate_command_buffers(device, command_pool, framebuffers.len())?;
+
+    for (i, &command_buffer) in command_buffers.iter().enumerate() {
+        let draw_items = [DrawItem {
+            pipeline: graphics_pipeline,
+            pipeline_layout,
+            descriptor_sets: vec![descriptor_sets[i]],
+            command: draw_command.clone(),
+        }];
+
+        begin_command_buffer(
+            device,
+            command_buffer,
+            vk::CommandBufferUsageFlags::SIMULTANEOUS_USE,
+        )?;
+        cmd_draw_items(
+            device,
+            debug_marker,
+            command_buffer,
+            "main pass",
+            render_pass,
+            framebuffers[i],
+            surface_extent,
+            &draw_items,
+        );
+        end_command_buffer(device, command_buffer)?;
+    }
+
+    Ok(command_buffers)
+}
+
+pub fn allocate_command_buffers(
+    device: &ash::Device,
+    command_pool: vk::CommandPool,
+    count: usize,
+) -> Result<Vec<vk::CommandBuffer>> {
     let command_buffer_allocate_info = vk::CommandBufferAllocateInfo {
         s_type: vk::StructureType::COMMAND_BUFFER_ALLOCATE_INFO,
         p_next: ptr::null(),
-        command_buffer_count: framebuffers.len() as u32,
+        command_buffer_count: count as u32,
         command_pool,
         level: vk::CommandBufferLevel::PRIMARY,
     };
 
-    let command_buffers = unsafe {
+    unsafe {
         device
             .allocate_command_buffers(&command_buffer_allocate_info)
-            .expect("Failed to allocate Command Buffers")
+            .vk_context("Failed to allocate Command Buffers")
+    }
+}
+
+pub fn begin_command_buffer(
+    device: &ash::Device,
+    command_buffer: vk::CommandBuffer,
+    flags: vk::CommandBufferUsageFlags,
+) -> Result<()> {
+    let command_buffer_begin_info = vk::CommandBufferBeginInfo {
+        s_type: vk::StructureType::COMMAND_BUFFER_BEGIN_INFO,
+        p_next: ptr::null(),
+        p_inheritance_info: ptr::null(),
+        flags,
     };
 
-    for (i, &command_buffer) in command_buffers.iter().enumerate() {
-        let command_buffer_begin_info = vk::CommandBufferBeginInfo {
-            s_type: vk::StructureType::COMMAND_BUFFER_BEGIN_INFO,
-            p_next: ptr::null(),
-            p_inheritance_info: ptr::null(),
-            flags: vk::CommandBufferUsageFlags::SIMULTANEOUS_USE,
-        };
+    unsafe {
+        device
+            .begin_command_buffer(command_buffer, &command_buffer_begin_info)
+            .vk_context("Failed to begin recording Command Buffer at beginning")
+    }
+}
 
-        unsafe {
-            device
-                .begin_command_buffer(command_buffer, &command_buffer_begin_info)
-                .expect("Failed to begin recording Command Buffer at beginning");
-        }
+pub fn end_command_buffer(device: &ash::Device, command_buffer: vk::CommandBuffer) -> Result<()> {
+    unsafe {
+        device
+            .end_command_buffer(command_buffer)
+            .vk_context("Failed to record Command Buffer at Ending")
+    }
+}
 
-        let clear_values = [
-            vk::ClearValue {
-                color: vk::ClearColorValue {
-                    float32: [0.0, 0.0, 0.0, 1.0],
-                },
+/// Records `render_pass` into `framebuffer`, clearing color and depth, with the draw items
+/// in order. Viewport and scissor cover `extent`, and the pass is wrapped in a `label`.
+pub fn cmd_draw_items(
+    device: &ash::Device,
+    debug_marker: &DebugMarker,
+    command_buffer: vk::CommandBuffer,
+    label: &str,
+    render_pass: vk::RenderPass,
+    framebuffer: vk::Framebuffer,
+    extent: vk::Extent2D,
+    draw_items: &[DrawItem],
+) {
+    let clear_values = [
+        vk::ClearValue {
+            color: vk::ClearColorValue {
+                float32: [0.0, 0.0, 0.0, 1.0],
             },
-            vk::ClearValue {
-                depth_stencil: vk::ClearDepthStencilValue {
-                    depth: 1.0,
-                    stencil: 0,
-                },
+        },
+        vk::ClearValue {
+            depth_stencil: vk::ClearDepthStencilValue {
+                depth: 1.0,
+                stencil: 0,
             },
-        ];
+        },
+    ];
 
-        let render_pass_begin_info = vk::RenderPassBeginInfo {
-            s_type: vk::StructureType::RENDER_PASS_BEGIN_INFO,
-            p_next: ptr::null(),
-            render_pass,
-            framebuffer: framebuffers[i],
-            render_area: vk::Rect2D {
-                offset: vk::Offset2D { x: 0, y: 0 },
-                extent: surface_extent,
-            },
-            clear_value_count: clear_values.len() as u32,
-            p_clear_values: clear_values.as_ptr(),
-        };
+    let render_pass_begin_info = vk::RenderPassBeginInfo {
+        s_type: vk::StructureType::RENDER_PASS_BEGIN_INFO,
+        p_next: ptr::null(),
+        render_pass,
+        framebuffer,
+        render_area: vk::Rect2D {
+            offset: vk::Offset2D { x: 0, y: 0 },
+            extent,
+        },
+        clear_value_count: clear_values.len() as u32,
+        p_clear_values: clear_values.as_ptr(),
+    };
 
-        unsafe {
-            device.cmd_begin_render_pass(
-                command_buffer,
-                &render_pass_begin_info,
-                vk::SubpassContents::INLINE,
-            );
+    debug_marker.cmd_begin_label(command_buffer, label);
+    unsafe {
+        device.cmd_begin_render_pass(
+            command_buffer,
+            &render_pass_begin_info,
+            vk::SubpassContents::INLINE,
+        );
+
+        for draw_item in draw_items.iter() {
             device.cmd_bind_pipeline(
                 command_buffer,
                 vk::PipelineBindPoint::GRAPHICS,
-                graphics_pipeline,
+                draw_item.pipeline,
             );
+            cmd_set_viewport_and_scissor(device, command_buffer, extent);
+            if !draw_item.descriptor_sets.is_empty() {
+                device.cmd_bind_descriptor_sets(
+                    command_buffer,
+                    vk::PipelineBindPoint::GRAPHICS,
+                    draw_item.pipeline_layout,
+                    0,
+                    &draw_item.descriptor_sets,
+                    &[],
+                );
+            }
 
-            let vertex_buffers = [vertex_buffer];
-            let offsets = [0_u64];
-            let descriptor_sets_to_bind = [descriptor_sets[i]];
-
-            device.cmd_bind_vertex_buffers(command_buffer, 0, &vertex_buffers, &offsets);
-            device.cmd_bind_index_buffer(command_buffer, index_buffer, 0, vk::IndexType::UINT32);
-            device.cmd_bind_descriptor_sets(
+            cmd_draw(
+                device,
                 command_buffer,
-                vk::PipelineBindPoint::GRAPHICS,
-                pipeline_layout,
-                0,
-                &descriptor_sets_to_bind,
-                &[],
+                draw_item.pipeline_layout,
+                &draw_item.command,
             );
+        }
 
-            device.cmd_draw_indexed(command_buffer, indices_len, 1, 0, 0, 0);
-
-            device.cmd_end_render_pass(command_buffer);
+        device.cmd_end_render_pass(command_buffer);
+    }
+    debug_marker.cmd_end_label(command_buffer);
+}
 
-            device
-                .end_command_buffer(command_buffer)
-                .expect("Failed to record Command Buffer at Ending");
+fn cmd_draw(
+    device: &ash::Device,
+    command_buffer: vk::CommandBuffer,
+    pipeline_layout: vk::PipelineLayout,
+    draw_command: &DrawCommand,
+) {
+    unsafe {
+        match *draw_command {
+            DrawCommand::Indexed {
+                vertex_buffer,
+                index_buffer,
+                index_count,
+            } => {
+                let vertex_buffers = [vertex_buffer];
+                let offsets = [0_u64];
+
+                device.cmd_bind_vertex_buffers(command_buffer, 0, &vertex_buffers, &offsets);
+                device.cmd_bind_index_buffer(
+                    command_buffer,
+                    index_buffer,
+                    0,
+                    vk::IndexType::UINT32,
+                );
+                device.cmd_draw_indexed(command_buffer, index_count, 1, 0, 0, 0);
+            }
+            DrawCommand::IndexedMeshes {
+                vertex_buffer,
+                index_buffer,
+                ref draws,
+            } => {
+                let vertex_buffers = [vertex_buffer];
+                let offsets = [0_u64];
+
+                device.cmd_bind_vertex_buffers(command_buffer, 0, &vertex_buffers, &offsets);
+                device.cmd_bind_index_buffer(
+                    command_buffer,
+                    index_buffer,
+                    0,
+                    vk::IndexType::UINT32,
+                );
+                for draw in draws.iter() {
+                    cmd_push_constants(
+                        device,
+                        command_buffer,
+                        pipeline_layout,
+                        MeshPushConstants::push_constant_range(),
+                        &draw.push_constants,
+                    );
+                    device.cmd_draw_indexed(
+                        command_buffer,
+                        draw.index_count,
+                        1,
+                        draw.first_index,
+                        0,
+                        0,
+                    );
+                }
+            }
+            DrawCommand::FullscreenTriangle => {
+                device.cmd_draw(command_buffer, 3, 1, 0, 0);
+            }
         }
     }
-
-    command_buffers
 }
 
-pub fn create_sync_objects(device: &ash::Device, max_frame_in_flight: usize) -> SyncObjects {
+pub fn create_sync_objects(
+    device: &ash::Device,
+    max_frame_in_flight: usize,
+) -> Result<SyncObjects> {
     let mut sync_objects = SyncObjects {
         image_available_semaphores: vec![],
         render_finished_semaphores: vec![],
@@ -1063,13 +1267,13 @@ pub fn create_sync_objects(device: &ash::Device, max_frame_in_flight: usize) ->
         unsafe {
             let image_available_semaphore = device
                 .create_semaphore(&semaphore_create_info, None)
-                .expect("Failed to create Semaphore Object");
+                .vk_context("Failed to create Semaphore Object")?;
             let render_finished_semaphore = device
                 .create_semaphore(&semaphore_create_info, None)
-                .expect("Failed to create Semaphore Object");
+                .vk_context("Failed to create Semaphore Object")?;
             let inflight_fence = device
                 .create_fence(&fence_create_info, None)
-                .expect("Failed to create Fence Object");
+                .vk_context("Failed to create Fence Object")?;
 
             sync_objects
                 .image_available_semaphores
@@ -1081,42 +1285,30 @@ pub fn create_sync_objects(device: &ash::Device, max_frame_in_flight: usize) ->
         }
     }
 
-    sync_objects
+    Ok(sync_objects)
 }
 
-pub fn create_vertex_buffer<T>(
-    instance: &ash::Instance,
+pub fn create_vertex_buffer<T: Vertex>(
     device: &ash::Device,
-    physical_device: vk::PhysicalDevice,
+    allocator: &mut MemoryAllocator,
     command_pool: vk::CommandPool,
     submit_queue: vk::Queue,
     data: &[T],
-) -> (vk::Buffer, vk::DeviceMemory) {
+) -> Result<(vk::Buffer, Allocation)> {
     let buffer_size = std::mem::size_of_val(data) as vk::DeviceSize;
-    let device_memory_properties =
-        unsafe { instance.get_physical_device_memory_properties(physical_device) };
 
     let (staging_buffer, staging_buffer_memory) = share::create_buffer(
         device,
         buffer_size,
         vk::BufferUsageFlags::TRANSFER_SRC,
         vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
-        &device_memory_properties,
-    );
+        allocator,
+    )?;
 
     unsafe {
-        let data_ptr = device
-            .map_memory(
-                staging_buffer_memory,
-                0,
-                buffer_size,
-                vk::MemoryMapFlags::empty(),
-            )
-            .expect("Failed to Map Memory") as *mut T;
+        let data_ptr = staging_buffer_memory.mapped_ptr()? as *mut T;
 
         data_ptr.copy_from_nonoverlapping(data.as_ptr(), data.len());
-
-        device.unmap_memory(staging_buffer_memory);
     }
 
     let (vertex_buffer, vertex_buffer_memory) = share::create_buffer(
@@ -1124,8 +1316,8 @@ pub fn create_vertex_buffer<T>(
         buffer_size,
         vk::BufferUsageFlags::TRANSFER_DST | vk::BufferUsageFlags::VERTEX_BUFFER,
         vk::MemoryPropertyFlags::DEVICE_LOCAL,
-        &device_memory_properties,
-    );
+        allocator,
+    )?;
 
     share::copy_buffer(
         device,
@@ -1134,49 +1326,37 @@ pub fn create_vertex_buffer<T>(
         staging_buffer,
         vertex_buffer,
         buffer_size,
-    );
+    )?;
 
     unsafe {
         device.destroy_buffer(staging_buffer, None);
-        device.free_memory(staging_buffer_memory, None);
+        allocator.free(device, staging_buffer_memory);
     }
 
-    (vertex_buffer, vertex_buffer_memory)
+    Ok((vertex_buffer, vertex_buffer_memory))
 }
 
 pub fn create_index_buffer(
-    instance: &ash::Instance,
     device: &ash::Device,
-    physical_device: vk::PhysicalDevice,
+    allocator: &mut MemoryAllocator,
     command_pool: vk::CommandPool,
     submit_queue: vk::Queue,
     data: &[u32],
-) -> (vk::Buffer, vk::DeviceMemory) {
+) -> Result<(vk::Buffer, Allocation)> {
     let buffer_size = std::mem::size_of_val(data) as vk::DeviceSize;
-    let device_memory_properties =
-        unsafe { instance.get_physical_device_memory_properties(physical_device) };
 
     let (staging_buffer, staging_buffer_memory) = share::create_buffer(
         device,
         buffer_size,
         vk::BufferUsageFlags::TRANSFER_SRC,
         vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
-        &device_memory_properties,
-    );
+        allocator,
+    )?;
 
     unsafe {
-        let data_ptr = device
-            .map_memory(
-                staging_buffer_memory,
-                0,
-                buffer_size,
-                vk::MemoryMapFlags::empty(),
-            )
-            .expect("Failed to Map Memory") as *mut u32;
+        let data_ptr = staging_buffer_memory.mapped_ptr()? as *mut u32;
 
         data_ptr.copy_from_nonoverlapping(data.as_ptr(), data.len());
-
-        device.unmap_memory(staging_buffer_memory);
     }
 
     let (index_buffer, index_buffer_memory) = share::create_buffer(
@@ -1184,8 +1364,8 @@ pub fn create_index_buffer(
         buffer_size,
         vk::BufferUsageFlags::TRANSFER_DST | vk::BufferUsageFlags::INDEX_BUFFER,
         vk::MemoryPropertyFlags::DEVICE_LOCAL,
-        &device_memory_properties,
-    );
+        allocator,
+    )?;
 
     share::copy_buffer(
         device,
@@ -1194,17 +1374,20 @@ pub fn create_index_buffer(
         staging_buffer,
         index_buffer,
         buffer_size,
-    );
+    )?;
 
     unsafe {
         device.destroy_buffer(staging_buffer, None);
-        device.free_memory(staging_buffer_memory, None);
+        allocator.free(device, staging_buffer_memory);
     }
 
-    (index_buffer, index_buffer_memory)
+    Ok((index_buffer, index_buffer_memory))
 }
 
-pub fn create_descriptor_set_layout(device: &ash::Device) -> vk::DescriptorSetLayout {
+/// Layout of the default pipeline: the uniform block at binding 0, the texture at binding 1
+/// and its sampler at binding 2. The texture and sampler are separate descriptors because
+/// the in-process GLSL compiler does not support combined `sampler2D` uniforms.
+pub fn create_descriptor_set_layout(device: &ash::Device) -> Result<vk::DescriptorSetLayout> {
     let ubo_layout_bindings = [
         vk::DescriptorSetLayoutBinding {
             // transform uniform
@@ -1215,9 +1398,17 @@ pub fn create_descriptor_set_layout(device: &ash::Device) -> vk::DescriptorSetLa
             p_immutable_samplers: ptr::null(),
         },
         vk::DescriptorSetLayoutBinding {
-            // sampler uniform
+            // texture uniform
             binding: 1,
-            descriptor_type: vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+            descriptor_type: vk::DescriptorType::SAMPLED_IMAGE,
+            descriptor_count: 1,
+            stage_flags: vk::ShaderStageFlags::FRAGMENT,
+            p_immutable_samplers: ptr::null(),
+        },
+        vk::DescriptorSetLayoutBinding {
+            // sampler uniform
+            binding: 2,
+            descriptor_type: vk::DescriptorType::SAMPLER,
             descriptor_count: 1,
             stage_flags: vk::ShaderStageFlags::FRAGMENT,
             p_immutable_samplers: ptr::null(),
@@ -1235,17 +1426,16 @@ pub fn create_descriptor_set_layout(device: &ash::Device) -> vk::DescriptorSetLa
     unsafe {
         device
             .create_descriptor_set_layout(&ubo_layout_create_info, None)
-            .expect("Failed to create Descriptor Set Layout")
+            .vk_context("Failed to create Descriptor Set Layout")
     }
 }
 
 pub fn create_uniform_buffers(
     device: &ash::Device,
-    device_memory_properties: &vk::PhysicalDeviceMemoryProperties,
+    allocator: &mut MemoryAllocator,
+    buffer_size: usize,
     swapchain_image_count: usize,
-) -> (Vec<vk::Buffer>, Vec<vk::DeviceMemory>) {
-    let buffer_size = std::mem::size_of::<UniformBufferObject>();
-
+) -> Result<(Vec<vk::Buffer>, Vec<Allocation>)> {
     let mut uniform_buffers = vec![];
     let mut uniform_buffers_memory = vec![];
 
@@ -1255,29 +1445,34 @@ pub fn create_uniform_buffers(
             buffer_size as u64,
             vk::BufferUsageFlags::UNIFORM_BUFFER,
             vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
-            device_memory_properties,
-        );
+            allocator,
+        )?;
 
         uniform_buffers.push(uniform_buffer);
         uniform_buffers_memory.push(uniform_buffer_memory);
     }
 
-    (uniform_buffers, uniform_buffers_memory)
+    Ok((uniform_buffers, uniform_buffers_memory))
 }
 
 pub fn create_descriptor_pool(
     device: &ash::Device,
     swapchain_images_size: usize,
-) -> vk::DescriptorPool {
+) -> Result<vk::DescriptorPool> {
     let pool_sizes = [
         vk::DescriptorPoolSize {
             // transform descriptor pool
             ty: vk::DescriptorType::UNIFORM_BUFFER,
             descriptor_count: swapchain_images_size as u32,
         },
+        vk::DescriptorPoolSize {
+            // texture descriptor pool
+            ty: vk::DescriptorType::SAMPLED_IMAGE,
+            descriptor_count: swapchain_images_size as u32,
+        },
         vk::DescriptorPoolSize {
             // sampler descriptor pool
-            ty: vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+            ty: vk::DescriptorType::SAMPLER,
             descriptor_count: swapchain_images_size as u32,
         },
     ];
@@ -1294,7 +1489,7 @@ pub fn create_descriptor_pool(
     unsafe {
         device
             .create_descriptor_pool(&descriptor_pool_create_info, None)
-            .expect("Failed to create Descriptor Pool")
+            .vk_context("Failed to create Descriptor Pool")
     }
 }
 
@@ -1302,10 +1497,10 @@ pub fn create_descriptor_sets(
     device: &ash::Device,
     descriptor_pool: vk::DescriptorPool,
     descriptor_set_layout: vk::DescriptorSetLayout,
-    uniform_buffers: &Vec<vk::Buffer>,
-    texture_details: Option<(vk::ImageView, vk::Sampler)>, // texture_image_view, texture_sampler
+    uniform_buffers: &[vk::Buffer],
+    texture_details: (vk::ImageView, vk::Sampler), // texture_image_view, texture_sampler
     swapchain_images_size: usize,
-) -> Vec<vk::DescriptorSet> {
+) -> Result<Vec<vk::DescriptorSet>> {
     let mut layouts = vec![];
 
     for _ in 0..swapchain_images_size {
@@ -1323,23 +1518,201 @@ pub fn create_descriptor_sets(
     let descriptor_sets = unsafe {
         device
             .allocate_descriptor_sets(&descriptor_set_allocate_info)
-            .expect("Failed to allocate descriptor sets")
+            .vk_context("Failed to allocate descriptor sets")?
     };
 
+    let (texture_image_view, texture_sampler) = texture_details;
+    let descriptor_texture_info = [vk::DescriptorImageInfo {
+        sampler: vk::Sampler::null(),
+        image_view: texture_image_view,
+        image_layout: vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+    }];
+    let descriptor_sampler_info = [vk::DescriptorImageInfo {
+        sampler: texture_sampler,
+        image_view: vk::ImageView::null(),
+        image_layout: vk::ImageLayout::UNDEFINED,
+    }];
+
     for (i, &descriptor_set) in descriptor_sets.iter().enumerate() {
         let descriptor_buffer_info = [vk::DescriptorBufferInfo {
             buffer: uniform_buffers[i],
             offset: 0,
-            range: std::mem::size_of::<UniformBufferObject>() as u64,
+            range: vk::WHOLE_SIZE,
         }];
 
-        let descriptor_image_infos = texture_details.map(|(image_view, sampler)| {
+        let descriptor_ctr =
+            |dst_binding, descriptor_type, p_image_info, p_buffer_info| vk::WriteDescriptorSet {
+                s_type: vk::StructureType::WRITE_DESCRIPTOR_SET,
+                p_next: ptr::null(),
+                dst_set: descriptor_set,
+                dst_binding,
+                dst_array_element: 0,
+                descriptor_count: 1,
+                descriptor_type,
+                p_image_info,
+                p_buffer_info,
+                p_texel_buffer_view: ptr::null(),
+            };
+
+        let descriptor_write_sets = [
+            descriptor_ctr(
+                0,
+                vk::DescriptorType::UNIFORM_BUFFER,
+                ptr::null(),
+                descriptor_buffer_info.as_ptr(),
+            ),
+            descriptor_ctr(
+                1,
+                vk::DescriptorType::SAMPLED_IMAGE,
+                descriptor_texture_info.as_ptr(),
+                ptr::null(),
+            ),
+            descriptor_ctr(
+                2,
+                vk::DescriptorType::SAMPLER,
+                descriptor_sampler_info.as_ptr(),
+                ptr::null(),
+            ),
+        ];
+
+        unsafe {
+            device.update_descriptor_sets(&descriptor_write_sets, &[]);
+        }
+    }
+
+    Ok(descriptor_sets)
+}
+
+/// Layout shared by all Shadertoy passes: the uniform block at binding 0, one sampler at
+/// binding 1 and the `iChannel0..3` textures at bindings 2 to 5.
+pub fn create_shadertoy_descriptor_set_layout(
+    device: &ash::Device,
+) -> Result<vk::DescriptorSetLayout> {
+    let mut layout_bindings = vec![
+        vk::DescriptorSetLayoutBinding {
+            // shadertoy uniforms
+            binding: 0,
+            descriptor_type: vk::DescriptorType::UNIFORM_BUFFER,
+            descriptor_count: 1,
+            stage_flags: vk::ShaderStageFlags::VERTEX | vk::ShaderStageFlags::FRAGMENT,
+            p_immutable_samplers: ptr::null(),
+        },
+        vk::DescriptorSetLayoutBinding {
+            // channel sampler
+            binding: 1,
+            descriptor_type: vk::DescriptorType::SAMPLER,
+            descriptor_count: 1,
+            stage_flags: vk::ShaderStageFlags::FRAGMENT,
+            p_immutable_samplers: ptr::null(),
+        },
+    ];
+    for channel in 0..CHANNEL_COUNT {
+        layout_bindings.push(vk::DescriptorSetLayoutBinding {
+            binding: 2 + channel as u32,
+            descriptor_type: vk::DescriptorType::SAMPLED_IMAGE,
+            descriptor_count: 1,
+            stage_flags: vk::ShaderStageFlags::FRAGMENT,
+            p_immutable_samplers: ptr::null(),
+        });
+    }
+
+    let layout_create_info = vk::DescriptorSetLayoutCreateInfo {
+        s_type: vk::StructureType::DESCRIPTOR_SET_LAYOUT_CREATE_INFO,
+        p_next: ptr::null(),
+        flags: vk::DescriptorSetLayoutCreateFlags::empty(),
+        binding_count: layout_bindings.len() as u32,
+        p_bindings: layout_bindings.as_ptr(),
+    };
+
+    unsafe {
+        device
+            .create_descriptor_set_layout(&layout_create_info, None)
+            .vk_context("Failed to create Descriptor Set Layout")
+    }
+}
+
+pub fn create_shadertoy_descriptor_pool(
+    device: &ash::Device,
+    set_count: usize,
+) -> Result<vk::DescriptorPool> {
+    let pool_sizes = [
+        vk::DescriptorPoolSize {
+            ty: vk::DescriptorType::UNIFORM_BUFFER,
+            descriptor_count: set_count as u32,
+        },
+        vk::DescriptorPoolSize {
+            ty: vk::DescriptorType::SAMPLER,
+            descriptor_count: set_count as u32,
+        },
+        vk::DescriptorPoolSize {
+            ty: vk::DescriptorType::SAMPLED_IMAGE,
+            descriptor_count: (set_count * CHANNEL_COUNT) as u32,
+        },
+    ];
+
+    let descriptor_pool_create_info = vk::DescriptorPoolCreateInfo {
+        s_type: vk::StructureType::DESCRIPTOR_POOL_CREATE_INFO,
+        p_next: ptr::null(),
+        flags: vk::DescriptorPoolCreateFlags::empty(),
+        max_sets: set_count as u32,
+        pool_size_count: pool_sizes.len() as u32,
+        p_pool_sizes: pool_sizes.as_ptr(),
+    };
+
+    unsafe {
+        device
+            .create_descriptor_pool(&descriptor_pool_create_info, None)
+            .vk_context("Failed to create Descriptor Pool!")
+    }
+}
+
+/// Allocates one descriptor set per uniform buffer, all sampling the same channel images.
+pub fn create_shadertoy_descriptor_sets(
+    device: &ash::Device,
+    descriptor_pool: vk::DescriptorPool,
+    descriptor_set_layout: vk::DescriptorSetLayout,
+    uniform_buffers: &[vk::Buffer],
+    sampler: vk::Sampler,
+    channel_image_views: &[vk::ImageView; CHANNEL_COUNT],
+) -> Result<Vec<vk::DescriptorSet>> {
+    let layouts = vec![descriptor_set_layout; uniform_buffers.len()];
+
+    let descriptor_set_allocate_info = vk::DescriptorSetAllocateInfo {
+        s_type: vk::StructureType::DESCRIPTOR_SET_ALLOCATE_INFO,
+        p_next: ptr::null(),
+        descriptor_pool,
+        descriptor_set_count: layouts.len() as u32,
+        p_set_layouts: layouts.as_ptr(),
+    };
+
+    let descriptor_sets = unsafe {
+        device
+            .allocate_descriptor_sets(&descriptor_set_allocate_info)
+            .vk_context("Failed to allocate descriptor sets")?
+    };
+
+    let sampler_infos = [vk::DescriptorImageInfo {
+        sampler,
+        image_view: vk::ImageView::null(),
+        image_layout: vk::ImageLayout::UNDEFINED,
+    }];
+    let channel_image_infos: Vec<[vk::DescriptorImageInfo; 1]> = channel_image_views
+        .iter()
+        .map(|&image_view| {
             [vk::DescriptorImageInfo {
-                sampler,
+                sampler: vk::Sampler::null(),
                 image_view,
                 image_layout: vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
             }]
-        });
+        })
+        .collect();
+
+    for (&descriptor_set, &uniform_buffer) in descriptor_sets.iter().zip(uniform_buffers) {
+        let descriptor_buffer_infos = [vk::DescriptorBufferInfo {
+            buffer: uniform_buffer,
+            offset: 0,
+            range: vk::WHOLE_SIZE,
+        }];
 
         let descriptor_ctr =
             |dst_binding, descriptor_type, p_image_info, p_buffer_info| vk::WriteDescriptorSet {
@@ -1355,40 +1728,65 @@ pub fn create_descriptor_sets(
                 p_texel_buffer_view: ptr::null(),
             };
 
-        if let Some(descriptor_image_info) = descriptor_image_infos {
-            let descriptor_write_sets = [
-                descriptor_ctr(
-                    0,
-                    vk::DescriptorType::UNIFORM_BUFFER,
-                    ptr::null(),
-                    descriptor_buffer_info.as_ptr(),
-                ),
-                descriptor_ctr(
-                    1,
-                    vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
-                    descriptor_image_info.as_ptr(),
-                    ptr::null(),
-                ),
-            ];
-
-            unsafe {
-                device.update_descriptor_sets(&descriptor_write_sets, &[]);
-            }
-        } else {
-            let descriptor_write_sets = [descriptor_ctr(
+        let mut descriptor_write_sets = vec![
+            descriptor_ctr(
                 0,
                 vk::DescriptorType::UNIFORM_BUFFER,
                 ptr::null(),
-                descriptor_buffer_info.as_ptr(),
-            )];
+                descriptor_buffer_infos.as_ptr(),
+            ),
+            descriptor_ctr(
+                1,
+                vk::DescriptorType::SAMPLER,
+                sampler_infos.as_ptr(),
+                ptr::null(),
+            ),
+        ];
+        for (channel, channel_image_info) in channel_image_infos.iter().enumerate() {
+            descriptor_write_sets.push(descriptor_ctr(
+                2 + channel as u32,
+                vk::DescriptorType::SAMPLED_IMAGE,
+                channel_image_info.as_ptr(),
+                ptr::null(),
+            ));
+        }
 
-            unsafe {
-                device.update_descriptor_sets(&descriptor_write_sets, &[]);
-            }
+        unsafe {
+            device.update_descriptor_sets(&descriptor_write_sets, &[]);
         }
     }
 
-    descriptor_sets
+    Ok(descriptor_sets)
+}
+
+/// Bilinear, clamp-to-edge sampler matching Shadertoy's default channel settings.
+pub fn create_channel_sampler(device: &ash::Device) -> Result<vk::Sampler> {
+    let sampler_create_info = vk::SamplerCreateInfo {
+        s_type: vk::StructureType::SAMPLER_CREATE_INFO,
+        p_next: ptr::null(),
+        flags: vk::SamplerCreateFlags::empty(),
+        mag_filter: vk::Filter::LINEAR,
+        min_filter: vk::Filter::LINEAR,
+        mipmap_mode: vk::SamplerMipmapMode::NEAREST,
+        address_mode_u: vk::SamplerAddressMode::CLAMP_TO_EDGE,
+        address_mode_v: vk::SamplerAddressMode::CLAMP_TO_EDGE,
+        address_mode_w: vk::SamplerAddressMode::CLAMP_TO_EDGE,
+        mip_lod_bias: 0.0,
+        anisotropy_enable: vk::FALSE,
+        max_anisotropy: 1.0,
+        compare_enable: vk::FALSE,
+        compare_op: vk::CompareOp::ALWAYS,
+        min_lod: 0.0,
+        max_lod: 0.0,
+        border_color: vk::BorderColor::INT_OPAQUE_BLACK,
+        unnormalized_coordinates: vk::FALSE,
+    };
+
+    unsafe {
+        device
+            .create_sampler(&sampler_create_info, None)
+            .vk_context("Failed to create Sampler!")
+    }
 }
 
 pub fn create_depth_resources(
@@ -1398,28 +1796,210 @@ pub fn create_depth_resources(
     _command_pool: vk::CommandPool,
     _submit_queue: vk::Queue,
     swapchain_extent: vk::Extent2D,
-    device_memory_properties: &vk::PhysicalDeviceMemoryProperties,
-) -> (vk::Image, vk::ImageView, vk::DeviceMemory) {
-    let depth_format = share::pipeline::find_depth_format(instance, physical_device);
+    msaa_samples: vk::SampleCountFlags,
+    allocator: &mut MemoryAllocator,
+) -> Result<(vk::Image, vk::ImageView, Allocation)> {
+    let depth_format = share::pipeline::find_depth_format(instance, physical_device)?;
     let (depth_image, depth_image_memory) = share::pipeline::create_image(
         device,
         swapchain_extent.width,
         swapchain_extent.height,
         1,
-        vk::SampleCountFlags::TYPE_1,
+        msaa_samples,
         depth_format,
         vk::ImageTiling::OPTIMAL,
         vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT,
         vk::MemoryPropertyFlags::DEVICE_LOCAL,
-        device_memory_properties,
-    );
+        allocator,
+    )?;
     let depth_image_view = share::pipeline::create_image_view(
         device,
         depth_image,
         depth_format,
         vk::ImageAspectFlags::DEPTH,
         1,
-    );
+    )?;
+
+    Ok((depth_image, depth_image_view, depth_image_memory))
+}
+
+/// Multisampled color attachment that gets resolved into the swapchain image. Its contents
+/// never leave the render pass.
+pub fn create_color_resources(
+    device: &ash::Device,
+    color_format: vk::Format,
+    extent: vk::Extent2D,
+    msaa_samples: vk::SampleCountFlags,
+    allocator: &mut MemoryAllocator,
+) -> Result<(vk::Image, vk::ImageView, Allocation)> {
+    let (color_image, color_image_memory) = create_image(
+        device,
+        extent.width,
+        extent.height,
+        1,
+        msaa_samples,
+        color_format,
+        vk::ImageTiling::OPTIMAL,
+        vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::TRANSIENT_ATTACHMENT,
+        vk::MemoryPropertyFlags::DEVICE_LOCAL,
+        allocator,
+    )?;
+    let color_image_view = create_image_view(
+        device,
+        color_image,
+        color_format,
+        vk::ImageAspectFlags::COLOR,
+        1,
+    )?;
+
+    Ok((color_image, color_image_view, color_image_memory))
+}
+
+/// Sample counts usable for both color and depth framebuffer attachments.
+pub fn supported_sample_counts(
+    instance: &ash::Instance,
+    physical_device: vk::PhysicalDevice,
+) -> vk::SampleCountFlags {
+    let limits = unsafe { instance.get_physical_device_properties(physical_device) }.limits;
+
+    limits.framebuffer_color_sample_counts & limits.framebuffer_depth_sample_counts
+}
+
+/// Highest sample count in `supported` that does not exceed `requested`, falling back to a
+/// single sample.
+pub fn clamp_sample_count(requested: u32, supported: vk::SampleCountFlags) -> vk::SampleCountFlags {
+    [
+        vk::SampleCountFlags::TYPE_64,
+        vk::SampleCountFlags::TYPE_32,
+        vk::SampleCountFlags::TYPE_16,
+        vk::SampleCountFlags::TYPE_8,
+        vk::SampleCountFlags::TYPE_4,
+        vk::SampleCountFlags::TYPE_2,
+    ]
+    .iter()
+    .copied()
+    .find(|&samples| samples.as_raw() <= requested && supported.contains(samples))
+    .unwrap_or(vk::SampleCountFlags::TYPE_1)
+}
+
+pub fn create_offscreen_color_resources(
+    device: &ash::Device,
+    color_format: vk::Format,
+    extent: vk::Extent2D,
+    allocator: &mut MemoryAllocator,
+) -> Result<(vk::Image, vk::ImageView, Allocation)> {
+    let (color_image, color_image_memory) = create_image(
+        device,
+        extent.width,
+        extent.height,
+        1,
+        vk::SampleCountFlags::TYPE_1,
+        color_format,
+        vk::ImageTiling::OPTIMAL,
+        vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::TRANSFER_SRC,
+        vk::MemoryPropertyFlags::DEVICE_LOCAL,
+        allocator,
+    )?;
+    let color_image_view = create_image_view(
+        device,
+        color_image,
+        color_format,
+        vk::ImageAspectFlags::COLOR,
+        1,
+    )?;
+
+    Ok((color_image, color_image_view, color_image_memory))
+}
+
+/// Copies a 4-bytes-per-pixel color image into host memory. The image has to be in
+/// `TRANSFER_SRC_OPTIMAL` layout, which is what the offscreen render pass leaves it in.
+pub fn read_color_image(
+    device: &ash::Device,
+    command_pool: vk::CommandPool,
+    submit_queue: vk::Queue,
+    image: vk::Image,
+    extent: vk::Extent2D,
+    allocator: &mut MemoryAllocator,
+) -> Result<Vec<u8>> {
+    let buffer_size = (extent.width * extent.height * 4) as vk::DeviceSize;
+    let (readback_buffer, readback_buffer_memory) = share::create_buffer(
+        device,
+        buffer_size,
+        vk::BufferUsageFlags::TRANSFER_DST,
+        vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
+        allocator,
+    )?;
+
+    let command_buffer = share::begin_single_time_command(device, command_pool)?;
+
+    // Make the color attachment writes of the render pass visible to the copy.
+    let image_barriers = [vk::ImageMemoryBarrier {
+        s_type: vk::StructureType::IMAGE_MEMORY_BARRIER,
+        p_next: ptr::null(),
+        src_access_mask: vk::AccessFlags::COLOR_ATTACHMENT_WRITE,
+        dst_access_mask: vk::AccessFlags::TRANSFER_READ,
+        old_layout: vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+        new_layout: vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+        src_queue_family_index: vk::QUEUE_FAMILY_IGNORED,
+        dst_queue_family_index: vk::QUEUE_FAMILY_IGNORED,
+        image,
+        subresource_range: vk::ImageSubresourceRange {
+            aspect_mask: vk::ImageAspectFlags::COLOR,
+            base_mip_level: 0,
+            level_count: 1,
+            base_array_layer: 0,
+            layer_count: 1,
+        },
+    }];
+
+    let buffer_image_regions = [vk::BufferImageCopy {
+        image_subresource: vk::ImageSubresourceLayers {
+            aspect_mask: vk::ImageAspectFlags::COLOR,
+            mip_level: 0,
+            base_array_layer: 0,
+            layer_count: 1,
+        },
+        image_extent: vk::Extent3D {
+            width: extent.width,
+            height: extent.height,
+            depth: 1,
+        },
+        buffer_offset: 0,
+        buffer_image_height: 0,
+        buffer_row_length: 0,
+        image_offset: vk::Offset3D { x: 0, y: 0, z: 0 },
+    }];
+
+    unsafe {
+        device.cmd_pipeline_barrier(
+            command_buffer,
+            vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+            vk::PipelineStageFlags::TRANSFER,
+            vk::DependencyFlags::empty(),
+            &[],
+            &[],
+            &image_barriers,
+        );
+        device.cmd_copy_image_to_buffer(
+            command_buffer,
+            image,
+            vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+            readback_buffer,
+            &buffer_image_regions,
+        );
+    }
+
+    share::end_single_time_command(device, command_pool, submit_queue, command_buffer)?;
+
+    let mut pixels = vec![0_u8; buffer_size as usize];
+    unsafe {
+        let data_ptr = readback_buffer_memory.mapped_ptr()? as *const u8;
+
+        data_ptr.copy_to_nonoverlapping(pixels.as_mut_ptr(), pixels.len());
+
+        device.destroy_buffer(readback_buffer, None);
+        allocator.free(device, readback_buffer_memory);
+    }
 
-    (depth_image, depth_image_view, depth_image_memory)
+    Ok(pixels)
 }
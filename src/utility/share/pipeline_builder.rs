@@ -0,0 +1,568 @@
+use crate::error::{Error, Result, VkResultExt};
+use crate::utility::{share, specialization::SpecializationConstants, tools, vertex::Vertex};
+
+use ash::version::DeviceV1_0;
+use ash::vk;
+use std::ffi::CString;
+use std::path::{Path, PathBuf};
+use std::ptr;
+
+// `maxPushConstantsSize` every Vulkan implementation supports.
+const MIN_MAX_PUSH_CONSTANTS_SIZE: u32 = 128;
+
+/// Builds a graphics pipeline and its layout.
+///
+/// The defaults are no vertex input, triangle lists, filled polygons, back-face culling with
+/// clockwise front faces, depth test and write with `LESS`, no stencil test, single-sample
+/// rasterization and one opaque color attachment.
+///
+/// Viewport and scissor are dynamic unless `static_viewport` is called, so command buffers
+/// have to set them (see `share::pipeline::cmd_set_viewport_and_scissor`) and the pipeline
+/// survives swapchain resizes.
+///
+/// ```ignore
+/// let (pipeline, pipeline_layout) = GraphicsPipelineBuilder::new()
+///     .shader(vk::ShaderStageFlags::VERTEX, Path::new("shaders/mesh.vert"))
+///     .shader(vk::ShaderStageFlags::FRAGMENT, Path::new("shaders/mesh.frag"))
+///     .vertex_input::<VertexV3>()
+///     .front_face(vk::FrontFace::COUNTER_CLOCKWISE)
+///     .descriptor_set_layout(ubo_layout)
+///     .build(device, render_pass)?;
+/// ```
+#[derive(Debug, Clone)]
+pub struct GraphicsPipelineBuilder {
+    // `None` for dynamic viewport and scissor.
+    static_viewport: Option<vk::Extent2D>,
+    shader_stages: Vec<(vk::ShaderStageFlags, PathBuf)>,
+    specializations: Vec<(vk::ShaderStageFlags, SpecializationConstants)>,
+    vertex_bindings: Vec<vk::VertexInputBindingDescription>,
+    vertex_attributes: Vec<vk::VertexInputAttributeDescription>,
+    topology: vk::PrimitiveTopology,
+    polygon_mode: vk::PolygonMode,
+    cull_mode: vk::CullModeFlags,
+    front_face: vk::FrontFace,
+    depth_test_enable: bool,
+    depth_write_enable: bool,
+    depth_compare_op: vk::CompareOp,
+    // Front and back stencil operations, or `None` to disable the stencil test.
+    stencil_ops: Option<(vk::StencilOpState, vk::StencilOpState)>,
+    blend_attachments: Vec<vk::PipelineColorBlendAttachmentState>,
+    rasterization_samples: vk::SampleCountFlags,
+    // Minimum fraction of samples shaded individually, or `None` to shade once per pixel.
+    min_sample_shading: Option<f32>,
+    push_constant_ranges: Vec<vk::PushConstantRange>,
+    max_push_constants_size: u32,
+    set_layouts: Vec<vk::DescriptorSetLayout>,
+    pipeline_cache: vk::PipelineCache,
+}
+
+impl GraphicsPipelineBuilder {
+    pub fn new() -> GraphicsPipelineBuilder {
+        GraphicsPipelineBuilder {
+            static_viewport: None,
+            shader_stages: vec![],
+            specializations: vec![],
+            vertex_bindings: vec![],
+            vertex_attributes: vec![],
+            topology: vk::PrimitiveTopology::TRIANGLE_LIST,
+            polygon_mode: vk::PolygonMode::FILL,
+            cull_mode: vk::CullModeFlags::BACK,
+            front_face: vk::FrontFace::CLOCKWISE,
+            depth_test_enable: true,
+            depth_write_enable: true,
+            depth_compare_op: vk::CompareOp::LESS,
+            stencil_ops: None,
+            blend_attachments: vec![opaque_blend_attachment()],
+            rasterization_samples: vk::SampleCountFlags::TYPE_1,
+            min_sample_shading: None,
+            push_constant_ranges: vec![],
+            max_push_constants_size: MIN_MAX_PUSH_CONSTANTS_SIZE,
+            set_layouts: vec![],
+            pipeline_cache: vk::PipelineCache::null(),
+        }
+    }
+
+    /// Adds a stage whose code is read with `tools::read_specialized_shader_code`, calling
+    /// `main`.
+    pub fn shader(mut self, stage: vk::ShaderStageFlags, shader_path: &Path) -> Self {
+        self.shader_stages.push((stage, shader_path.to_path_buf()));
+        self
+    }
+
+    /// Sets the specialization constants of `stage`, replacing earlier ones.
+    pub fn specialization(
+        mut self,
+        stage: vk::ShaderStageFlags,
+        constants: SpecializationConstants,
+    ) -> Self {
+        self.specializations
+            .retain(|(specialized_stage, _)| *specialized_stage != stage);
+        self.specializations.push((stage, constants));
+        self
+    }
+
+    /// Bakes a viewport and scissor covering `extent` into the pipeline, which then has to
+    /// be rebuilt when the target size changes.
+    pub fn static_viewport(mut self, extent: vk::Extent2D) -> Self {
+        self.static_viewport = Some(extent);
+        self
+    }
+
+    /// Reads vertices of type `V` from binding 0.
+    pub fn vertex_input<V: Vertex>(mut self) -> Self {
+        self.vertex_bindings = V::binding_descriptions();
+        self.vertex_attributes = V::attribute_descriptions();
+        self
+    }
+
+    pub fn topology(mut self, topology: vk::PrimitiveTopology) -> Self {
+        self.topology = topology;
+        self
+    }
+
+    /// `LINE` and `POINT` need the `fillModeNonSolid` device feature, which the logical device
+    /// enables whenever it is supported.
+    pub fn polygon_mode(mut self, polygon_mode: vk::PolygonMode) -> Self {
+        self.polygon_mode = polygon_mode;
+        self
+    }
+
+    pub fn cull_mode(mut self, cull_mode: vk::CullModeFlags) -> Self {
+        self.cull_mode = cull_mode;
+        self
+    }
+
+    /// `COUNTER_CLOCKWISE` for meshes drawn through `Camera::projection`, whose Y flip
+    /// mirrors the winding of their triangles.
+    pub fn front_face(mut self, front_face: vk::FrontFace) -> Self {
+        self.front_face = front_face;
+        self
+    }
+
+    pub fn depth_test(
+        mut self,
+        depth_test_enable: bool,
+        depth_write_enable: bool,
+        depth_compare_op: vk::CompareOp,
+    ) -> Self {
+        self.depth_test_enable = depth_test_enable;
+        self.depth_write_enable = depth_write_enable;
+        self.depth_compare_op = depth_compare_op;
+        self
+    }
+
+    /// Enables the stencil test; the render pass needs a format with a stencil aspect.
+    pub fn stencil_test(mut self, front: vk::StencilOpState, back: vk::StencilOpState) -> Self {
+        self.stencil_ops = Some((front, back));
+        self
+    }
+
+    /// Replaces the blend state, one entry per color attachment of the subpass.
+    pub fn blend_attachments(
+        mut self,
+        blend_attachments: &[vk::PipelineColorBlendAttachmentState],
+    ) -> Self {
+        self.blend_attachments = blend_attachments.to_vec();
+        self
+    }
+
+    /// Has to match the sample count of the render pass attachments.
+    pub fn rasterization_samples(mut self, rasterization_samples: vk::SampleCountFlags) -> Self {
+        self.rasterization_samples = rasterization_samples;
+        self
+    }
+
+    /// Runs the fragment shader for at least `min_sample_shading` (0 to 1) of the samples of
+    /// each pixel, which also smooths aliasing inside triangles such as texture edges. Needs
+    /// the `sampleRateShading` device feature.
+    pub fn sample_shading(mut self, min_sample_shading: f32) -> Self {
+        self.min_sample_shading = Some(min_sample_shading);
+        self
+    }
+
+    /// Declares push constants for the stages in `push_constant_range.stage_flags`; see
+    /// `share::pipeline::cmd_push_constants`.
+    pub fn push_constant_range(mut self, push_constant_range: vk::PushConstantRange) -> Self {
+        self.push_constant_ranges.push(push_constant_range);
+        self
+    }
+
+    /// `maxPushConstantsSize` of the device. `build` fails if a push constant range ends past
+    /// it; without this, ranges are checked against the 128 bytes all devices support.
+    pub fn max_push_constants_size(mut self, max_push_constants_size: u32) -> Self {
+        self.max_push_constants_size = max_push_constants_size;
+        self
+    }
+
+    /// Appends a descriptor set layout; the first one added is set 0.
+    pub fn descriptor_set_layout(mut self, set_layout: vk::DescriptorSetLayout) -> Self {
+        self.set_layouts.push(set_layout);
+        self
+    }
+
+    /// Reuses and extends `pipeline_cache`, e.g. from `pipeline_cache::load_pipeline_cache`.
+    pub fn pipeline_cache(mut self, pipeline_cache: vk::PipelineCache) -> Self {
+        self.pipeline_cache = pipeline_cache;
+        self
+    }
+
+    /// Compiles the shader stages and creates the pipeline for subpass 0 of `render_pass`.
+    ///
+    /// Nothing is leaked if a shader fails to load or pipeline creation fails.
+    pub fn build(
+        &self,
+        device: &ash::Device,
+        render_pass: vk::RenderPass,
+    ) -> Result<(vk::Pipeline, vk::PipelineLayout)> {
+        self.check_push_constants()?;
+
+        let mut shader_modules = vec![];
+        for (stage, shader_path) in self.shader_stages.iter() {
+            let shader_module =
+                tools::read_specialized_shader_code(shader_path, self.constants(*stage))
+                    .and_then(|code| share::create_shader_module(device, code));
+            match shader_module {
+                Ok(shader_module) => shader_modules.push(shader_module),
+                Err(err) => {
+                    destroy_shader_modules(device, &shader_modules);
+                    return Err(err);
+                }
+            }
+        }
+
+        let pipeline = self.create_pipeline(device, render_pass, &shader_modules);
+        destroy_shader_modules(device, &shader_modules);
+
+        pipeline
+    }
+
+    /// Fails with `Error::PushConstantsTooLarge` if a push constant range ends past
+    /// `max_push_constants_size`.
+    pub fn check_push_constants(&self) -> Result<()> {
+        let push_constants_size = self
+            .push_constant_ranges
+            .iter()
+            .map(|range| range.offset + range.size)
+            .max()
+            .unwrap_or(0);
+        if push_constants_size > self.max_push_constants_size {
+            return Err(Error::PushConstantsTooLarge {
+                size: push_constants_size,
+                max_size: self.max_push_constants_size,
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Viewport and scissor baked into the pipeline. Both are empty for dynamic viewports,
+    /// whose count still has to be given although their contents are ignored.
+    pub fn viewport_and_scissor(&self) -> (vk::Viewport, vk::Rect2D) {
+        let extent = self.static_viewport.unwrap_or_default();
+        (full_viewport(extent), full_scissor(extent))
+    }
+
+    pub fn dynamic_states(&self) -> Vec<vk::DynamicState> {
+        match self.static_viewport {
+            Some(_) => vec![],
+            None => vec![vk::DynamicState::VIEWPORT, vk::DynamicState::SCISSOR],
+        }
+    }
+
+    /// Map entries and data of each shader stage, in the order the stages were added, or
+    /// `None` for stages without specialization constants.
+    pub fn stage_specializations(&self) -> Vec<Option<(Vec<vk::SpecializationMapEntry>, Vec<u8>)>> {
+        self.shader_stages
+            .iter()
+            .map(|(stage, _)| self.constants(*stage))
+            .map(|constants| {
+                if constants.is_empty() {
+                    None
+                } else {
+                    Some(constants.map_entries_and_data())
+                }
+            })
+            .collect()
+    }
+
+    fn constants(&self, stage: vk::ShaderStageFlags) -> &SpecializationConstants {
+        static NO_CONSTANTS: SpecializationConstants = SpecializationConstants::new();
+
+        self.specializations
+            .iter()
+            .find(|(specialized_stage, _)| *specialized_stage == stage)
+            .map_or(&NO_CONSTANTS, |(_, constants)| constants)
+    }
+
+    fn create_pipeline(
+        &self,
+        device: &ash::Device,
+        render_pass: vk::RenderPass,
+        shader_modules: &[vk::ShaderModule],
+    ) -> Result<(vk::Pipeline, vk::PipelineLayout)> {
+        let main_function_name = CString::new("main").unwrap();
+
+        // -#- Programmable pipeline -#-
+        let specialization_data = self.stage_specializations();
+        let specialization_infos: Vec<Option<vk::SpecializationInfo>> = specialization_data
+            .iter()
+            .map(|specialization| {
+                specialization
+                    .as_ref()
+                    .map(|(map_entries, data)| vk::SpecializationInfo {
+                        map_entry_count: map_entries.len() as u32,
+                        p_map_entries: map_entries.as_ptr(),
+                        data_size: data.len(),
+                        p_data: data.as_ptr() as *const std::ffi::c_void,
+                    })
+            })
+            .collect();
+        let shader_stages: Vec<vk::PipelineShaderStageCreateInfo> = self
+            .shader_stages
+            .iter()
+            .zip(shader_modules)
+            .zip(specialization_infos.iter())
+            .map(
+                |(((stage, _), &module), specialization_info)| vk::PipelineShaderStageCreateInfo {
+                    s_type: vk::StructureType::PIPELINE_SHADER_STAGE_CREATE_INFO,
+                    p_next: ptr::null(),
+                    flags: vk::PipelineShaderStageCreateFlags::empty(),
+                    module,
+                    p_name: main_function_name.as_ptr(),
+                    p_specialization_info: specialization_info
+                        .as_ref()
+                        .map_or(ptr::null(), |specialization_info| specialization_info),
+                    stage: *stage,
+                },
+            )
+            .collect();
+
+        // -#- Fixed pipeline -#-
+        // * Vertex input
+        let vertex_input_state_create_info = vk::PipelineVertexInputStateCreateInfo {
+            s_type: vk::StructureType::PIPELINE_VERTEX_INPUT_STATE_CREATE_INFO,
+            p_next: ptr::null(),
+            flags: vk::PipelineVertexInputStateCreateFlags::empty(),
+            vertex_attribute_description_count: self.vertex_attributes.len() as u32,
+            p_vertex_attribute_descriptions: self.vertex_attributes.as_ptr(),
+            vertex_binding_description_count: self.vertex_bindings.len() as u32,
+            p_vertex_binding_descriptions: self.vertex_bindings.as_ptr(),
+        };
+        // * Input assembly
+        let vertex_input_assembly_state_info = vk::PipelineInputAssemblyStateCreateInfo {
+            s_type: vk::StructureType::PIPELINE_INPUT_ASSEMBLY_STATE_CREATE_INFO,
+            flags: vk::PipelineInputAssemblyStateCreateFlags::empty(),
+            p_next: ptr::null(),
+            primitive_restart_enable: vk::FALSE,
+            topology: self.topology,
+        };
+
+        // * Viewport and scissors
+        let (viewport, scissor) = self.viewport_and_scissor();
+        let viewports = [viewport];
+        let scissors = [scissor];
+        let viewport_state_create_info = vk::PipelineViewportStateCreateInfo {
+            s_type: vk::StructureType::PIPELINE_VIEWPORT_STATE_CREATE_INFO,
+            p_next: ptr::null(),
+            flags: vk::PipelineViewportStateCreateFlags::empty(),
+            scissor_count: scissors.len() as u32,
+            p_scissors: scissors.as_ptr(),
+            viewport_count: viewports.len() as u32,
+            p_viewports: viewports.as_ptr(),
+        };
+
+        // * Rasterizer
+        let rasterization_state_create_info = vk::PipelineRasterizationStateCreateInfo {
+            s_type: vk::StructureType::PIPELINE_RASTERIZATION_STATE_CREATE_INFO,
+            p_next: ptr::null(),
+            flags: vk::PipelineRasterizationStateCreateFlags::empty(),
+            depth_clamp_enable: vk::FALSE,
+            cull_mode: self.cull_mode,
+            front_face: self.front_face,
+            line_width: 1.0,
+            polygon_mode: self.polygon_mode,
+            rasterizer_discard_enable: vk::FALSE,
+            depth_bias_clamp: 0.0,
+            depth_bias_constant_factor: 0.0,
+            depth_bias_enable: vk::FALSE,
+            depth_bias_slope_factor: 0.0,
+        };
+        // * Multisampling
+        let multisample_state_create_info = vk::PipelineMultisampleStateCreateInfo {
+            s_type: vk::StructureType::PIPELINE_MULTISAMPLE_STATE_CREATE_INFO,
+            flags: vk::PipelineMultisampleStateCreateFlags::empty(),
+            p_next: ptr::null(),
+            rasterization_samples: self.rasterization_samples,
+            sample_shading_enable: self.min_sample_shading.is_some() as vk::Bool32,
+            min_sample_shading: self.min_sample_shading.unwrap_or(0.0),
+            p_sample_mask: ptr::null(),
+            alpha_to_one_enable: vk::FALSE,
+            alpha_to_coverage_enable: vk::FALSE,
+        };
+
+        // * Depth/stencil test
+        let disabled_stencil_state = vk::StencilOpState {
+            fail_op: vk::StencilOp::KEEP,
+            pass_op: vk::StencilOp::KEEP,
+            depth_fail_op: vk::StencilOp::KEEP,
+            compare_op: vk::CompareOp::ALWAYS,
+            compare_mask: 0,
+            write_mask: 0,
+            reference: 0,
+        };
+        let (stencil_front, stencil_back) = self
+            .stencil_ops
+            .unwrap_or((disabled_stencil_state, disabled_stencil_state));
+        let depth_state_create_info = vk::PipelineDepthStencilStateCreateInfo {
+            s_type: vk::StructureType::PIPELINE_DEPTH_STENCIL_STATE_CREATE_INFO,
+            p_next: ptr::null(),
+            flags: vk::PipelineDepthStencilStateCreateFlags::empty(),
+            depth_test_enable: self.depth_test_enable as vk::Bool32,
+            depth_write_enable: self.depth_write_enable as vk::Bool32,
+            depth_compare_op: self.depth_compare_op,
+            depth_bounds_test_enable: vk::FALSE,
+            stencil_test_enable: self.stencil_ops.is_some() as vk::Bool32,
+            front: stencil_front,
+            back: stencil_back,
+            max_depth_bounds: 1.0,
+            min_depth_bounds: 0.0,
+        };
+
+        // * Color blending
+        let color_blend_state = vk::PipelineColorBlendStateCreateInfo {
+            s_type: vk::StructureType::PIPELINE_COLOR_BLEND_STATE_CREATE_INFO,
+            p_next: ptr::null(),
+            flags: vk::PipelineColorBlendStateCreateFlags::empty(),
+            logic_op_enable: vk::FALSE,
+            logic_op: vk::LogicOp::COPY,
+            attachment_count: self.blend_attachments.len() as u32,
+            p_attachments: self.blend_attachments.as_ptr(),
+            blend_constants: [0.0, 0.0, 0.0, 0.0],
+        };
+
+        // * Dynamic state
+        let dynamic_states = self.dynamic_states();
+        let dynamic_state_create_info = vk::PipelineDynamicStateCreateInfo {
+            s_type: vk::StructureType::PIPELINE_DYNAMIC_STATE_CREATE_INFO,
+            p_next: ptr::null(),
+            flags: vk::PipelineDynamicStateCreateFlags::empty(),
+            dynamic_state_count: dynamic_states.len() as u32,
+            p_dynamic_states: dynamic_states.as_ptr(),
+        };
+
+        let pipeline_layout_create_info = vk::PipelineLayoutCreateInfo {
+            s_type: vk::StructureType::PIPELINE_LAYOUT_CREATE_INFO,
+            p_next: ptr::null(),
+            flags: vk::PipelineLayoutCreateFlags::empty(),
+            set_layout_count: self.set_layouts.len() as u32,
+            p_set_layouts: self.set_layouts.as_ptr(),
+            push_constant_range_count: self.push_constant_ranges.len() as u32,
+            p_push_constant_ranges: self.push_constant_ranges.as_ptr(),
+        };
+        let pipeline_layout = unsafe {
+            device
+                .create_pipeline_layout(&pipeline_layout_create_info, None)
+                .vk_context("Failed to create pipeline layout!")?
+        };
+
+        let graphic_pipeline_create_infos = [vk::GraphicsPipelineCreateInfo {
+            s_type: vk::StructureType::GRAPHICS_PIPELINE_CREATE_INFO,
+            p_next: ptr::null(),
+            flags: vk::PipelineCreateFlags::empty(),
+            stage_count: shader_stages.len() as u32,
+            p_stages: shader_stages.as_ptr(),
+            p_vertex_input_state: &vertex_input_state_create_info,
+            p_input_assembly_state: &vertex_input_assembly_state_info,
+            p_tessellation_state: ptr::null(),
+            p_viewport_state: &viewport_state_create_info,
+            p_rasterization_state: &rasterization_state_create_info,
+            p_multisample_state: &multisample_state_create_info,
+            p_depth_stencil_state: &depth_state_create_info,
+            p_color_blend_state: &color_blend_state,
+            p_dynamic_state: &dynamic_state_create_info,
+            layout: pipeline_layout,
+            render_pass,
+            subpass: 0,
+            base_pipeline_handle: vk::Pipeline::null(),
+            base_pipeline_index: -1,
+        }];
+
+        let graphics_pipelines = unsafe {
+            device
+                .create_graphics_pipelines(
+                    self.pipeline_cache,
+                    &graphic_pipeline_create_infos,
+                    None,
+                )
+                .map_err(|(_, result)| result)
+                .vk_context("Failed to create Graphics Pipeline!")
+        };
+
+        match graphics_pipelines {
+            Ok(graphics_pipelines) => Ok((graphics_pipelines[0], pipeline_layout)),
+            Err(err) => {
+                unsafe { device.destroy_pipeline_layout(pipeline_layout, None) };
+                Err(err)
+            }
+        }
+    }
+}
+
+impl Default for GraphicsPipelineBuilder {
+    fn default() -> Self {
+        GraphicsPipelineBuilder::new()
+    }
+}
+
+/// Viewport covering all of `extent` with the full depth range.
+pub fn full_viewport(extent: vk::Extent2D) -> vk::Viewport {
+    vk::Viewport {
+        x: 0.0,
+        y: 0.0,
+        width: extent.width as f32,
+        height: extent.height as f32,
+        min_depth: 0.0,
+        max_depth: 1.0,
+    }
+}
+
+pub fn full_scissor(extent: vk::Extent2D) -> vk::Rect2D {
+    vk::Rect2D {
+        offset: vk::Offset2D { x: 0, y: 0 },
+        extent,
+    }
+}
+
+/// Writes the fragment color as is.
+pub fn opaque_blend_attachment() -> vk::PipelineColorBlendAttachmentState {
+    vk::PipelineColorBlendAttachmentState {
+        blend_enable: vk::FALSE,
+        color_write_mask: vk::ColorComponentFlags::all(),
+        src_color_blend_factor: vk::BlendFactor::ONE,
+        dst_color_blend_factor: vk::BlendFactor::ZERO,
+        color_blend_op: vk::BlendOp::ADD,
+        src_alpha_blend_factor: vk::BlendFactor::ONE,
+        dst_alpha_blend_factor: vk::BlendFactor::ZERO,
+        alpha_blend_op: vk::BlendOp::ADD,
+    }
+}
+
+/// Blends the fragment color over the attachment by its (non-premultiplied) alpha.
+pub fn alpha_blend_attachment() -> vk::PipelineColorBlendAttachmentState {
+    vk::PipelineColorBlendAttachmentState {
+        blend_enable: vk::TRUE,
+        color_write_mask: vk::ColorComponentFlags::all(),
+        src_color_blend_factor: vk::BlendFactor::SRC_ALPHA,
+        dst_color_blend_factor: vk::BlendFactor::ONE_MINUS_SRC_ALPHA,
+        color_blend_op: vk::BlendOp::ADD,
+        src_alpha_blend_factor: vk::BlendFactor::ONE,
+        dst_alpha_blend_factor: vk::BlendFactor::ONE_MINUS_SRC_ALPHA,
+        alpha_blend_op: vk::BlendOp::ADD,
+    }
+}
+
+fn destroy_shader_modules(device: &ash::Device, shader_modules: &[vk::ShaderModule]) {
+    for &shader_module in shader_modules.iter() {
+        unsafe { device.destroy_shader_module(shader_module, None) };
+    }
+}
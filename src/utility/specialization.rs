@@ -0,0 +1,191 @@
+use crate::error::{Error, Result};
+
+use ash::vk;
+use std::path::Path;
+
+/// Value of a `layout(constant_id = N) const` declaration.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SpecializationValue {
+    Bool(bool),
+    Int(i32),
+    Float(f32),
+}
+
+impl SpecializationValue {
+    /// The 4 bytes Vulkan reads for the constant; booleans are `VkBool32`.
+    fn to_ne_bytes(self) -> [u8; 4] {
+        match self {
+            SpecializationValue::Bool(value) => (value as vk::Bool32).to_ne_bytes(),
+            SpecializationValue::Int(value) => value.to_ne_bytes(),
+            SpecializationValue::Float(value) => value.to_ne_bytes(),
+        }
+    }
+
+    /// The value as the in-process compiler takes it; booleans are 0 or 1.
+    pub fn as_f64(self) -> f64 {
+        match self {
+            SpecializationValue::Bool(value) => value as u8 as f64,
+            SpecializationValue::Int(value) => value as f64,
+            SpecializationValue::Float(value) => value as f64,
+        }
+    }
+
+    fn parse(value: &str) -> Option<SpecializationValue> {
+        match value {
+            "true" => Some(SpecializationValue::Bool(true)),
+            "false" => Some(SpecializationValue::Bool(false)),
+            _ => value
+                .parse()
+                .map(SpecializationValue::Int)
+                .or_else(|_| value.parse().map(SpecializationValue::Float))
+                .ok(),
+        }
+    }
+}
+
+/// Specialization constants of one shader stage, by constant ID.
+///
+/// Constants the shader does not declare are ignored, and declared constants without a
+/// value keep the default from the shader source.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SpecializationConstants {
+    // Sorted by ID, each ID at most once.
+    constants: Vec<(u32, SpecializationValue)>,
+}
+
+impl SpecializationConstants {
+    pub const fn new() -> SpecializationConstants {
+        SpecializationConstants { constants: vec![] }
+    }
+
+    /// Sets constant `id`, replacing any previous value.
+    pub fn set(&mut self, id: u32, value: SpecializationValue) {
+        match self
+            .constants
+            .binary_search_by_key(&id, |&(constant_id, _)| constant_id)
+        {
+            Ok(index) => self.constants[index].1 = value,
+            Err(index) => self.constants.insert(index, (id, value)),
+        }
+    }
+
+    pub fn get(&self, id: u32) -> Option<SpecializationValue> {
+        self.constants
+            .iter()
+            .find(|&&(constant_id, _)| constant_id == id)
+            .map(|&(_, value)| value)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.constants.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (u32, SpecializationValue)> + '_ {
+        self.constants.iter().copied()
+    }
+
+    /// Map entries and data for a `vk::SpecializationInfo`, 4 bytes per constant.
+    pub fn map_entries_and_data(&self) -> (Vec<vk::SpecializationMapEntry>, Vec<u8>) {
+        let mut map_entries = vec![];
+        let mut data = vec![];
+        for (constant_id, value) in self.iter() {
+            map_entries.push(vk::SpecializationMapEntry {
+                constant_id,
+                offset: data.len() as u32,
+                size: 4,
+            });
+            data.extend_from_slice(&value.to_ne_bytes());
+        }
+
+        (map_entries, data)
+    }
+}
+
+/// Specialization constants per shader stage, read from a config file so shader features
+/// can be switched without editing GLSL.
+///
+/// The file has one constant per line, `#` starts a comment:
+///
+/// ```text
+/// # stage   id  value
+/// fragment  0   1       # debug view
+/// vertex    3   true
+/// fragment  1   0.5
+/// ```
+///
+/// Values are `true`/`false`, integers or floats, and have to match the type declared in
+/// the shader, so floats need a decimal point.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SpecializationConfig {
+    pub vertex: SpecializationConstants,
+    pub fragment: SpecializationConstants,
+}
+
+impl SpecializationConfig {
+    pub fn load(path: &Path) -> Result<SpecializationConfig> {
+        let source = std::fs::read_to_string(path).map_err(|source| Error::Io {
+            path: path.to_path_buf(),
+            source,
+        })?;
+
+        SpecializationConfig::parse(&source).map_err(|(line, reason)| {
+            Error::InvalidSpecialization {
+                path: path.to_path_buf(),
+                line,
+                reason,
+            }
+        })
+    }
+
+    /// Constants of `stage`, empty for stages the config cannot name.
+    pub fn constants(&self, stage: vk::ShaderStageFlags) -> &SpecializationConstants {
+        static EMPTY: SpecializationConstants = SpecializationConstants::new();
+
+        match stage {
+            vk::ShaderStageFlags::VERTEX => &self.vertex,
+            vk::ShaderStageFlags::FRAGMENT => &self.fragment,
+            _ => &EMPTY,
+        }
+    }
+
+    /// On failure returns the 1-based line number and the reason.
+    fn parse(source: &str) -> std::result::Result<SpecializationConfig, (usize, String)> {
+        let mut config = SpecializationConfig::default();
+        for (index, line) in source.lines().enumerate() {
+            let line_number = index + 1;
+            let line = line.split('#').next().unwrap_or("");
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let (stage, id, value) = match fields[..] {
+                [] => continue,
+                [stage, id, value] => (stage, id, value),
+                _ => {
+                    return Err((
+                        line_number,
+                        "expected '<stage> <constant id> <value>'".to_owned(),
+                    ))
+                }
+            };
+
+            let constants = match stage {
+                "vertex" => &mut config.vertex,
+                "fragment" => &mut config.fragment,
+                _ => return Err((line_number, format!("unknown stage '{}'", stage))),
+            };
+            let id = id
+                .parse()
+                .map_err(|_| (line_number, format!("invalid constant id '{}'", id)))?;
+            if constants.get(id).is_some() {
+                return Err((
+                    line_number,
+                    format!("constant {} of the {} stage is set twice", id, stage),
+                ));
+            }
+            let value = SpecializationValue::parse(value)
+                .ok_or_else(|| (line_number, format!("invalid value '{}'", value)))?;
+
+            constants.set(id, value);
+        }
+
+        Ok(config)
+    }
+}
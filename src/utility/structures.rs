@@ -1,18 +1,11 @@
+use crate::utility::vertex::Vertex;
+
 use ash::vk;
-use memoffset::offset_of;
-use std::os::raw::c_char;
+use cgmath::{Matrix4, SquareMatrix};
+use std::path::PathBuf;
 
 pub struct DeviceExtension {
-    pub names: [&'static str; 1],
-}
-
-impl DeviceExtension {
-    pub fn get_extensions_raw_names(&self) -> [*const c_char; 1] {
-        [
-            // currently just enable the Swapchain extension.
-            ash::extensions::khr::Swapchain::name().as_ptr(),
-        ]
-    }
+    pub names: &'static [&'static str],
 }
 
 pub struct SurfaceStuff {
@@ -41,6 +34,12 @@ pub struct QueueFamilyIndices {
     pub present_family: Option<u32>,
 }
 
+impl Default for QueueFamilyIndices {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl QueueFamilyIndices {
     pub fn new() -> QueueFamilyIndices {
         QueueFamilyIndices {
@@ -60,123 +59,180 @@ pub struct SyncObjects {
     pub inflight_fences: Vec<vk::Fence>,
 }
 
+/// Uniform block of the rectangle and mesh shaders, laid out to match std140.
 #[repr(C)]
 #[derive(Debug, Clone, Copy)]
 pub struct UniformBufferObject {
+    pub model: Matrix4<f32>,
+    pub view: Matrix4<f32>,
+    pub proj: Matrix4<f32>,
     pub u_time: f32,
 }
 
+impl Default for UniformBufferObject {
+    /// Identity transforms, which leave the rectangle in normalized device coordinates.
+    fn default() -> Self {
+        UniformBufferObject {
+            model: Matrix4::identity(),
+            view: Matrix4::identity(),
+            proj: Matrix4::identity(),
+            u_time: 0.0,
+        }
+    }
+}
+
+/// Uniform block of the Shadertoy prelude, laid out to match std140.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ShadertoyUniforms {
+    pub i_resolution: [f32; 3],
+    pub i_time: f32,
+    pub i_mouse: [f32; 4],
+    pub i_date: [f32; 4],
+    pub i_time_delta: f32,
+    pub i_frame: i32,
+}
+
+/// Push constant block of the mesh shaders, set for every draw.
 #[repr(C)]
 #[derive(Debug, Clone, Copy)]
-pub struct VertexV1 {
-    pub pos: [f32; 2],
-    pub color: [f32; 3],
+pub struct MeshPushConstants {
+    /// Applied before `UniformBufferObject::model`.
+    pub transform: Matrix4<f32>,
+    /// Multiplies the vertex color, e.g. the diffuse color of the material.
+    pub base_color: [f32; 4],
 }
-impl VertexV1 {
-    pub fn get_binding_descriptions() -> [vk::VertexInputBindingDescription; 1] {
-        [vk::VertexInputBindingDescription {
-            binding: 0,
-            stride: std::mem::size_of::<Self>() as u32,
-            input_rate: vk::VertexInputRate::VERTEX,
-        }]
+
+impl MeshPushConstants {
+    pub fn push_constant_range() -> vk::PushConstantRange {
+        vk::PushConstantRange {
+            stage_flags: vk::ShaderStageFlags::VERTEX,
+            offset: 0,
+            size: std::mem::size_of::<MeshPushConstants>() as u32,
+        }
     }
+}
 
-    pub fn get_attribute_descriptions() -> [vk::VertexInputAttributeDescription; 2] {
-        [
-            vk::VertexInputAttributeDescription {
-                location: 0,
-                binding: 0,
-                format: vk::Format::R32G32_SFLOAT,
-                offset: offset_of!(Self, pos) as u32,
-            },
-            vk::VertexInputAttributeDescription {
-                location: 1,
-                binding: 0,
-                format: vk::Format::R32G32B32_SFLOAT,
-                offset: offset_of!(Self, color) as u32,
-            },
-        ]
+impl Default for MeshPushConstants {
+    fn default() -> Self {
+        MeshPushConstants {
+            transform: Matrix4::identity(),
+            base_color: [1.0; 4],
+        }
     }
 }
 
-#[repr(C)]
+/// One draw call of `DrawCommand::IndexedMeshes`.
 #[derive(Debug, Clone, Copy)]
-pub struct VertexV2 {
+pub struct MeshDraw {
+    pub first_index: u32,
+    pub index_count: u32,
+    pub push_constants: MeshPushConstants,
+}
+
+/// What a `DrawItem` draws once its pipeline and descriptor sets are bound.
+#[derive(Debug, Clone)]
+pub enum DrawCommand {
+    Indexed {
+        vertex_buffer: vk::Buffer,
+        index_buffer: vk::Buffer,
+        index_count: u32,
+    },
+    /// Ranges of one index buffer drawn one after another, each with its own push constants.
+    /// The pipeline layout needs `MeshPushConstants::push_constant_range`.
+    IndexedMeshes {
+        vertex_buffer: vk::Buffer,
+        index_buffer: vk::Buffer,
+        draws: Vec<MeshDraw>,
+    },
+    /// A single triangle covering the viewport, generated from `gl_VertexIndex` without
+    /// any vertex buffer.
+    FullscreenTriangle,
+}
+
+/// Draw calls of one pipeline, recorded into a frame's command buffer by
+/// `share::pipeline::cmd_draw_items`.
+#[derive(Debug, Clone)]
+pub struct DrawItem {
+    pub pipeline: vk::Pipeline,
+    pub pipeline_layout: vk::PipelineLayout,
+    /// Bound starting at set 0.
+    pub descriptor_sets: Vec<vk::DescriptorSet>,
+    pub command: DrawCommand,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Vertex)]
+pub struct VertexV1 {
+    #[location(0)]
     pub pos: [f32; 2],
+    #[location(1)]
     pub color: [f32; 3],
-    pub tex_coord: [f32; 2],
 }
-impl VertexV2 {
-    pub fn get_binding_descriptions() -> [vk::VertexInputBindingDescription; 1] {
-        [vk::VertexInputBindingDescription {
-            binding: 0,
-            stride: ::std::mem::size_of::<Self>() as u32,
-            input_rate: vk::VertexInputRate::VERTEX,
-        }]
-    }
 
-    pub fn get_attribute_descriptions() -> [vk::VertexInputAttributeDescription; 3] {
-        [
-            vk::VertexInputAttributeDescription {
-                binding: 0,
-                location: 0,
-                format: vk::Format::R32G32_SFLOAT,
-                offset: offset_of!(Self, pos) as u32,
-            },
-            vk::VertexInputAttributeDescription {
-                binding: 0,
-                location: 1,
-                format: vk::Format::R32G32B32_SFLOAT,
-                offset: offset_of!(Self, color) as u32,
-            },
-            vk::VertexInputAttributeDescription {
-                binding: 0,
-                location: 2,
-                format: vk::Format::R32G32_SFLOAT,
-                offset: offset_of!(Self, tex_coord) as u32,
-            },
-        ]
-    }
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Vertex)]
+pub struct VertexV2 {
+    #[location(0)]
+    pub pos: [f32; 2],
+    #[location(1)]
+    pub color: [f32; 3],
+    #[location(2)]
+    pub tex_coord: [f32; 2],
 }
 
 #[repr(C)]
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, Vertex)]
 pub struct VertexV3 {
+    #[location(0)]
     pub pos: [f32; 4],
+    #[location(1)]
     pub color: [f32; 4],
+    #[location(2)]
     pub tex_coord: [f32; 2],
+    #[location(3)]
+    pub normal: [f32; 3],
+    /// xyz is the tangent, w the handedness of the bitangent.
+    #[location(4)]
+    pub tangent: [f32; 4],
 }
-impl VertexV3 {
-    pub fn get_binding_descriptions() -> [vk::VertexInputBindingDescription; 1] {
-        [vk::VertexInputBindingDescription {
-            binding: 0,
-            stride: ::std::mem::size_of::<Self>() as u32,
-            input_rate: vk::VertexInputRate::VERTEX,
-        }]
-    }
 
-    pub fn get_attribute_descriptions() -> [vk::VertexInputAttributeDescription; 3] {
-        [
-            vk::VertexInputAttributeDescription {
-                binding: 0,
-                location: 0,
-                format: vk::Format::R32G32B32A32_SFLOAT,
-                offset: offset_of!(Self, pos) as u32,
-            },
-            vk::VertexInputAttributeDescription {
-                binding: 0,
-                location: 1,
-                format: vk::Format::R32G32B32A32_SFLOAT,
-                offset: offset_of!(Self, color) as u32,
-            },
-            vk::VertexInputAttributeDescription {
-                binding: 0,
-                location: 2,
-                format: vk::Format::R32G32_SFLOAT,
-                offset: offset_of!(Self, tex_coord) as u32,
-            },
-        ]
-    }
+/// Meshes of a model file (OBJ or glTF) merged into a single vertex and index buffer.
+#[derive(Debug, Clone)]
+pub struct Model {
+    pub vertices: Vec<VertexV3>,
+    pub indices: Vec<u32>,
+    pub meshes: Vec<MeshRange>,
+    pub materials: Vec<Material>,
+}
+
+/// Part of `Model::indices` drawn with one material. The indices already point at the
+/// mesh's vertices inside `Model::vertices`, so no vertex offset is needed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MeshRange {
+    pub first_index: u32,
+    pub index_count: u32,
+    /// Index into `Model::materials`.
+    pub material_index: Option<usize>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Material {
+    pub name: String,
+    pub diffuse_color: [f32; 3],
+    pub diffuse_texture: Option<TextureSource>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TextureSource {
+    /// Image file for `create_texture_image`, resolved against the directory of the model.
+    Path(PathBuf),
+    /// Decoded RGBA8 rows, top row first, for `create_texture_image_from_pixels`.
+    Rgba {
+        width: u32,
+        height: u32,
+        pixels: Vec<u8>,
+    },
 }
 
 pub const RECT_VERTICES_DATA: [VertexV2; 4] = [
@@ -1,3 +1,8 @@
+use crate::error::{Error, Result};
+#[cfg(feature = "runtime-shaders")]
+use crate::utility::shadertoy;
+use crate::utility::specialization::SpecializationConstants;
+
 use std::ffi::CStr;
 use std::os::raw::c_char;
 use std::path::Path;
@@ -15,13 +20,89 @@ pub fn vk_to_string(raw_string_array: &[c_char]) -> String {
         .to_owned()
 }
 
-pub fn read_shader_code(shader_path: &Path) -> Vec<u8> {
-    use std::fs::File;
-    use std::io::Read;
+/// Reads SPIR-V bytecode for a shader module.
+///
+/// `.vert` and `.frag` files are treated as GLSL source and compiled in-process when the
+/// `runtime-shaders` feature is enabled; any other file is loaded as precompiled SPIR-V.
+/// Fragment sources written for Shadertoy (defining `mainImage`) are wrapped with the
+/// Shadertoy prelude first.
+pub fn read_shader_code(shader_path: &Path) -> Result<Vec<u8>> {
+    read_specialized_shader_code(shader_path, &SpecializationConstants::new())
+}
+
+/// Same as `read_shader_code`, but GLSL compiled in-process gets its specialization
+/// constants replaced by `constants`, since the compiler cannot emit them as SPIR-V
+/// specialization constants. Precompiled SPIR-V is specialized when creating the pipeline.
+pub fn read_specialized_shader_code(
+    shader_path: &Path,
+    constants: &SpecializationConstants,
+) -> Result<Vec<u8>> {
+    let read_error = |source| Error::Io {
+        path: shader_path.to_path_buf(),
+        source,
+    };
+
+    match shader_path
+        .extension()
+        .and_then(|extension| extension.to_str())
+    {
+        Some("vert") | Some("frag") => {
+            let source = std::fs::read_to_string(shader_path).map_err(read_error)?;
+            compile_shader_source(shader_path, &source, constants)
+        }
+        _ => std::fs::read(shader_path).map_err(read_error),
+    }
+}
+
+#[cfg(feature = "runtime-shaders")]
+fn compile_shader_source(
+    shader_path: &Path,
+    source: &str,
+    constants: &SpecializationConstants,
+) -> Result<Vec<u8>> {
+    let stage = match shader_path
+        .extension()
+        .and_then(|extension| extension.to_str())
+    {
+        Some("vert") => naga::ShaderStage::Vertex,
+        _ => naga::ShaderStage::Fragment,
+    };
 
-    let spv_file =
-        File::open(shader_path).expect(&format!("Failed to find spv file at {:?}", shader_path));
-    let bytes_code: Vec<u8> = spv_file.bytes().filter_map(|byte| byte.ok()).collect();
+    if stage == naga::ShaderStage::Fragment && shadertoy::is_shadertoy_source(source) {
+        let wrapped_source = shadertoy::wrap_source(source);
+        return super::shader_compiler::compile_glsl(
+            shader_path,
+            &wrapped_source,
+            stage,
+            constants,
+        )
+        .map_err(|err| match err {
+            Error::ShaderCompile {
+                path,
+                line,
+                column,
+                message,
+            } => Error::ShaderCompile {
+                path,
+                line: line.map(shadertoy::source_line),
+                column,
+                message,
+            },
+            err => err,
+        });
+    }
+
+    super::shader_compiler::compile_glsl(shader_path, source, stage, constants)
+}
 
-    bytes_code
+#[cfg(not(feature = "runtime-shaders"))]
+fn compile_shader_source(
+    shader_path: &Path,
+    _source: &str,
+    _constants: &SpecializationConstants,
+) -> Result<Vec<u8>> {
+    Err(Error::UnsupportedShader {
+        path: shader_path.to_path_buf(),
+        reason: "GLSL source requires the `runtime-shaders` feature; pass a .spv file instead",
+    })
 }
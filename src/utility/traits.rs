@@ -1,8 +1,21 @@
+use crate::error::Result;
+
+use std::path::PathBuf;
+
 pub trait VulkanApp {
-    fn draw_frame(&mut self, delta_time: f32);
-    fn recreate_swapchain(&mut self);
-    fn cleanup_swapchain(&self);
+    fn draw_frame(&mut self, delta_time: f32) -> Result<()>;
+    fn recreate_swapchain(&mut self) -> Result<()>;
+    fn cleanup_swapchain(&mut self);
     fn wait_device_idle(&self);
     fn resize_framebuffer(&mut self);
     fn window_ref(&self) -> &winit::window::Window;
+    /// Shader files watched for changes by `ProgramProc::main_loop`.
+    fn shader_paths(&self) -> Vec<PathBuf>;
+    /// Rebuilds the graphics pipeline from the current shader files. On error the
+    /// previous pipeline must stay in use.
+    fn reload_shaders(&mut self) -> Result<()>;
+    /// Cursor position in window pixels, with the origin in the top-left corner.
+    fn cursor_moved(&mut self, _x: f64, _y: f64) {}
+    /// Left mouse button was pressed or released.
+    fn mouse_button_changed(&mut self, _is_pressed: bool) {}
 }
@@ -0,0 +1,67 @@
+use ash::vk;
+use cgmath::{Vector2, Vector3, Vector4};
+
+// Re-exported so `#[derive(Vertex)]` works in crates that do not depend on ash directly.
+pub use ash::vk::{VertexInputAttributeDescription, VertexInputBindingDescription};
+pub use rust_game_derive::Vertex;
+
+/// Vertex types that can be uploaded with `create_vertex_buffer` and fed to a pipeline.
+///
+/// Usually derived with `#[derive(Vertex)]`, which reads a `#[location(N)]` attribute on
+/// every field. All attributes come from a single, per-vertex binding 0.
+pub trait Vertex: Copy {
+    fn binding_descriptions() -> Vec<VertexInputBindingDescription> {
+        vec![VertexInputBindingDescription {
+            binding: 0,
+            stride: std::mem::size_of::<Self>() as u32,
+            input_rate: vk::VertexInputRate::VERTEX,
+        }]
+    }
+
+    fn attribute_descriptions() -> Vec<VertexInputAttributeDescription>;
+}
+
+/// No vertex input at all, for geometry generated in the vertex shader such as the
+/// fullscreen triangle.
+impl Vertex for () {
+    fn binding_descriptions() -> Vec<VertexInputBindingDescription> {
+        vec![]
+    }
+
+    fn attribute_descriptions() -> Vec<VertexInputAttributeDescription> {
+        vec![]
+    }
+}
+
+/// Vulkan format of a vertex field type.
+pub trait VertexFormat {
+    const FORMAT: vk::Format;
+}
+
+macro_rules! impl_vertex_format {
+    ($($field_type:ty => $format:ident,)*) => {
+        $(
+            impl VertexFormat for $field_type {
+                const FORMAT: vk::Format = vk::Format::$format;
+            }
+        )*
+    };
+}
+
+impl_vertex_format! {
+    f32 => R32_SFLOAT,
+    [f32; 2] => R32G32_SFLOAT,
+    [f32; 3] => R32G32B32_SFLOAT,
+    [f32; 4] => R32G32B32A32_SFLOAT,
+    Vector2<f32> => R32G32_SFLOAT,
+    Vector3<f32> => R32G32B32_SFLOAT,
+    Vector4<f32> => R32G32B32A32_SFLOAT,
+    i32 => R32_SINT,
+    [i32; 2] => R32G32_SINT,
+    [i32; 3] => R32G32B32_SINT,
+    [i32; 4] => R32G32B32A32_SINT,
+    u32 => R32_UINT,
+    [u32; 2] => R32G32_UINT,
+    [u32; 3] => R32G32B32_UINT,
+    [u32; 4] => R32G32B32A32_UINT,
+}
@@ -0,0 +1,63 @@
+use rust_game::utility::allocator::{Allocation, AllocatorStats, SubAllocator};
+
+#[test]
+fn aligns_offsets() {
+    let mut sub_allocator = SubAllocator::new(1024);
+
+    assert_eq!(sub_allocator.allocate(10, 4), Some(0));
+    assert_eq!(sub_allocator.allocate(100, 256), Some(256));
+    assert_eq!(sub_allocator.allocate(8, 8), Some(16));
+    assert_eq!(sub_allocator.allocation_count(), 3);
+    assert_eq!(sub_allocator.used_bytes(), 118);
+}
+
+#[test]
+fn reuses_freed_ranges_first_fit() {
+    let mut sub_allocator = SubAllocator::new(300);
+    let first = sub_allocator.allocate(100, 1).unwrap();
+    let second = sub_allocator.allocate(100, 1).unwrap();
+    let third = sub_allocator.allocate(100, 1).unwrap();
+    assert_eq!(sub_allocator.allocate(1, 1), None);
+
+    assert!(sub_allocator.free(second));
+    assert_eq!(sub_allocator.allocate(150, 1), None);
+    assert_eq!(sub_allocator.allocate(60, 1), Some(100));
+    assert_eq!(sub_allocator.allocate(40, 1), Some(160));
+
+    assert!(sub_allocator.free(first));
+    assert!(sub_allocator.free(third));
+    assert_eq!(sub_allocator.used_bytes(), 100);
+    assert_eq!(sub_allocator.allocate(100, 1), Some(0));
+}
+
+#[test]
+fn rejects_unknown_frees_and_oversized_allocations() {
+    let mut sub_allocator = SubAllocator::new(64);
+
+    assert_eq!(sub_allocator.allocate(65, 1), None);
+    let offset = sub_allocator.allocate(16, 16).unwrap();
+    assert!(!sub_allocator.free(offset + 1));
+    assert!(sub_allocator.free(offset));
+    assert!(!sub_allocator.free(offset));
+    assert!(sub_allocator.is_empty());
+}
+
+#[test]
+fn null_allocation_is_not_mapped() {
+    assert!(Allocation::null().mapped_ptr().is_err());
+}
+
+#[test]
+fn formats_stats() {
+    let stats = AllocatorStats {
+        block_count: 2,
+        block_bytes: 128 * 1024 * 1024,
+        allocation_count: 5,
+        allocated_bytes: 3 * 1024 * 1024 / 2,
+    };
+
+    assert_eq!(
+        stats.to_string(),
+        "5 allocations (1.5 MiB) in 2 blocks (128.0 MiB)"
+    );
+}
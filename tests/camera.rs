@@ -0,0 +1,57 @@
+use ash::vk;
+use cgmath::{Deg, Point3, Vector3, Vector4};
+use rust_game::utility::camera::Camera;
+
+const CAMERA: Camera = Camera {
+    eye: Point3::new(0.0, 0.0, 2.0),
+    target: Point3::new(0.0, 0.0, 0.0),
+    up: Vector3::new(0.0, 1.0, 0.0),
+    fov_y: Deg(90.0),
+    z_near: 1.0,
+    z_far: 3.0,
+};
+
+fn to_clip(camera: &Camera, extent: vk::Extent2D, point: [f32; 3]) -> [f32; 3] {
+    let clip =
+        camera.projection(extent) * camera.view() * Vector4::new(point[0], point[1], point[2], 1.0);
+    [clip.x / clip.w, clip.y / clip.w, clip.z / clip.w]
+}
+
+fn assert_close(actual: [f32; 3], expected: [f32; 3]) {
+    for (actual, expected) in actual.iter().zip(expected.iter()) {
+        assert!(
+            (actual - expected).abs() < 1e-5,
+            "{:?} != {:?}",
+            actual,
+            expected
+        );
+    }
+}
+
+#[test]
+fn projection_maps_to_vulkan_clip_space() {
+    let extent = vk::Extent2D {
+        width: 600,
+        height: 600,
+    };
+
+    // Up in the world is towards the top of the framebuffer, where Vulkan's Y is negative.
+    assert_close(to_clip(&CAMERA, extent, [0.0, 1.0, 1.0]), [0.0, -1.0, 0.0]);
+    // The far plane ends at depth 1 instead of OpenGL's -1..1 range.
+    assert_close(to_clip(&CAMERA, extent, [0.0, 0.0, -1.0]), [0.0, 0.0, 1.0]);
+}
+
+#[test]
+fn projection_follows_the_aspect_ratio() {
+    let square = vk::Extent2D {
+        width: 600,
+        height: 600,
+    };
+    let wide = vk::Extent2D {
+        width: 1200,
+        height: 600,
+    };
+
+    assert_close(to_clip(&CAMERA, square, [1.0, 0.0, 1.0]), [1.0, 0.0, 0.0]);
+    assert_close(to_clip(&CAMERA, wide, [1.0, 0.0, 1.0]), [0.5, 0.0, 0.0]);
+}
@@ -0,0 +1,95 @@
+use ash::vk;
+use rust_game::utility::capability_report::{
+    device_features, device_limits, version_string, DeviceReport, ExtensionReport,
+    MemoryHeapReport, QueueFamilyReport,
+};
+
+fn device_report() -> DeviceReport {
+    DeviceReport {
+        index: 1,
+        name: "llvmpipe".to_owned(),
+        device_type: "CPU".to_owned(),
+        vendor_id: 0x10005,
+        device_id: 0,
+        api_version: vk::make_version(1, 2, 131),
+        driver_version: 1,
+        required_extensions_supported: true,
+        extensions: vec![ExtensionReport {
+            name: "VK_KHR_swapchain".to_owned(),
+            spec_version: 70,
+        }],
+        limits: device_limits(&vk::PhysicalDeviceLimits {
+            max_push_constants_size: 128,
+            framebuffer_color_sample_counts: vk::SampleCountFlags::TYPE_1
+                | vk::SampleCountFlags::TYPE_4,
+            ..Default::default()
+        }),
+        features: device_features(&vk::PhysicalDeviceFeatures {
+            sampler_anisotropy: vk::TRUE,
+            ..Default::default()
+        }),
+        memory_heaps: vec![MemoryHeapReport {
+            size: 512 << 20,
+            flags: "DEVICE_LOCAL".to_owned(),
+        }],
+        memory_types: vec![],
+        queue_families: vec![QueueFamilyReport {
+            queue_count: 1,
+            flags: "GRAPHICS | COMPUTE | TRANSFER".to_owned(),
+            timestamp_valid_bits: 64,
+            present_support: None,
+        }],
+        formats: vec![],
+        depth_format: Some("D32_SFLOAT".to_owned()),
+        swapchain: None,
+    }
+}
+
+#[test]
+fn lists_every_feature() {
+    let features = device_features(&vk::PhysicalDeviceFeatures {
+        geometry_shader: vk::TRUE,
+        ..Default::default()
+    });
+
+    assert_eq!(features.len(), 55);
+    assert!(features.contains(&("geometry_shader", true)));
+    assert!(features.contains(&("inherited_queries", false)));
+}
+
+#[test]
+fn formats_versions() {
+    assert_eq!(version_string(vk::make_version(1, 2, 131)), "1.2.131");
+}
+
+#[test]
+fn prints_device_as_text() {
+    let text = device_report().to_string();
+
+    assert!(text.starts_with("GPU 1: llvmpipe\n  Type: CPU\n  ID: 10005:0000\n"));
+    assert!(text.contains("  API version: 1.2.131\n"));
+    assert!(text.contains("    VK_KHR_swapchain v70\n"));
+    assert!(text.contains("    max_push_constants_size: 128\n"));
+    assert!(text.contains("    framebuffer_color_sample_counts: TYPE_1 | TYPE_4\n"));
+    assert!(text.contains("    framebuffer_depth_sample_counts: none\n"));
+    assert!(text.contains("    sampler_anisotropy: yes\n"));
+    assert!(text.contains("    0: 512.0 MiB, DEVICE_LOCAL\n"));
+    assert!(text.contains("    0: 1 queue(s), GRAPHICS | COMPUTE | TRANSFER, 64 timestamp bits\n"));
+    assert!(text.contains("  Depth format: D32_SFLOAT\n"));
+    assert!(text.ends_with("  Swapchain: no surface\n"));
+}
+
+#[test]
+fn prints_device_as_json() {
+    let json = device_report().to_json();
+
+    assert_eq!(json["vendor_id"], "10005");
+    assert_eq!(json["api_version"], "1.2.131");
+    assert_eq!(json["extensions"]["VK_KHR_swapchain"], 70);
+    assert_eq!(json["limits"]["max_push_constants_size"], 128);
+    assert_eq!(json["limits"]["max_compute_work_group_size"][2], 0);
+    assert_eq!(json["features"]["sampler_anisotropy"], true);
+    assert_eq!(json["memory_heaps"][0]["size"], 512 << 20);
+    assert!(json["queue_families"][0]["present_support"].is_null());
+    assert!(json["swapchain"].is_null());
+}
@@ -0,0 +1,204 @@
+// Every test crate including this module uses only part of it.
+#![allow(dead_code)]
+
+use rust_game::utility::{
+    debug::ValidationInfo, device_selection::DeviceSelector, headless::HeadlessRenderer,
+    structures::UniformBufferObject,
+};
+
+use std::env;
+use std::path::{Path, PathBuf};
+
+pub const GOLDEN_WIDTH: u32 = 256;
+pub const GOLDEN_HEIGHT: u32 = 192;
+
+pub struct GoldenCase<'a> {
+    /// Reference image name, resolved to `tests/golden/<name>.png`.
+    pub name: &'a str,
+    pub vert_shader: &'a str,
+    pub frag_shader: &'a str,
+    pub frame_count: u32,
+    /// `u_time` is kept at this value for every rendered frame.
+    pub u_time: f32,
+    /// Maximum allowed per-channel difference for a pixel to still count as equal.
+    pub tolerance: u8,
+}
+
+pub struct Comparison {
+    pub mismatched_pixels: u32,
+    pub max_difference: u8,
+    pub diff_image: image::RgbaImage,
+}
+
+/// Directory `dir` below the integration test scratch directory, created if missing.
+pub fn fixture_dir(dir: &str) -> PathBuf {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join(dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// Writes `contents` to `name` in `fixture_dir(dir)` and returns its path.
+pub fn write_fixture(dir: &str, name: &str, contents: impl AsRef<[u8]>) -> PathBuf {
+    let path = fixture_dir(dir).join(name);
+    std::fs::write(&path, contents).unwrap();
+    path
+}
+
+fn manifest_path(relative: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join(relative)
+}
+
+fn is_env_flag_set(name: &str) -> bool {
+    env::var(name).map(|value| value == "1").unwrap_or(false)
+}
+
+/// Compares two images channel by channel. Mismatched pixels are painted red in the
+/// diff image, matching ones are kept as a dimmed copy of the expected image.
+pub fn compare_images(
+    actual: &image::RgbaImage,
+    expected: &image::RgbaImage,
+    tolerance: u8,
+) -> Comparison {
+    assert_eq!(
+        actual.dimensions(),
+        expected.dimensions(),
+        "Compared images have different sizes"
+    );
+
+    let mut mismatched_pixels = 0;
+    let mut max_difference = 0;
+    let mut diff_image = image::RgbaImage::new(actual.width(), actual.height());
+
+    for (x, y, expected_pixel) in expected.enumerate_pixels() {
+        let actual_pixel = actual.get_pixel(x, y);
+        let difference = actual_pixel
+            .0
+            .iter()
+            .zip(expected_pixel.0.iter())
+            .map(|(&a, &e)| a.abs_diff(e))
+            .max()
+            .unwrap_or(0);
+
+        max_difference = max_difference.max(difference);
+        let diff_pixel = if difference > tolerance {
+            mismatched_pixels += 1;
+            image::Rgba([255, 0, 0, 255])
+        } else {
+            let [r, g, b, _] = expected_pixel.0;
+            image::Rgba([r / 4, g / 4, b / 4, 255])
+        };
+        diff_image.put_pixel(x, y, diff_pixel);
+    }
+
+    Comparison {
+        mismatched_pixels,
+        max_difference,
+        diff_image,
+    }
+}
+
+/// Renders `case` offscreen and checks it against its reference image.
+///
+/// The test is skipped when no Vulkan device is available, unless `REQUIRE_VULKAN=1`.
+/// With `UPDATE_GOLDEN=1` the rendered frame replaces the stored reference.
+/// On mismatch the rendered frame and a diff image are written to `target/golden/`.
+pub fn assert_matches_golden(case: &GoldenCase) {
+    let vert_shader_path = manifest_path(case.vert_shader);
+    let frag_shader_path = manifest_path(case.frag_shader);
+
+    let missing_shader = [&vert_shader_path, &frag_shader_path]
+        .iter()
+        .find(|path| !path.exists())
+        .map(|path| format!("missing shader {:?}", path));
+    let skip_reason = if !HeadlessRenderer::is_available() {
+        Some("no Vulkan device available".to_owned())
+    } else {
+        missing_shader
+    };
+    if let Some(skip_reason) = skip_reason {
+        if is_env_flag_set("REQUIRE_VULKAN") {
+            panic!("Golden test '{}' cannot run: {}", case.name, skip_reason);
+        }
+        eprintln!("Skipping golden test '{}': {}", case.name, skip_reason);
+        return;
+    }
+
+    let mut renderer = HeadlessRenderer::new(
+        GOLDEN_WIDTH,
+        GOLDEN_HEIGHT,
+        // Validation errors fail the test. Without the layers installed the run only warns.
+        &ValidationInfo {
+            fail_on_error: true,
+            ..ValidationInfo::default()
+        },
+        DeviceSelector::from_env(),
+        &vert_shader_path,
+        &frag_shader_path,
+    )
+    .unwrap_or_else(|err| panic!("Failed to create headless renderer: {}", err));
+    let ubo = UniformBufferObject {
+        u_time: case.u_time,
+        ..Default::default()
+    };
+    for _ in 0..case.frame_count {
+        renderer
+            .render_frame(&ubo)
+            .unwrap_or_else(|err| panic!("Failed to render frame: {}", err));
+    }
+    let actual = renderer
+        .capture()
+        .unwrap_or_else(|err| panic!("Failed to capture frame: {}", err));
+    renderer
+        .messenger()
+        .check()
+        .unwrap_or_else(|err| panic!("Golden test '{}' failed: {}", case.name, err));
+
+    let reference_path = manifest_path(&format!("tests/golden/{}.png", case.name));
+    if is_env_flag_set("UPDATE_GOLDEN") {
+        actual
+            .save(&reference_path)
+            .expect("Failed to write reference image");
+        eprintln!("Updated reference image {:?}", reference_path);
+        return;
+    }
+
+    let output_dir = manifest_path("target/golden");
+    std::fs::create_dir_all(&output_dir).expect("Failed to create golden output directory");
+    let actual_path = output_dir.join(format!("{}.actual.png", case.name));
+
+    let expected = match image::open(&reference_path) {
+        Ok(expected) => expected.to_rgba(),
+        Err(_) => {
+            actual
+                .save(&actual_path)
+                .expect("Failed to write rendered image");
+            panic!(
+                "No reference image at {:?}; rendered frame written to {:?}. \
+                 Rerun with UPDATE_GOLDEN=1 to accept it.",
+                reference_path, actual_path
+            );
+        }
+    };
+
+    let comparison = compare_images(&actual, &expected, case.tolerance);
+    if comparison.mismatched_pixels > 0 {
+        let diff_path = output_dir.join(format!("{}.diff.png", case.name));
+        actual
+            .save(&actual_path)
+            .expect("Failed to write rendered image");
+        comparison
+            .diff_image
+            .save(&diff_path)
+            .expect("Failed to write diff image");
+        panic!(
+            "Golden test '{}' failed: {} pixel(s) differ by more than {} (max difference {}). \
+             See {:?} and {:?}.",
+            case.name,
+            comparison.mismatched_pixels,
+            case.tolerance,
+            comparison.max_difference,
+            actual_path,
+            diff_path
+        );
+    }
+}
@@ -0,0 +1,127 @@
+use ash::vk;
+use rust_game::utility::{
+    debug::{DebugSinkKind, ValidationInfo},
+    debug_messenger::{DebugCounts, DebugMessage, DebugMessenger, DebugSeverity, MemorySink},
+};
+use rust_game::Error;
+use std::ffi::CString;
+use std::ptr;
+use std::sync::Arc;
+
+fn message(severity: DebugSeverity, text: &str) -> DebugMessage {
+    DebugMessage {
+        severity,
+        message_type: vk::DebugUtilsMessageTypeFlagsEXT::VALIDATION,
+        message_id_name: None,
+        message_id_number: 0,
+        message: text.to_owned(),
+        objects: vec![],
+        queue_labels: vec![],
+        command_buffer_labels: vec![],
+    }
+}
+
+#[test]
+fn reads_callback_data() {
+    let id_name = CString::new("VUID-vkCmdDraw-None-02859").unwrap();
+    let text = CString::new("Image layout mismatch").unwrap();
+    let object_name = CString::new("depth image").unwrap();
+    let label_name = CString::new("main pass").unwrap();
+    let objects = [
+        vk::DebugUtilsObjectNameInfoEXT {
+            object_type: vk::ObjectType::IMAGE,
+            object_handle: 0x55d3,
+            p_object_name: object_name.as_ptr(),
+            ..Default::default()
+        },
+        vk::DebugUtilsObjectNameInfoEXT {
+            object_type: vk::ObjectType::COMMAND_BUFFER,
+            object_handle: 0x42,
+            p_object_name: ptr::null(),
+            ..Default::default()
+        },
+    ];
+    let labels = [vk::DebugUtilsLabelEXT {
+        p_label_name: label_name.as_ptr(),
+        ..Default::default()
+    }];
+    let callback_data = vk::DebugUtilsMessengerCallbackDataEXT {
+        p_message_id_name: id_name.as_ptr(),
+        message_id_number: 0x1234,
+        p_message: text.as_ptr(),
+        object_count: objects.len() as u32,
+        p_objects: objects.as_ptr(),
+        cmd_buf_label_count: labels.len() as u32,
+        p_cmd_buf_labels: labels.as_ptr(),
+        ..Default::default()
+    };
+
+    let message = unsafe {
+        DebugMessage::from_callback_data(
+            vk::DebugUtilsMessageSeverityFlagsEXT::ERROR,
+            vk::DebugUtilsMessageTypeFlagsEXT::VALIDATION,
+            &callback_data,
+        )
+    };
+
+    assert_eq!(message.severity, DebugSeverity::Error);
+    assert_eq!(
+        message.message_id_name.as_deref(),
+        Some("VUID-vkCmdDraw-None-02859")
+    );
+    assert_eq!(message.objects[0].name.as_deref(), Some("depth image"));
+    assert_eq!(message.objects[1].name, None);
+    assert!(message.queue_labels.is_empty());
+    assert_eq!(message.to_string(), "[Error][VALIDATION] VUID-vkCmdDraw-None-02859 (0x1234) Image layout mismatch\n    object: IMAGE 0x55d3 'depth image'\n    object: COMMAND_BUFFER 0x42\n    command buffer labels: main pass");
+}
+
+#[test]
+fn counts_and_collects_messages() {
+    let sink = Arc::new(MemorySink::default());
+    let messenger = DebugMessenger::new(vec![sink.clone()], false);
+
+    messenger.emit(&message(DebugSeverity::Warning, "first"));
+    messenger.emit(&message(DebugSeverity::Error, "second"));
+    messenger.emit(&message(DebugSeverity::Warning, "third"));
+
+    assert_eq!(
+        messenger.counts(),
+        DebugCounts {
+            verbose: 0,
+            info: 0,
+            warning: 2,
+            error: 1,
+        }
+    );
+    let texts: Vec<String> = messenger
+        .messages()
+        .into_iter()
+        .map(|message| message.message)
+        .collect();
+    assert_eq!(texts, vec!["first", "second", "third"]);
+    // Errors are only fatal in fail on error mode.
+    assert!(messenger.check().is_ok());
+}
+
+#[test]
+fn fails_on_error_when_asked_to() {
+    let validation = ValidationInfo {
+        sink: DebugSinkKind::Memory,
+        fail_on_error: true,
+        ..ValidationInfo::default()
+    };
+    let messenger = DebugMessenger::from_validation(&validation);
+
+    messenger.emit(&message(DebugSeverity::Warning, "not fatal"));
+    assert!(messenger.check().is_ok());
+
+    messenger.emit(&message(DebugSeverity::Error, "fatal"));
+    match messenger.check() {
+        Err(err @ Error::ValidationFailed(_)) => assert_eq!(
+            err.to_string(),
+            "Validation reported 1 errors, 1 warnings, 0 info, 0 verbose"
+        ),
+        other => panic!("expected a validation failure, got {:?}", other),
+    }
+    assert_eq!(messenger.messages().len(), 2);
+}
@@ -0,0 +1,126 @@
+use ash::vk;
+use rust_game::utility::device_selection::{DeviceScore, DeviceSelector};
+
+fn device_properties(
+    name: &str,
+    device_type: vk::PhysicalDeviceType,
+) -> vk::PhysicalDeviceProperties {
+    let mut properties = vk::PhysicalDeviceProperties {
+        vendor_id: 0x10de,
+        device_id: 0x1c82,
+        device_type,
+        ..Default::default()
+    };
+    for (dst, &src) in properties.device_name.iter_mut().zip(name.as_bytes()) {
+        *dst = src as _;
+    }
+    properties
+}
+
+fn memory_properties(
+    device_local_heap_sizes: &[vk::DeviceSize],
+) -> vk::PhysicalDeviceMemoryProperties {
+    let mut memory_properties = vk::PhysicalDeviceMemoryProperties {
+        memory_heap_count: device_local_heap_sizes.len() as u32 + 1,
+        ..Default::default()
+    };
+    for (heap, &size) in memory_properties
+        .memory_heaps
+        .iter_mut()
+        .zip(device_local_heap_sizes)
+    {
+        heap.size = size;
+        heap.flags = vk::MemoryHeapFlags::DEVICE_LOCAL;
+    }
+    // Host memory is not counted.
+    memory_properties.memory_heaps[device_local_heap_sizes.len()].size = 1 << 40;
+    memory_properties
+}
+
+fn score(
+    device_type: vk::PhysicalDeviceType,
+    vram: vk::DeviceSize,
+    features: vk::Bool32,
+) -> DeviceScore {
+    DeviceScore::new(
+        &device_properties("GPU", device_type),
+        &memory_properties(&[vram]),
+        &vk::PhysicalDeviceFeatures {
+            sample_rate_shading: features,
+            ..Default::default()
+        },
+    )
+}
+
+#[test]
+fn parses_selectors() {
+    assert_eq!(DeviceSelector::parse("1"), DeviceSelector::Index(1));
+    assert_eq!(
+        DeviceSelector::parse("10de:1c82"),
+        DeviceSelector::Id {
+            vendor_id: 0x10de,
+            device_id: 0x1c82
+        }
+    );
+    assert_eq!(
+        DeviceSelector::parse("0x1002:0x73bf"),
+        DeviceSelector::Id {
+            vendor_id: 0x1002,
+            device_id: 0x73bf
+        }
+    );
+    assert_eq!(
+        DeviceSelector::parse(" GeForce "),
+        DeviceSelector::Name("GeForce".to_owned())
+    );
+    assert_eq!(
+        DeviceSelector::parse("llvmpipe:x"),
+        DeviceSelector::Name("llvmpipe:x".to_owned())
+    );
+}
+
+#[test]
+fn matches_by_index_name_or_id() {
+    let properties = device_properties(
+        "NVIDIA GeForce GTX 1050 Ti",
+        vk::PhysicalDeviceType::DISCRETE_GPU,
+    );
+
+    assert!(DeviceSelector::Index(2).matches(2, &properties));
+    assert!(!DeviceSelector::Index(2).matches(0, &properties));
+    assert!(DeviceSelector::parse("geforce gtx").matches(0, &properties));
+    assert!(!DeviceSelector::parse("radeon").matches(0, &properties));
+    assert!(DeviceSelector::parse("10de:1c82").matches(0, &properties));
+    assert!(!DeviceSelector::parse("10de:1c83").matches(0, &properties));
+}
+
+#[test]
+fn ranks_device_type_over_memory_over_features() {
+    let discrete = score(vk::PhysicalDeviceType::DISCRETE_GPU, 2 << 30, vk::FALSE);
+    let integrated = score(vk::PhysicalDeviceType::INTEGRATED_GPU, 16 << 30, vk::TRUE);
+    let virtual_gpu = score(vk::PhysicalDeviceType::VIRTUAL_GPU, 16 << 30, vk::TRUE);
+    let cpu = score(vk::PhysicalDeviceType::CPU, 16 << 30, vk::TRUE);
+    assert!(discrete > integrated);
+    assert!(integrated > virtual_gpu);
+    assert!(virtual_gpu > cpu);
+
+    let larger = score(vk::PhysicalDeviceType::DISCRETE_GPU, 4 << 30, vk::FALSE);
+    let more_features = score(vk::PhysicalDeviceType::DISCRETE_GPU, 2 << 30, vk::TRUE);
+    assert!(larger > more_features);
+    assert!(more_features > discrete);
+}
+
+#[test]
+fn counts_only_device_local_heaps() {
+    let score = DeviceScore::new(
+        &device_properties("GPU", vk::PhysicalDeviceType::DISCRETE_GPU),
+        &memory_properties(&[1 << 30, 256 << 20]),
+        &vk::PhysicalDeviceFeatures::default(),
+    );
+
+    assert_eq!(score.device_local_bytes, (1 << 30) + (256 << 20));
+    assert_eq!(
+        score.to_string(),
+        "discrete, 1.2 GiB device local memory, 0 optional features"
+    );
+}
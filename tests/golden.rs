@@ -0,0 +1,43 @@
+mod common;
+
+use common::{assert_matches_golden, compare_images, GoldenCase};
+use rust_game::utility::constants::{FRAG_SHADER_PATH, VERT_SHADER_PATH};
+
+#[test]
+fn rect_shader_at_time_zero() {
+    assert_matches_golden(&GoldenCase {
+        name: "rect_time_zero",
+        vert_shader: VERT_SHADER_PATH,
+        frag_shader: FRAG_SHADER_PATH,
+        frame_count: 1,
+        u_time: 0.0,
+        tolerance: 2,
+    });
+}
+
+#[test]
+fn rect_shader_at_pinned_time() {
+    assert_matches_golden(&GoldenCase {
+        name: "rect_time_one",
+        vert_shader: VERT_SHADER_PATH,
+        frag_shader: FRAG_SHADER_PATH,
+        frame_count: 3,
+        u_time: 1.0,
+        tolerance: 2,
+    });
+}
+
+#[test]
+fn compare_images_respects_tolerance() {
+    let expected = image::RgbaImage::from_pixel(4, 4, image::Rgba([100, 100, 100, 255]));
+    let mut actual = expected.clone();
+    actual.put_pixel(0, 0, image::Rgba([102, 100, 100, 255]));
+    actual.put_pixel(3, 3, image::Rgba([100, 90, 100, 255]));
+
+    let comparison = compare_images(&actual, &expected, 2);
+
+    assert_eq!(comparison.mismatched_pixels, 1);
+    assert_eq!(comparison.max_difference, 10);
+    assert_eq!(comparison.diff_image.get_pixel(3, 3).0, [255, 0, 0, 255]);
+    assert_ne!(comparison.diff_image.get_pixel(0, 0).0, [255, 0, 0, 255]);
+}
@@ -0,0 +1,159 @@
+mod common;
+
+use common::{fixture_dir, write_fixture};
+use rust_game::utility::share::load_model;
+use rust_game::utility::structures::{MeshRange, TextureSource};
+use std::path::Path;
+
+#[test]
+fn merges_meshes_with_offset_indices() {
+    write_fixture(
+        "model_loading",
+        "two_meshes.mtl",
+        "newmtl red\nKd 1.0 0.0 0.0\n\nnewmtl bricks\nKd 1.0 1.0 1.0\nmap_Kd bricks.png\n",
+    );
+    let path = write_fixture(
+        "model_loading",
+        "two_meshes.obj",
+        "mtllib two_meshes.mtl\n\
+         o first\nv 0 0 0\nv 1 0 0\nv 0 1 0\nvn 0 0 1\nusemtl red\nf 1//1 2//1 3//1\n\
+         o second\nv 0 0 1\nv 1 0 1\nv 1 1 1\nv 0 1 1\nusemtl bricks\nf 4 5 6 7\n",
+    );
+
+    let model = load_model(&path).unwrap_or_else(|err| panic!("{}", err));
+
+    assert_eq!(model.vertices.len(), 7);
+    assert_eq!(model.indices, [0, 1, 2, 3, 4, 5, 3, 5, 6]);
+    assert_eq!(
+        model.meshes,
+        [
+            MeshRange {
+                first_index: 0,
+                index_count: 3,
+                material_index: Some(0),
+            },
+            MeshRange {
+                first_index: 3,
+                index_count: 6,
+                material_index: Some(1),
+            },
+        ]
+    );
+
+    // Missing attributes are zeroed instead of rejected.
+    assert_eq!(model.vertices[0].normal, [0.0, 0.0, 1.0]);
+    assert_eq!(model.vertices[3].normal, [0.0, 0.0, 0.0]);
+    assert_eq!(model.vertices[3].tex_coord, [0.0, 0.0]);
+
+    assert_eq!(model.materials[0].name, "red");
+    assert_eq!(model.materials[0].diffuse_color, [1.0, 0.0, 0.0]);
+    assert_eq!(model.materials[0].diffuse_texture, None);
+    assert_eq!(
+        model.materials[1].diffuse_texture,
+        Some(TextureSource::Path(path.with_file_name("bricks.png")))
+    );
+}
+
+#[test]
+fn loads_bundled_viking_room() {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("assets/viking_room.obj");
+
+    let model = load_model(&path).unwrap_or_else(|err| panic!("{}", err));
+
+    let index_count: u32 = model.meshes.iter().map(|mesh| mesh.index_count).sum();
+    assert_eq!(index_count as usize, model.indices.len());
+    assert!(model
+        .indices
+        .iter()
+        .all(|&index| (index as usize) < model.vertices.len()));
+}
+
+#[test]
+fn loads_gltf_with_node_transforms_and_external_resources() {
+    let dir = fixture_dir("model_loading");
+
+    // Positions then normals of one triangle, followed by u16 indices.
+    let mut buffer = vec![];
+    let floats: [f32; 18] = [
+        0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0, //
+        0.0, 0.0, 1.0, 0.0, 0.0, 1.0, 0.0, 0.0, 1.0,
+    ];
+    for value in floats.iter() {
+        buffer.extend_from_slice(&value.to_le_bytes());
+    }
+    for index in [0u16, 1, 2].iter() {
+        buffer.extend_from_slice(&index.to_le_bytes());
+    }
+    std::fs::write(dir.join("triangle.bin"), &buffer).unwrap();
+    image::RgbImage::from_pixel(1, 1, image::Rgb([255, 0, 0]))
+        .save(dir.join("red.png"))
+        .unwrap();
+
+    let path = write_fixture(
+        "model_loading",
+        "triangle.gltf",
+        r#"{
+            "asset": { "version": "2.0" },
+            "scene": 0,
+            "scenes": [{ "nodes": [0] }],
+            "nodes": [
+                { "translation": [1.0, 0.0, 0.0], "children": [1] },
+                { "scale": [2.0, 2.0, 2.0], "mesh": 0 }
+            ],
+            "meshes": [{
+                "primitives": [{
+                    "attributes": { "POSITION": 0, "NORMAL": 1 },
+                    "indices": 2,
+                    "material": 0
+                }]
+            }],
+            "materials": [{
+                "name": "red",
+                "pbrMetallicRoughness": { "baseColorTexture": { "index": 0 } }
+            }],
+            "textures": [{ "source": 0 }],
+            "images": [{ "uri": "red.png" }],
+            "buffers": [{ "uri": "triangle.bin", "byteLength": 78 }],
+            "bufferViews": [
+                { "buffer": 0, "byteOffset": 0, "byteLength": 72 },
+                { "buffer": 0, "byteOffset": 72, "byteLength": 6 }
+            ],
+            "accessors": [
+                {
+                    "bufferView": 0, "byteOffset": 0, "componentType": 5126, "count": 3,
+                    "type": "VEC3", "min": [0.0, 0.0, 0.0], "max": [1.0, 1.0, 0.0]
+                },
+                {
+                    "bufferView": 0, "byteOffset": 36, "componentType": 5126, "count": 3,
+                    "type": "VEC3"
+                },
+                { "bufferView": 1, "componentType": 5123, "count": 3, "type": "SCALAR" }
+            ]
+        }"#,
+    );
+
+    let model = load_model(&path).unwrap_or_else(|err| panic!("{}", err));
+
+    let positions: Vec<[f32; 4]> = model.vertices.iter().map(|vertex| vertex.pos).collect();
+    assert_eq!(
+        positions,
+        [
+            [1.0, 0.0, 0.0, 1.0],
+            [3.0, 0.0, 0.0, 1.0],
+            [1.0, 2.0, 0.0, 1.0]
+        ]
+    );
+    assert_eq!(model.vertices[0].normal, [0.0, 0.0, 1.0]);
+    assert_eq!(model.vertices[0].tex_coord, [0.0, 0.0]);
+    assert_eq!(model.indices, [0, 1, 2]);
+    assert_eq!(model.meshes[0].material_index, Some(0));
+    assert_eq!(model.materials[0].name, "red");
+    assert_eq!(
+        model.materials[0].diffuse_texture,
+        Some(TextureSource::Rgba {
+            width: 1,
+            height: 1,
+            pixels: vec![255, 0, 0, 255],
+        })
+    );
+}
@@ -0,0 +1,127 @@
+use ash::vk;
+use rust_game::utility::share::pipeline_builder::GraphicsPipelineBuilder;
+use rust_game::utility::specialization::{SpecializationConstants, SpecializationValue};
+use rust_game::Error;
+use std::path::Path;
+
+fn push_constant_range(offset: u32, size: u32) -> vk::PushConstantRange {
+    vk::PushConstantRange {
+        stage_flags: vk::ShaderStageFlags::VERTEX,
+        offset,
+        size,
+    }
+}
+
+#[test]
+fn push_constants_fit_the_guaranteed_size_by_default() {
+    GraphicsPipelineBuilder::new()
+        .check_push_constants()
+        .unwrap_or_else(|err| panic!("{}", err));
+    GraphicsPipelineBuilder::new()
+        .push_constant_range(push_constant_range(64, 64))
+        .check_push_constants()
+        .unwrap_or_else(|err| panic!("{}", err));
+
+    match GraphicsPipelineBuilder::new()
+        .push_constant_range(push_constant_range(0, 64))
+        .push_constant_range(push_constant_range(96, 64))
+        .check_push_constants()
+    {
+        Err(Error::PushConstantsTooLarge { size, max_size }) => {
+            assert_eq!(size, 160);
+            assert_eq!(max_size, 128);
+        }
+        result => panic!("Expected PushConstantsTooLarge, got {:?}", result),
+    }
+}
+
+#[test]
+fn push_constants_are_checked_against_the_device_limit() {
+    let builder = GraphicsPipelineBuilder::new().push_constant_range(push_constant_range(0, 256));
+
+    builder
+        .clone()
+        .max_push_constants_size(256)
+        .check_push_constants()
+        .unwrap_or_else(|err| panic!("{}", err));
+    assert!(matches!(
+        builder.max_push_constants_size(255).check_push_constants(),
+        Err(Error::PushConstantsTooLarge {
+            size: 256,
+            max_size: 255,
+        })
+    ));
+}
+
+#[test]
+fn viewport_and_scissor_are_dynamic_by_default() {
+    let builder = GraphicsPipelineBuilder::new();
+
+    assert_eq!(
+        builder.dynamic_states(),
+        vec![vk::DynamicState::VIEWPORT, vk::DynamicState::SCISSOR]
+    );
+    let (viewport, scissor) = builder.viewport_and_scissor();
+    assert_eq!((viewport.width, viewport.height), (0.0, 0.0));
+    assert_eq!(scissor.extent, vk::Extent2D::default());
+}
+
+#[test]
+fn static_viewport_covers_the_extent() {
+    let extent = vk::Extent2D {
+        width: 640,
+        height: 480,
+    };
+    let builder = GraphicsPipelineBuilder::new().static_viewport(extent);
+
+    assert!(builder.dynamic_states().is_empty());
+    let (viewport, scissor) = builder.viewport_and_scissor();
+    assert_eq!((viewport.x, viewport.y), (0.0, 0.0));
+    assert_eq!((viewport.width, viewport.height), (640.0, 480.0));
+    assert_eq!((viewport.min_depth, viewport.max_depth), (0.0, 1.0));
+    assert_eq!((scissor.offset.x, scissor.offset.y), (0, 0));
+    assert_eq!(scissor.extent, extent);
+}
+
+#[test]
+fn specialization_info_is_omitted_for_stages_without_constants() {
+    let mut constants = SpecializationConstants::new();
+    constants.set(3, SpecializationValue::Int(7));
+    let builder = GraphicsPipelineBuilder::new()
+        .shader(vk::ShaderStageFlags::VERTEX, Path::new("shaders/mesh.vert"))
+        .shader(
+            vk::ShaderStageFlags::FRAGMENT,
+            Path::new("shaders/mesh.frag"),
+        )
+        .specialization(vk::ShaderStageFlags::FRAGMENT, constants)
+        .specialization(
+            vk::ShaderStageFlags::COMPUTE,
+            SpecializationConstants::new(),
+        );
+
+    let specializations = builder.stage_specializations();
+    assert_eq!(specializations.len(), 2);
+    assert!(specializations[0].is_none());
+    let (map_entries, data) = specializations[1]
+        .as_ref()
+        .expect("The fragment stage has constants");
+    assert_eq!(map_entries.len(), 1);
+    assert_eq!(map_entries[0].constant_id, 3);
+    assert_eq!(map_entries[0].offset, 0);
+    assert_eq!(map_entries[0].size, 4);
+    assert_eq!(data, &7i32.to_ne_bytes().to_vec());
+}
+
+#[test]
+fn later_specialization_replaces_earlier_one() {
+    let mut first = SpecializationConstants::new();
+    first.set(0, SpecializationValue::Bool(true));
+    let builder = GraphicsPipelineBuilder::new()
+        .shader(vk::ShaderStageFlags::VERTEX, Path::new("shaders/mesh.vert"))
+        .specialization(vk::ShaderStageFlags::VERTEX, first)
+        .specialization(vk::ShaderStageFlags::VERTEX, SpecializationConstants::new());
+
+    let specializations = builder.stage_specializations();
+    assert_eq!(specializations.len(), 1);
+    assert!(specializations[0].is_none());
+}
@@ -0,0 +1,72 @@
+use ash::vk;
+use rust_game::utility::pipeline_cache::is_compatible_cache_data;
+
+fn device_properties() -> vk::PhysicalDeviceProperties {
+    vk::PhysicalDeviceProperties {
+        vendor_id: 0x10de,
+        device_id: 0x1c82,
+        pipeline_cache_uuid: [7; vk::UUID_SIZE],
+        ..Default::default()
+    }
+}
+
+fn cache_data(properties: &vk::PhysicalDeviceProperties) -> Vec<u8> {
+    let mut data = vec![];
+    data.extend_from_slice(&32_u32.to_le_bytes());
+    data.extend_from_slice(&1_u32.to_le_bytes());
+    data.extend_from_slice(&properties.vendor_id.to_le_bytes());
+    data.extend_from_slice(&properties.device_id.to_le_bytes());
+    data.extend_from_slice(&properties.pipeline_cache_uuid);
+    // Driver specific payload.
+    data.extend_from_slice(&[1, 2, 3, 4]);
+    data
+}
+
+#[test]
+fn accepts_cache_from_same_device() {
+    let properties = device_properties();
+
+    assert!(is_compatible_cache_data(
+        &cache_data(&properties),
+        &properties
+    ));
+}
+
+#[test]
+fn rejects_cache_from_other_device_or_driver() {
+    let properties = device_properties();
+    let data = cache_data(&properties);
+
+    let other_vendor = vk::PhysicalDeviceProperties {
+        vendor_id: 0x1002,
+        ..properties
+    };
+    let other_device = vk::PhysicalDeviceProperties {
+        device_id: 0x1c83,
+        ..properties
+    };
+    let other_driver = vk::PhysicalDeviceProperties {
+        pipeline_cache_uuid: [8; vk::UUID_SIZE],
+        ..properties
+    };
+    assert!(!is_compatible_cache_data(&data, &other_vendor));
+    assert!(!is_compatible_cache_data(&data, &other_device));
+    assert!(!is_compatible_cache_data(&data, &other_driver));
+}
+
+#[test]
+fn rejects_truncated_or_malformed_cache() {
+    let properties = device_properties();
+    let data = cache_data(&properties);
+
+    assert!(!is_compatible_cache_data(&[], &properties));
+    assert!(!is_compatible_cache_data(&data[..31], &properties));
+
+    let mut unknown_version = data.clone();
+    unknown_version[4] = 2;
+    assert!(!is_compatible_cache_data(&unknown_version, &properties));
+
+    let mut oversized_header = data;
+    oversized_header[0] = 255;
+    assert!(!is_compatible_cache_data(&oversized_header, &properties));
+}
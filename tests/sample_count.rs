@@ -0,0 +1,34 @@
+use ash::vk;
+use rust_game::utility::share::pipeline::clamp_sample_count;
+
+#[test]
+fn picks_highest_supported_count_up_to_request() {
+    let supported =
+        vk::SampleCountFlags::TYPE_1 | vk::SampleCountFlags::TYPE_2 | vk::SampleCountFlags::TYPE_8;
+
+    assert_eq!(
+        clamp_sample_count(8, supported),
+        vk::SampleCountFlags::TYPE_8
+    );
+    // 4x is missing, so it drops to the next lower count instead of rounding up.
+    assert_eq!(
+        clamp_sample_count(4, supported),
+        vk::SampleCountFlags::TYPE_2
+    );
+    assert_eq!(
+        clamp_sample_count(64, supported),
+        vk::SampleCountFlags::TYPE_8
+    );
+}
+
+#[test]
+fn falls_back_to_single_sample() {
+    assert_eq!(
+        clamp_sample_count(4, vk::SampleCountFlags::TYPE_1),
+        vk::SampleCountFlags::TYPE_1
+    );
+    assert_eq!(
+        clamp_sample_count(0, vk::SampleCountFlags::all()),
+        vk::SampleCountFlags::TYPE_1
+    );
+}
@@ -0,0 +1,79 @@
+#![cfg(feature = "runtime-shaders")]
+
+mod common;
+
+use common::write_fixture;
+use rust_game::utility::tools::read_shader_code;
+use rust_game::Error;
+use std::path::Path;
+
+#[test]
+fn compiles_bundled_glsl_sources() {
+    for shader in &[
+        "shaders/shader.vert",
+        "shaders/shader.frag",
+        "shaders/mesh.vert",
+        "shaders/mesh.frag",
+    ] {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join(shader);
+        let code = read_shader_code(&path).unwrap_or_else(|err| panic!("{}", err));
+
+        // SPIR-V magic number, little-endian.
+        assert_eq!(&code[..4], &[0x03, 0x02, 0x23, 0x07]);
+        assert_eq!(code.len() % 4, 0);
+    }
+}
+
+#[test]
+fn reports_compile_errors_with_location() {
+    let path = write_fixture("shader_compiler", 
+        "broken.frag",
+        "#version 450\n\nlayout(location = 0) out vec4 outColor;\n\nvoid main() {\n    outColor = undefined_value;\n}\n",
+    );
+
+    match read_shader_code(&path) {
+        Err(Error::ShaderCompile {
+            path: error_path,
+            line,
+            ..
+        }) => {
+            assert_eq!(error_path, path);
+            assert_eq!(line, Some(6));
+        }
+        other => panic!("expected a shader compile error, got {:?}", other),
+    }
+}
+
+#[test]
+fn compiles_shadertoy_sources_with_prelude() {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("shaders/shadertoy/example.frag");
+    read_shader_code(&path).unwrap_or_else(|err| panic!("{}", err));
+
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("shaders/fullscreen.vert");
+    read_shader_code(&path).unwrap_or_else(|err| panic!("{}", err));
+}
+
+#[test]
+fn reports_shadertoy_errors_relative_to_pasted_source() {
+    let path = write_fixture("shader_compiler", 
+        "broken_shadertoy.frag",
+        "void mainImage(out vec4 fragColor, in vec2 fragCoord)\n{\n    fragColor = vec4(iTime, undefined_value, 0.0, 1.0);\n}\n",
+    );
+
+    match read_shader_code(&path) {
+        Err(Error::ShaderCompile { line, .. }) => assert_eq!(line, Some(3)),
+        other => panic!("expected a shader compile error, got {:?}", other),
+    }
+}
+
+#[test]
+fn compiles_multipass_example() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+    for shader in &[
+        "shaders/fullscreen_buffer.vert",
+        "shaders/shadertoy/feedback/buffer_a.frag",
+        "shaders/shadertoy/feedback/image.frag",
+    ] {
+        read_shader_code(&dir.join(shader)).unwrap_or_else(|err| panic!("{}", err));
+    }
+}
@@ -0,0 +1,84 @@
+mod common;
+
+use common::write_fixture;
+use rust_game::utility::shadertoy::{PassName, PassesDescription};
+use rust_game::Error;
+use std::path::Path;
+
+fn invalid_line(name: &str, source: &str) -> usize {
+    match PassesDescription::load(&write_fixture("shadertoy_passes", name, source)) {
+        Err(Error::InvalidPasses { line, .. }) => line,
+        other => panic!("expected an invalid description, got {:?}", other),
+    }
+}
+
+#[test]
+fn loads_bundled_feedback_example() {
+    let path =
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("shaders/shadertoy/feedback/feedback.passes");
+    let passes = PassesDescription::load(&path).unwrap_or_else(|err| panic!("{}", err));
+
+    assert_eq!(passes.buffers.len(), 1);
+    assert_eq!(passes.buffers[0].name, PassName::BufferA);
+    assert_eq!(passes.buffers[0].channels, [Some(0), None, None, None]);
+    assert_eq!(passes.image.channels, [Some(0), None, None, None]);
+    assert_eq!(
+        passes.image.shader_path,
+        path.parent().unwrap().join("image.frag")
+    );
+    for shader_path in passes.shader_paths() {
+        assert!(shader_path.exists(), "missing {:?}", shader_path);
+    }
+}
+
+#[test]
+fn resolves_channels_declared_later() {
+    let path = write_fixture("shadertoy_passes", 
+        "forward.passes",
+        "buffer_a a.frag buffer_b # reads last frame of B\nbuffer_b b.frag - buffer_a\nimage image.frag buffer_b\n",
+    );
+    let passes = PassesDescription::load(&path).unwrap();
+
+    assert_eq!(passes.buffers[0].channels, [Some(1), None, None, None]);
+    assert_eq!(passes.buffers[1].channels, [None, Some(0), None, None]);
+    assert_eq!(passes.image.channels, [Some(1), None, None, None]);
+}
+
+#[test]
+fn a_fragment_shader_is_a_lone_image_pass() {
+    let passes = PassesDescription::load(Path::new("shaders/shadertoy/example.frag")).unwrap();
+
+    assert!(passes.buffers.is_empty());
+    assert_eq!(
+        passes.image.shader_path,
+        Path::new("shaders/shadertoy/example.frag")
+    );
+}
+
+#[test]
+fn reports_invalid_descriptions_with_line() {
+    assert_eq!(
+        invalid_line("unknown.passes", "buffer_e e.frag\nimage i.frag\n"),
+        1
+    );
+    assert_eq!(
+        invalid_line(
+            "twice.passes",
+            "buffer_a a.frag\nbuffer_a a.frag\nimage i.frag\n"
+        ),
+        2
+    );
+    assert_eq!(
+        invalid_line("channel.passes", "# comment\nimage i.frag buffer_c\n"),
+        2
+    );
+    assert_eq!(
+        invalid_line("image_not_last.passes", "image i.frag\nbuffer_a a.frag\n"),
+        1
+    );
+    assert_eq!(invalid_line("no_image.passes", "buffer_a a.frag\n"), 1);
+    assert_eq!(
+        invalid_line("channels.passes", "image i.frag - - - - buffer_a\n"),
+        1
+    );
+}
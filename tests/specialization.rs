@@ -0,0 +1,104 @@
+mod common;
+
+use ash::vk;
+use common::write_fixture;
+use rust_game::utility::specialization::{SpecializationConfig, SpecializationValue};
+use rust_game::Error;
+use std::path::Path;
+
+#[test]
+fn loads_constants_per_stage() {
+    let path = write_fixture(
+        "specialization",
+        "valid.cfg",
+        "# stage id value\n\
+         fragment 2 0.5\n\
+         \n\
+         vertex   1 true  # trailing comment\n\
+         fragment 0 -3\n",
+    );
+    let config = SpecializationConfig::load(&path).unwrap_or_else(|err| panic!("{}", err));
+
+    assert_eq!(
+        config.vertex.iter().collect::<Vec<_>>(),
+        vec![(1, SpecializationValue::Bool(true))]
+    );
+    // Sorted by constant ID.
+    assert_eq!(
+        config
+            .constants(vk::ShaderStageFlags::FRAGMENT)
+            .iter()
+            .collect::<Vec<_>>(),
+        vec![
+            (0, SpecializationValue::Int(-3)),
+            (2, SpecializationValue::Float(0.5)),
+        ]
+    );
+    assert!(config.constants(vk::ShaderStageFlags::COMPUTE).is_empty());
+}
+
+#[test]
+fn bundled_config_is_valid() {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("shaders/specialization.cfg");
+
+    SpecializationConfig::load(&path).unwrap_or_else(|err| panic!("{}", err));
+}
+
+#[test]
+fn reports_invalid_lines() {
+    for (source, expected_line) in &[
+        ("fragment 0 1\ngeometry 0 1\n", 2),
+        ("vertex 0\n", 1),
+        ("\n\nvertex x 1\n", 3),
+        ("fragment 0 maybe\n", 1),
+        ("fragment 0 1\nfragment 0 2\n", 2),
+    ] {
+        let path = write_fixture("specialization", "invalid.cfg", source);
+        match SpecializationConfig::load(&path) {
+            Err(Error::InvalidSpecialization { line, .. }) => {
+                assert_eq!(line, *expected_line, "{:?}", source)
+            }
+            result => panic!("unexpected result {:?} for {:?}", result, source),
+        }
+    }
+}
+
+#[test]
+fn packs_four_bytes_per_constant() {
+    let path = write_fixture(
+        "specialization",
+        "packed.cfg",
+        "vertex 7 2.0\nvertex 3 false\n",
+    );
+    let config = SpecializationConfig::load(&path).unwrap();
+    let (map_entries, data) = config.vertex.map_entries_and_data();
+
+    let entries: Vec<_> = map_entries
+        .iter()
+        .map(|entry| (entry.constant_id, entry.offset, entry.size))
+        .collect();
+    assert_eq!(entries, vec![(3, 0, 4), (7, 4, 4)]);
+    assert_eq!(&data[..4], &0_u32.to_ne_bytes());
+    assert_eq!(&data[4..], &2.0_f32.to_ne_bytes());
+}
+
+#[cfg(feature = "runtime-shaders")]
+#[test]
+fn bakes_constants_into_compiled_shader() {
+    use rust_game::utility::tools::{read_shader_code, read_specialized_shader_code};
+
+    let shader_path = Path::new(env!("CARGO_MANIFEST_DIR")).join("shaders/mesh.frag");
+    let config_path = write_fixture(
+        "specialization",
+        "debug_view.cfg",
+        "fragment 0 1\n# not declared\nfragment 9 1\n",
+    );
+    let config = SpecializationConfig::load(&config_path).unwrap();
+
+    let default = read_shader_code(&shader_path).unwrap_or_else(|err| panic!("{}", err));
+    let specialized = read_specialized_shader_code(&shader_path, &config.fragment)
+        .unwrap_or_else(|err| panic!("{}", err));
+
+    assert_eq!(&specialized[..4], &[0x03, 0x02, 0x23, 0x07]);
+    assert_ne!(default, specialized);
+}
@@ -0,0 +1,131 @@
+mod common;
+
+use ash::vk;
+use common::write_fixture;
+use rust_game::utility::debug::{DebugSinkKind, ValidationInfo};
+use rust_game::Error;
+
+fn args(args: &[&str]) -> Vec<String> {
+    args.iter().map(|arg| arg.to_string()).collect()
+}
+
+#[test]
+fn defaults_to_warnings_and_errors_of_the_khronos_layer() {
+    let validation = ValidationInfo::default();
+
+    assert!(validation.is_enable);
+    assert_eq!(
+        validation.required_validation_layers,
+        vec!["VK_LAYER_KHRONOS_validation"]
+    );
+    assert_eq!(
+        validation.message_severity,
+        vk::DebugUtilsMessageSeverityFlagsEXT::WARNING
+            | vk::DebugUtilsMessageSeverityFlagsEXT::ERROR
+    );
+    assert!(!ValidationInfo::disabled().is_enable);
+}
+
+#[test]
+fn loads_config_file() {
+    let path = write_fixture(
+        "validation",
+        "valid.cfg",
+        "# name value\n\
+         validation off\n\
+         severity   verbose, error  # trailing comment\n\
+         \n\
+         types      validation\n\
+         layers     VK_LAYER_LUNARG_api_dump,VK_LAYER_KHRONOS_validation\n",
+    );
+    let mut validation = ValidationInfo::default();
+    validation
+        .load(&path)
+        .unwrap_or_else(|err| panic!("{}", err));
+
+    assert!(!validation.is_enable);
+    assert_eq!(
+        validation.message_severity,
+        vk::DebugUtilsMessageSeverityFlagsEXT::VERBOSE
+            | vk::DebugUtilsMessageSeverityFlagsEXT::ERROR
+    );
+    assert_eq!(
+        validation.message_type,
+        vk::DebugUtilsMessageTypeFlagsEXT::VALIDATION
+    );
+    // The Khronos layer is not added twice.
+    assert_eq!(
+        validation.required_validation_layers,
+        vec!["VK_LAYER_KHRONOS_validation", "VK_LAYER_LUNARG_api_dump"]
+    );
+}
+
+#[test]
+fn command_line_overrides_config_file() {
+    let path = write_fixture(
+        "validation",
+        "override.cfg",
+        "validation off\nseverity info\n",
+    );
+    let validation = ValidationInfo::resolve(
+        Some(&path),
+        &args(&[
+            "main",
+            "--validation",
+            "on",
+            "--validation-types",
+            "general,performance",
+            "--validation-sink",
+            "log",
+            "--validation-fail-on-error",
+            "on",
+        ]),
+    )
+    .unwrap_or_else(|err| panic!("{}", err));
+
+    assert!(validation.is_enable);
+    assert!(validation.fail_on_error);
+    assert_eq!(validation.sink, DebugSinkKind::Log);
+    assert_eq!(
+        validation.message_severity,
+        vk::DebugUtilsMessageSeverityFlagsEXT::INFO
+    );
+    assert_eq!(
+        validation.message_type,
+        vk::DebugUtilsMessageTypeFlagsEXT::GENERAL | vk::DebugUtilsMessageTypeFlagsEXT::PERFORMANCE
+    );
+}
+
+#[test]
+fn reports_invalid_settings() {
+    let path = write_fixture(
+        "validation",
+        "invalid.cfg",
+        "validation on\nseverity warning, loud\n",
+    );
+    match ValidationInfo::default().load(&path) {
+        Err(Error::InvalidValidationSetting { origin, reason }) => {
+            assert!(origin.ends_with("invalid.cfg:2"), "{}", origin);
+            assert_eq!(reason, "unknown severity 'loud'");
+        }
+        other => panic!("expected an invalid setting, got {:?}", other),
+    }
+
+    let err = ValidationInfo::default()
+        .apply_args(&args(&["main", "--validation", "maybe"]))
+        .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Invalid validation setting in --validation: expected 'on' or 'off', got 'maybe'"
+    );
+    assert!(ValidationInfo::default()
+        .apply_args(&args(&["main", "--validation-layers"]))
+        .is_err());
+    let err = ValidationInfo::default()
+        .apply_args(&args(&["main", "--validation-layers", "VK_LAYER\0x"]))
+        .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Invalid validation setting in --validation-layers: layer 'VK_LAYER\\0x' contains a NUL byte"
+    );
+}
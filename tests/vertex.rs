@@ -0,0 +1,60 @@
+use ash::vk;
+use cgmath::Vector3;
+use rust_game::utility::structures::VertexV2;
+use rust_game::utility::vertex::Vertex;
+
+#[repr(C)]
+#[derive(Clone, Copy, Vertex)]
+struct InstancedVertex {
+    #[location(0)]
+    position: Vector3<f32>,
+    #[location(3)]
+    weight: f32,
+    #[location(1)]
+    material: u32,
+}
+
+#[test]
+fn derives_descriptions_from_field_types_and_locations() {
+    let bindings = InstancedVertex::binding_descriptions();
+    assert_eq!(bindings.len(), 1);
+    assert_eq!(bindings[0].binding, 0);
+    assert_eq!(bindings[0].stride, 20);
+    assert_eq!(bindings[0].input_rate, vk::VertexInputRate::VERTEX);
+
+    let attributes: Vec<(u32, vk::Format, u32)> = InstancedVertex::attribute_descriptions()
+        .iter()
+        .map(|attribute| (attribute.location, attribute.format, attribute.offset))
+        .collect();
+    assert_eq!(
+        attributes,
+        [
+            (0, vk::Format::R32G32B32_SFLOAT, 0),
+            (3, vk::Format::R32_SFLOAT, 12),
+            (1, vk::Format::R32_UINT, 16),
+        ]
+    );
+}
+
+#[test]
+fn bundled_vertices_match_their_shader_inputs() {
+    let attributes: Vec<(u32, vk::Format, u32)> = VertexV2::attribute_descriptions()
+        .iter()
+        .map(|attribute| (attribute.location, attribute.format, attribute.offset))
+        .collect();
+    assert_eq!(
+        attributes,
+        [
+            (0, vk::Format::R32G32_SFLOAT, 0),
+            (1, vk::Format::R32G32B32_SFLOAT, 8),
+            (2, vk::Format::R32G32_SFLOAT, 20),
+        ]
+    );
+    assert_eq!(VertexV2::binding_descriptions()[0].stride, 28);
+}
+
+#[test]
+fn unit_has_no_vertex_input() {
+    assert!(<()>::binding_descriptions().is_empty());
+    assert!(<()>::attribute_descriptions().is_empty());
+}